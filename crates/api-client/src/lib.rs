@@ -0,0 +1,371 @@
+//! Async client for the board API. Every method maps to one endpoint; non-2xx
+//! responses are decoded into [`ApiError`] so callers can match on `code`.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), api_client::Error> {
+//! use api_client::{types::CreateGroupRequest, Client};
+//!
+//! let client = Client::new("http://127.0.0.1:8080");
+//! let group = client
+//!     .create_group(&CreateGroupRequest { group_name: "planning".into(), created_by: 1 })
+//!     .await?;
+//! let notes = client.list_notes(group.id, &Default::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use api_types as types;
+pub use reqwest::StatusCode;
+
+use api_types::{
+    AccountSummary, AccountsResponse, BackupFile, BackupsResponse, BatchReport, BatchRequest,
+    ChangePasswordRequest, ChangesParams, ClearResponse, CreateAccountRequest, CreateGroupRequest,
+    CreateNoteRequest, CreateNoteResponse, DeleteAccountRequest, DeleteAccountResponse, EmailRequest,
+    ErrorBody, FieldIssue, GroupMembersResponse, GroupSummary, GroupsResponse, JoinGroupRequest,
+    LoginRequest, LoginResponse, NoteChangesResponse, NoteListParams, NotesResponse, PageParams,
+    ResetPasswordRequest, UpdateAccountRequest, UpdateNoteContentRequest, UpdateNotePositionRequest,
+    UserLookupParams, UserLookupResponse, VerifyEmailRequest,
+};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error response from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// The server's error code (`group_not_found`, `email_invalid`, ...), or
+    /// `unexpected_response` when the body was not an error document.
+    pub code: String,
+    pub message: String,
+    /// The server's request ID, for matching the error with its logs.
+    pub request_id: Option<String>,
+    /// Every invalid field when the request failed validation.
+    pub fields: Vec<FieldIssue>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status.as_u16(), self.code, self.message)?;
+        if let Some(id) = &self.request_id {
+            write!(f, " (request {id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Debug)]
+pub enum Error {
+    Api(ApiError),
+    /// Connection failures and undecodable success bodies.
+    Http(reqwest::Error),
+}
+
+impl Error {
+    /// The server's error code, if the request reached the server.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Api(e) => Some(&e.code),
+            Self::Http(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(e) => e.fmt(f),
+            Self::Http(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Api(e) => Some(e),
+            Self::Http(e) => Some(e),
+        }
+    }
+}
+
+impl From<ApiError> for Error {
+    fn from(e: ApiError) -> Self {
+        Self::Api(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    admin_token: Option<String>,
+    session_token: Option<String>,
+    language: Option<String>,
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `http://127.0.0.1:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { http, base_url, admin_token: None, session_token: None, language: None }
+    }
+
+    /// Sent as a bearer token to the `/api/admin/*` endpoints and
+    /// [`Client::list_accounts`].
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// The `token` of [`Client::login`], sent as a bearer token to the
+    /// endpoints acting as the signed-in account.
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Sent as `Accept-Language`, e.g. `en` for English error messages
+    /// (Japanese by default). Error codes are the same in every language.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    // Accounts --------------------------------------------------------
+
+    pub async fn list_accounts(&self, params: &PageParams) -> Result<AccountsResponse> {
+        self.json(self.admin(Method::GET, "/api/accounts").query(params)).await
+    }
+
+    pub async fn current_account(&self) -> Result<AccountSummary> {
+        self.json(self.session(Method::GET, "/api/accounts/me")).await
+    }
+
+    pub async fn lookup_users(&self, params: &UserLookupParams) -> Result<UserLookupResponse> {
+        self.json(self.session(Method::GET, "/api/accounts/lookup").query(params)).await
+    }
+
+    pub async fn create_account(&self, req: &CreateAccountRequest) -> Result<AccountSummary> {
+        self.json(self.request(Method::POST, "/api/accounts").json(req)).await
+    }
+
+    pub async fn update_account(&self, account_id: i64, req: &UpdateAccountRequest) -> Result<AccountSummary> {
+        self.json(self.session(Method::PATCH, &format!("/api/accounts/{account_id}")).json(req)).await
+    }
+
+    pub async fn change_password(&self, account_id: i64, req: &ChangePasswordRequest) -> Result<()> {
+        self.empty(self.session(Method::PUT, &format!("/api/accounts/{account_id}/password")).json(req))
+            .await
+    }
+
+    pub async fn delete_account(&self, account_id: i64, current_password: &str) -> Result<DeleteAccountResponse> {
+        let req = DeleteAccountRequest { current_password: current_password.to_string() };
+        self.json(self.session(Method::DELETE, &format!("/api/accounts/{account_id}")).json(&req))
+            .await
+    }
+
+    pub async fn list_groups_for_user(&self, account_id: i64) -> Result<GroupsResponse> {
+        self.json(self.request(Method::GET, &format!("/api/accounts/{account_id}/groups")))
+            .await
+    }
+
+    // Auth ------------------------------------------------------------
+
+    /// Pass the returned token to [`Client::with_session_token`].
+    pub async fn login(&self, email: &str, password: &str) -> Result<LoginResponse> {
+        let req = LoginRequest { email: email.to_string(), password: password.to_string() };
+        self.json(self.request(Method::POST, "/api/auth/login").json(&req)).await
+    }
+
+    pub async fn logout(&self) -> Result<()> {
+        self.empty(self.session(Method::POST, "/api/auth/logout")).await
+    }
+
+    /// `token` is the code from the verification mail.
+    pub async fn verify_email(&self, token: &str) -> Result<()> {
+        let req = VerifyEmailRequest { token: token.to_string() };
+        self.empty(self.request(Method::POST, "/api/auth/verify-email").json(&req)).await
+    }
+
+    /// Succeeds whether or not an account has this address.
+    pub async fn resend_verification(&self, email: &str) -> Result<()> {
+        let req = EmailRequest { email: email.to_string() };
+        self.empty(self.request(Method::POST, "/api/auth/verify-email/resend").json(&req)).await
+    }
+
+    /// Succeeds whether or not an account has this address.
+    pub async fn request_password_reset(&self, email: &str) -> Result<()> {
+        let req = EmailRequest { email: email.to_string() };
+        self.empty(self.request(Method::POST, "/api/auth/password-reset").json(&req)).await
+    }
+
+    pub async fn reset_password(&self, req: &ResetPasswordRequest) -> Result<()> {
+        self.empty(self.request(Method::POST, "/api/auth/password-reset/confirm").json(req)).await
+    }
+
+    // Groups ----------------------------------------------------------
+
+    pub async fn create_group(&self, req: &CreateGroupRequest) -> Result<GroupSummary> {
+        self.json(self.request(Method::POST, "/api/groups").json(req)).await
+    }
+
+    pub async fn get_group(&self, group_id: i64) -> Result<GroupSummary> {
+        self.json(self.request(Method::GET, &format!("/api/groups/{group_id}"))).await
+    }
+
+    pub async fn list_group_members(
+        &self,
+        group_id: i64,
+        params: &PageParams,
+    ) -> Result<GroupMembersResponse> {
+        let path = format!("/api/groups/{group_id}/users");
+        self.json(self.request(Method::GET, &path).query(params)).await
+    }
+
+    pub async fn add_user_to_group(&self, group_id: i64, req: &JoinGroupRequest) -> Result<()> {
+        let path = format!("/api/groups/{group_id}/users");
+        self.empty(self.request(Method::POST, &path).json(req)).await
+    }
+
+    // Notes -----------------------------------------------------------
+
+    pub async fn list_notes(&self, group_id: i64, params: &NoteListParams) -> Result<NotesResponse> {
+        let path = format!("/api/groups/{group_id}/notes");
+        self.json(self.request(Method::GET, &path).query(params)).await
+    }
+
+    pub async fn create_note(&self, group_id: i64, req: &CreateNoteRequest) -> Result<CreateNoteResponse> {
+        let path = format!("/api/groups/{group_id}/notes");
+        self.json(self.request(Method::POST, &path).json(req)).await
+    }
+
+    pub async fn clear_notes(&self, group_id: i64) -> Result<ClearResponse> {
+        self.json(self.request(Method::DELETE, &format!("/api/groups/{group_id}/notes")))
+            .await
+    }
+
+    /// Pass `None` for a full sync, then the previous response's `cursor`.
+    pub async fn note_changes(&self, group_id: i64, since: Option<&str>) -> Result<NoteChangesResponse> {
+        let path = format!("/api/groups/{group_id}/notes/changes");
+        let params = ChangesParams { since: since.map(str::to_string) };
+        self.json(self.request(Method::GET, &path).query(&params)).await
+    }
+
+    /// A batch the server rejected is returned as `Ok` with `applied: false`,
+    /// since its report says which ops failed.
+    pub async fn batch_notes(&self, group_id: i64, req: &BatchRequest) -> Result<BatchReport> {
+        let path = format!("/api/groups/{group_id}/notes/batch");
+        let res = self.request(Method::POST, &path).json(req).send().await?;
+        if res.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            let bytes = res.bytes().await?;
+            if let Ok(report) = serde_json::from_slice::<BatchReport>(&bytes) {
+                return Ok(report);
+            }
+            return Err(decode_error(StatusCode::UNPROCESSABLE_ENTITY, &bytes).into());
+        }
+        Ok(check(res).await?.json().await?)
+    }
+
+    pub async fn update_note_position(&self, note_id: i64, req: &UpdateNotePositionRequest) -> Result<()> {
+        let path = format!("/api/notes/{note_id}/position");
+        self.empty(self.request(Method::PATCH, &path).json(req)).await
+    }
+
+    pub async fn update_note_content(&self, note_id: i64, req: &UpdateNoteContentRequest) -> Result<()> {
+        self.empty(self.request(Method::PATCH, &format!("/api/notes/{note_id}")).json(req))
+            .await
+    }
+
+    pub async fn delete_note(&self, note_id: i64) -> Result<()> {
+        self.empty(self.request(Method::DELETE, &format!("/api/notes/{note_id}"))).await
+    }
+
+    // Admin -----------------------------------------------------------
+
+    pub async fn list_backups(&self) -> Result<BackupsResponse> {
+        self.json(self.admin(Method::GET, "/api/admin/backups")).await
+    }
+
+    pub async fn create_backup(&self) -> Result<BackupFile> {
+        self.json(self.admin(Method::POST, "/api/admin/backups")).await
+    }
+
+    // Plumbing --------------------------------------------------------
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.http.request(method, format!("{}{path}", self.base_url));
+        match &self.language {
+            Some(language) => req.header(reqwest::header::ACCEPT_LANGUAGE, language),
+            None => req,
+        }
+    }
+
+    fn admin(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.request(method, path);
+        match &self.admin_token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    fn session(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.request(method, path);
+        match &self.session_token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    async fn json<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T> {
+        Ok(check(req.send().await?).await?.json().await?)
+    }
+
+    async fn empty(&self, req: RequestBuilder) -> Result<()> {
+        check(req.send().await?).await?;
+        Ok(())
+    }
+}
+
+async fn check(res: Response) -> Result<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let bytes = res.bytes().await?;
+    Err(decode_error(status, &bytes).into())
+}
+
+fn decode_error(status: StatusCode, body: &[u8]) -> ApiError {
+    match serde_json::from_slice::<ErrorBody>(body) {
+        Ok(body) => ApiError {
+            status,
+            code: body.code,
+            message: body.message,
+            request_id: body.request_id,
+            fields: body.fields,
+        },
+        // axum's own rejections (malformed JSON, unknown route) are plain text.
+        Err(_) => ApiError {
+            status,
+            code: "unexpected_response".to_string(),
+            message: String::from_utf8_lossy(body).into_owned(),
+            request_id: None,
+            fields: Vec::new(),
+        },
+    }
+}
@@ -0,0 +1,516 @@
+//! Request and response bodies of the HTTP API, shared by the server
+//! (`src/api.rs`) and `api-client`. Timestamps are `YYYY-MM-DD HH:MM:SS` UTC
+//! strings, as stored in the database.
+
+use serde::{Deserialize, Serialize};
+
+// -------------------------------------------------------------------
+// Accounts
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AccountSummary {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    /// Preferred language of messages, `ja` or `en`.
+    pub locale: Option<String>,
+    /// Whether the address was confirmed through the verification mail.
+    pub email_verified: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AccountsResponse {
+    pub accounts: Vec<AccountSummary>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateAccountRequest {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    /// `ja` or `en`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+}
+
+/// What `GET /api/accounts/lookup` reveals about another account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserProfile {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserLookupResponse {
+    pub users: Vec<UserProfile>,
+}
+
+/// Give exactly one: `email` matches one account exactly, `q` searches the
+/// names of people sharing a group with the caller.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct UserLookupParams {
+    pub email: Option<String>,
+    pub q: Option<String>,
+}
+
+/// Body of `PATCH /api/accounts/:id`; absent fields are left unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateAccountRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// A new address must be verified again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Required when `email` changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_password: Option<String>,
+}
+
+/// Body of `PUT /api/accounts/:id/password`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Body of `DELETE /api/accounts/:id`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteAccountRequest {
+    pub current_password: String,
+}
+
+/// What became of the groups the deleted account had created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteAccountResponse {
+    /// Now owned by another member.
+    pub transferred_groups: Vec<i64>,
+    /// Deleted with their notes, as they had no other members.
+    pub deleted_groups: Vec<i64>,
+}
+
+/// Body of `POST /api/auth/login`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginResponse {
+    /// Send as `Authorization: Bearer <token>`.
+    pub token: String,
+    pub account: AccountSummary,
+}
+
+/// Body of `POST /api/auth/verify-email`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VerifyEmailRequest {
+    /// The code from the verification mail.
+    pub token: String,
+}
+
+/// Body of `POST /api/auth/verify-email/resend` and
+/// `POST /api/auth/password-reset`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmailRequest {
+    pub email: String,
+}
+
+/// Body of `POST /api/auth/password-reset/confirm`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResetPasswordRequest {
+    /// The code from the password reset mail.
+    pub token: String,
+    pub password: String,
+}
+
+// -------------------------------------------------------------------
+// Groups
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupSummary {
+    pub id: i64,
+    pub group_name: String,
+    pub created_by: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupMembership {
+    pub id: i64,
+    pub group_name: String,
+    pub created_by: i64,
+    pub created_at: String,
+    /// `owner` or `member`.
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupsResponse {
+    pub groups: Vec<GroupMembership>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupMember {
+    pub id: i64,
+    pub group_id: i64,
+    pub user_id: i64,
+    pub role: String,
+    pub joined_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupMembersResponse {
+    pub members: Vec<GroupMember>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateGroupRequest {
+    pub group_name: String,
+    pub created_by: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct JoinGroupRequest {
+    pub user_id: i64,
+    /// `owner` or `member` (default).
+    pub role: Option<String>,
+}
+
+// -------------------------------------------------------------------
+// Notes
+
+/// A note as seen by one group.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Note {
+    pub id: i64,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    /// `#RRGGBB`.
+    pub color: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub z_index: i64,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub group_id: i64,
+    pub can_edit: bool,
+    pub shared_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NotesResponse {
+    pub notes: Vec<Note>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NoteChangesResponse {
+    /// Created or updated notes, in board stacking order.
+    pub notes: Vec<Note>,
+    /// IDs of notes removed from the group.
+    pub deleted: Vec<i64>,
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNoteRequest {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    /// Palette name or `#RRGGBB`; defaults to yellow.
+    pub color: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub z_index: Option<i64>,
+    pub created_by: Option<i64>,
+    pub can_edit: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNoteResponse {
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateNotePositionRequest {
+    pub x: f64,
+    pub y: f64,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub z_index: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateNoteContentRequest {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClearResponse {
+    pub removed: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchRequest {
+    pub ops: Vec<BatchOpRequest>,
+}
+
+/// Each op carries the same fields as the single-note endpoint it mirrors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOpRequest {
+    Create(CreateNoteRequest),
+    UpdatePosition {
+        note_id: i64,
+        x: f64,
+        y: f64,
+        width: Option<f64>,
+        height: Option<f64>,
+        z_index: Option<i64>,
+    },
+    UpdateContent {
+        note_id: i64,
+        title: Option<String>,
+        content: Option<String>,
+        color: Option<String>,
+    },
+    Delete {
+        note_id: i64,
+    },
+}
+
+impl BatchOpRequest {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Create(_) => "create",
+            Self::UpdatePosition { .. } => "update_position",
+            Self::UpdateContent { .. } => "update_content",
+            Self::Delete { .. } => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchReport {
+    pub applied: bool,
+    pub results: Vec<BatchOpResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchOpResult {
+    pub index: usize,
+    pub op: String,
+    /// `ok`, `error`, or `skipped` when another op made the batch fail.
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ImportIssue>,
+}
+
+// -------------------------------------------------------------------
+// Boards
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportReport {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportRowResult {
+    /// The line a CSV record starts on (the header is line 1), or the 1-based
+    /// position of a JSON entry.
+    pub row: usize,
+    /// `accepted` or `rejected`.
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ImportIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportIssue {
+    pub code: String,
+    pub message: String,
+    /// The offending field (`title`, `x`, ...), when the issue is about one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl ImportIssue {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { code: code.into(), message: message.into(), field: None }
+    }
+}
+
+// -------------------------------------------------------------------
+// Admin & debug
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BackupFile {
+    pub file_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BackupsResponse {
+    pub backups: Vec<BackupFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DebugInfo {
+    pub database_url: String,
+    pub db_file_path: Option<String>,
+    pub file_exists: bool,
+    pub file_size: Option<u64>,
+    pub total_notes: i64,
+}
+
+/// `/healthz`: the process is up and serving requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HealthStatus {
+    pub status: String,
+}
+
+/// `/readyz`: `status` is `ready` when every required check passed, and
+/// `not_ready` (with HTTP 503) otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadinessReport {
+    pub status: String,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadinessCheck {
+    /// `database`, `migrations` or `llm`.
+    pub name: String,
+    /// `ok`, `failed` or `skipped`.
+    pub status: String,
+    /// A failed optional check is reported but does not make the server
+    /// unready.
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Body of every non-2xx response except a rejected batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorBody {
+    /// Stable machine-readable code such as `group_not_found`.
+    pub code: String,
+    pub message: String,
+    /// Same as the `X-Request-Id` response header; quote it when reporting a
+    /// problem so the matching server logs can be found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Every invalid field of a rejected request, so all of them can be
+    /// highlighted at once. With a single entry `code` and `message` are that
+    /// field's; with several, `code` is `validation_failed`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FieldIssue {
+    /// Request field path: `name`, `ops`, `limit`, ...
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// -------------------------------------------------------------------
+// Query parameters
+
+/// Common list parameters. `sort` takes a field name, prefixed with `-` for
+/// descending order; `cursor` is the `next_cursor` of the previous page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct PageParams {
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct NoteListParams {
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+    pub author: Option<i64>,
+    pub color: Option<String>,
+    pub updated_since: Option<String>,
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ChangesParams {
+    /// `cursor` of the previous response; omit for a full sync.
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ExportQuery {
+    /// `json` (default), `csv` or `markdown`.
+    pub format: Option<String>,
+}
@@ -74,7 +74,7 @@ export default function BoardPage() {
   const handleSubmit = async (event) => {
     event.preventDefault();
     if (!accountId) {
-      setError('アカウントを選択してください');
+      setError('ログインしてください');
       return;
     }
     if (!numericGroupId) return;
@@ -221,7 +221,7 @@ export default function GroupsPage() {
 
         <div style={styles.bottomLinks}>
           <button type="button" onClick={() => router.push('/')} style={styles.linkButton}>
-            ← アカウント選択に戻る
+            ← ログイン画面に戻る
           </button>
         </div>
       </section>
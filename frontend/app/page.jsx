@@ -5,49 +5,57 @@ import { useRouter } from 'next/navigation';
 
 const API_BASE = process.env.NEXT_PUBLIC_API_BASE_URL ?? 'http://localhost:5085';
 const STORAGE_KEY = 'sticky_account_id';
+const SESSION_KEY = 'sticky_session_token';
 
-function readStoredAccountId() {
+function readSession() {
   if (typeof window === 'undefined') return null;
-  const raw = window.localStorage.getItem(STORAGE_KEY);
-  if (!raw) return null;
-  const id = Number(raw);
-  return Number.isFinite(id) ? id : null;
+  return window.localStorage.getItem(SESSION_KEY);
 }
 
-function storeAccountId(id) {
+function storeSession(token, accountId) {
   if (typeof window === 'undefined') return;
-  window.localStorage.setItem(STORAGE_KEY, String(id));
+  window.localStorage.setItem(SESSION_KEY, token);
+  window.localStorage.setItem(STORAGE_KEY, String(accountId));
 }
 
-function clearAccountId() {
+function clearSession() {
   if (typeof window === 'undefined') return;
+  window.localStorage.removeItem(SESSION_KEY);
   window.localStorage.removeItem(STORAGE_KEY);
 }
 
 export default function AccountPage() {
   const router = useRouter();
-  const [accounts, setAccounts] = useState([]);
+  const [account, setAccount] = useState(null);
   const [loading, setLoading] = useState(true);
   const [status, setStatus] = useState('');
   const [error, setError] = useState('');
+  const [login, setLogin] = useState({ email: '', password: '' });
   const [form, setForm] = useState({ name: '', email: '', password: '' });
 
   useEffect(() => {
     let mounted = true;
     async function load() {
+      const token = readSession();
+      if (!token) {
+        setLoading(false);
+        return;
+      }
       try {
-        setLoading(true);
-        const res = await fetch(`${API_BASE}/api/accounts`);
+        const res = await fetch(`${API_BASE}/api/accounts/me`, {
+          headers: { Authorization: `Bearer ${token}` },
+        });
+        if (res.status === 401) {
+          clearSession();
+          if (mounted) setStatus('ログインの有効期限が切れました。もう一度ログインしてください。');
+          return;
+        }
         if (!res.ok) throw await parseError(res);
         const data = await res.json();
         if (!mounted) return;
-        setAccounts(data.accounts ?? []);
-        setStatus(`アカウント数: ${(data.accounts ?? []).length}`);
+        storeSession(token, data.id);
+        setAccount(data);
         setError('');
-        const stored = readStoredAccountId();
-        if (stored) {
-          router.push('/groups');
-        }
       } catch (err) {
         console.error(err);
         if (!mounted) return;
@@ -61,7 +69,36 @@ export default function AccountPage() {
     return () => {
       mounted = false;
     };
-  }, [router]);
+  }, []);
+
+  const signIn = async (email, password) => {
+    const res = await fetch(`${API_BASE}/api/auth/login`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ email, password }),
+    });
+    if (!res.ok) throw await parseError(res);
+    const data = await res.json();
+    storeSession(data.token, data.account.id);
+    setAccount(data.account);
+    return data.account;
+  };
+
+  const handleLogin = async (event) => {
+    event.preventDefault();
+    setError('');
+    setStatus('ログイン中...');
+    try {
+      const signedIn = await signIn(login.email, login.password);
+      setLogin({ email: '', password: '' });
+      setStatus(`おかえりなさい、${signedIn.name} さん！`);
+      router.push('/groups');
+    } catch (err) {
+      console.error(err);
+      setError(err.message);
+      setStatus('');
+    }
+  };
 
   const handleSubmit = async (event) => {
     event.preventDefault();
@@ -74,11 +111,10 @@ export default function AccountPage() {
         body: JSON.stringify(form),
       });
       if (!res.ok) throw await parseError(res);
-      const account = await res.json();
-      setAccounts((prev) => [...prev, account]);
-      storeAccountId(account.id);
+      const created = await res.json();
+      await signIn(form.email, form.password);
       setForm({ name: '', email: '', password: '' });
-      setStatus(`ようこそ、${account.name} さん！`);
+      setStatus(`ようこそ、${created.name} さん！`);
       setError('');
       router.push('/groups');
     } catch (err) {
@@ -88,51 +124,77 @@ export default function AccountPage() {
     }
   };
 
-  const handleSelectAccount = (id) => {
-    storeAccountId(id);
-    router.push('/groups');
-  };
-
-  const handleReset = () => {
-    clearAccountId();
-    setStatus('選択済みアカウントをクリアしました。');
+  const handleLogout = async () => {
+    const token = readSession();
+    clearSession();
+    setAccount(null);
+    setStatus('ログアウトしました。');
+    if (!token) return;
+    try {
+      await fetch(`${API_BASE}/api/auth/logout`, {
+        method: 'POST',
+        headers: { Authorization: `Bearer ${token}` },
+      });
+    } catch (err) {
+      console.error(err);
+    }
   };
 
   return (
     <main style={styles.main}>
       <section style={styles.card}>
-        <h1 style={styles.heading}>アカウントを選択 / 作成</h1>
+        <h1 style={styles.heading}>ログイン / アカウント作成</h1>
         <p style={styles.lead}>
-          まずは共有に使うアカウントを選んでください。既存のアカウントを選ぶか、新しく作成できます。
+          登録済みのメールアドレスとパスワードでログインするか、新しくアカウントを作成してください。
         </p>
 
-        <div style={styles.panel}>
-          <div style={{ display: 'flex', justifyContent: 'space-between', alignItems: 'center' }}>
-            <h2 style={styles.subheading}>既存アカウント</h2>
-            <button type="button" onClick={handleReset} style={styles.linkButton}>
-              選択解除
-            </button>
+        {loading ? (
+          <div style={styles.message}>読み込み中...</div>
+        ) : account ? (
+          <div style={styles.panel}>
+            <div style={styles.listItem}>
+              <div>
+                <div style={{ fontWeight: 600 }}>{account.name}</div>
+                <div style={styles.muted}>{account.email}</div>
+              </div>
+              <button type="button" onClick={handleLogout} style={styles.linkButton}>
+                ログアウト
+              </button>
+            </div>
           </div>
-          {loading ? (
-            <div style={styles.message}>読み込み中...</div>
-          ) : accounts.length === 0 ? (
-            <div style={styles.message}>まだアカウントがありません。下のフォームから作成してください。</div>
-          ) : (
-            <ul style={styles.list}>
-              {accounts.map((account) => (
-                <li key={account.id} style={styles.listItem}>
-                  <div>
-                    <div style={{ fontWeight: 600 }}>{account.name}</div>
-                    <div style={styles.muted}>{account.email}</div>
-                  </div>
-                  <button style={styles.primaryButton} onClick={() => handleSelectAccount(account.id)}>
-                    これを使う
-                  </button>
-                </li>
-              ))}
-            </ul>
-          )}
-        </div>
+        ) : (
+          <form onSubmit={handleLogin} style={{ ...styles.form, ...styles.panel }}>
+            <h2 style={styles.subheading}>ログイン</h2>
+            <label style={styles.label}>
+              メールアドレス
+              <input
+                style={styles.input}
+                type="email"
+                name="email"
+                autoComplete="email"
+                value={login.email}
+                onChange={(e) => setLogin((prev) => ({ ...prev, email: e.target.value }))}
+                required
+                placeholder="you@example.com"
+              />
+            </label>
+            <label style={styles.label}>
+              パスワード
+              <input
+                style={styles.input}
+                type="password"
+                name="password"
+                autoComplete="current-password"
+                value={login.password}
+                onChange={(e) => setLogin((prev) => ({ ...prev, password: e.target.value }))}
+                required
+              />
+            </label>
+            <button type="submit" style={{ ...styles.primaryButton, width: '100%' }}>
+              ログイン
+            </button>
+          </form>
+        )}
 
         <form onSubmit={handleSubmit} style={styles.form}>
           <h2 style={styles.subheading}>新規アカウント作成</h2>
@@ -166,6 +228,7 @@ export default function AccountPage() {
               style={styles.input}
               type="password"
               name="password"
+              autoComplete="new-password"
               minLength={6}
               value={form.password}
               onChange={(e) => setForm((prev) => ({ ...prev, password: e.target.value }))}
@@ -179,11 +242,13 @@ export default function AccountPage() {
         </form>
 
         <Status status={status} error={error} />
-        <div style={styles.bottomLink}>
-          <button type="button" style={styles.linkButton} onClick={() => router.push('/groups')}>
-            グループ一覧へ進む →
-          </button>
-        </div>
+        {account && (
+          <div style={styles.bottomLink}>
+            <button type="button" style={styles.linkButton} onClick={() => router.push('/groups')}>
+              グループ一覧へ進む →
+            </button>
+          </div>
+        )}
       </section>
     </main>
   );
@@ -254,13 +319,6 @@ const styles = {
     display: 'grid',
     gap: 12,
   },
-  list: {
-    listStyle: 'none',
-    margin: 0,
-    padding: 0,
-    display: 'grid',
-    gap: 10,
-  },
   listItem: {
     borderRadius: 12,
     border: '1px solid #e2e8f0',
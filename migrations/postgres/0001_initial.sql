@@ -0,0 +1,60 @@
+-- Initial schema. Mirrors migrations/sqlite/0001_initial.sql; timestamps are
+-- stored as UTC text in the same 'YYYY-MM-DD HH:MM:SS' form SQLite produces.
+
+CREATE OR REPLACE FUNCTION utc_now_text() RETURNS TEXT AS $$
+    SELECT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE accounts (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT utc_now_text()
+);
+
+CREATE TABLE groups (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    group_name TEXT NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT utc_now_text(),
+    FOREIGN KEY (created_by) REFERENCES accounts(id) ON DELETE RESTRICT
+);
+
+CREATE TABLE group_users (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    group_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'member',
+    joined_at TEXT NOT NULL DEFAULT utc_now_text(),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    UNIQUE(group_id, user_id)
+);
+
+CREATE TABLE notes (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    title TEXT,
+    content TEXT,
+    color TEXT NOT NULL DEFAULT '#FFFF88',
+    x DOUBLE PRECISION NOT NULL,
+    y DOUBLE PRECISION NOT NULL,
+    width DOUBLE PRECISION NOT NULL DEFAULT 200,
+    height DOUBLE PRECISION NOT NULL DEFAULT 150,
+    z_index BIGINT NOT NULL DEFAULT 0,
+    created_by BIGINT,
+    created_at TEXT NOT NULL DEFAULT utc_now_text(),
+    updated_at TEXT NOT NULL DEFAULT utc_now_text(),
+    FOREIGN KEY (created_by) REFERENCES accounts(id) ON DELETE SET NULL
+);
+
+CREATE TABLE note_shares (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    note_id BIGINT NOT NULL,
+    group_id BIGINT NOT NULL,
+    can_edit BOOLEAN NOT NULL DEFAULT FALSE,
+    shared_at TEXT NOT NULL DEFAULT utc_now_text(),
+    FOREIGN KEY (note_id) REFERENCES notes(id) ON DELETE CASCADE,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    UNIQUE(note_id, group_id)
+);
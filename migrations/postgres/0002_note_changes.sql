@@ -0,0 +1,43 @@
+-- Change log behind GET /api/groups/:id/notes/changes. Mirrors
+-- migrations/sqlite/0002_note_changes.sql.
+CREATE TABLE note_changes (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    group_id BIGINT NOT NULL,
+    note_id BIGINT NOT NULL,
+    changed_at TEXT NOT NULL DEFAULT utc_now_text()
+);
+
+CREATE INDEX idx_note_changes_group ON note_changes(group_id, id);
+
+-- Identity values are handed out before commit, so two concurrent writers
+-- could commit ids out of order and a client could skip the lower one. The
+-- table lock (held until commit, and not blocking readers) keeps ids in
+-- commit order, matching SQLite's single-writer behaviour.
+CREATE FUNCTION log_note_share_change() RETURNS trigger AS $$
+BEGIN
+    LOCK TABLE note_changes IN SHARE ROW EXCLUSIVE MODE;
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO note_changes (group_id, note_id) VALUES (OLD.group_id, OLD.note_id);
+    ELSE
+        INSERT INTO note_changes (group_id, note_id) VALUES (NEW.group_id, NEW.note_id);
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER note_shares_changes
+AFTER INSERT OR UPDATE OR DELETE ON note_shares
+FOR EACH ROW EXECUTE FUNCTION log_note_share_change();
+
+CREATE FUNCTION log_note_change() RETURNS trigger AS $$
+BEGIN
+    LOCK TABLE note_changes IN SHARE ROW EXCLUSIVE MODE;
+    INSERT INTO note_changes (group_id, note_id)
+    SELECT group_id, note_id FROM note_shares WHERE note_id = NEW.id;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notes_changes
+AFTER UPDATE ON notes
+FOR EACH ROW EXECUTE FUNCTION log_note_change();
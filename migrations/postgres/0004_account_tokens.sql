@@ -0,0 +1,16 @@
+-- Email verification and password reset. Mirrors
+-- migrations/sqlite/0004_account_tokens.sql.
+ALTER TABLE accounts ADD COLUMN email_verified_at TEXT;
+
+CREATE TABLE account_tokens (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id BIGINT NOT NULL,
+    purpose TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    created_at TEXT NOT NULL DEFAULT utc_now_text(),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_account_tokens_account ON account_tokens(account_id, purpose);
@@ -0,0 +1,34 @@
+-- Change log behind GET /api/groups/:id/notes/changes. Every insert, update or
+-- removal of a note as seen by a group appends a row; the row id is the sync
+-- cursor. Whether a logged note was upserted or deleted is decided by looking
+-- at the current tables, so only (group_id, note_id) is recorded.
+CREATE TABLE note_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL,
+    note_id INTEGER NOT NULL,
+    changed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_note_changes_group ON note_changes(group_id, id);
+
+CREATE TRIGGER note_shares_after_insert AFTER INSERT ON note_shares
+BEGIN
+    INSERT INTO note_changes (group_id, note_id) VALUES (NEW.group_id, NEW.note_id);
+END;
+
+CREATE TRIGGER note_shares_after_update AFTER UPDATE ON note_shares
+BEGIN
+    INSERT INTO note_changes (group_id, note_id) VALUES (NEW.group_id, NEW.note_id);
+END;
+
+-- Also fires for shares removed by the ON DELETE CASCADE from notes.
+CREATE TRIGGER note_shares_after_delete AFTER DELETE ON note_shares
+BEGIN
+    INSERT INTO note_changes (group_id, note_id) VALUES (OLD.group_id, OLD.note_id);
+END;
+
+CREATE TRIGGER notes_after_update AFTER UPDATE ON notes
+BEGIN
+    INSERT INTO note_changes (group_id, note_id)
+    SELECT group_id, note_id FROM note_shares WHERE note_id = NEW.id;
+END;
@@ -0,0 +1,3 @@
+-- Preferred language of API messages and mails for each account ('ja' or
+-- 'en'); NULL leaves the choice to the client's Accept-Language.
+ALTER TABLE accounts ADD COLUMN locale TEXT;
@@ -0,0 +1,18 @@
+-- Email verification and password reset. Only a SHA-256 digest of each token
+-- is kept, so a leaked database does not hand out working links. A token is
+-- spent by setting used_at; consuming one also spends the account's other
+-- open tokens of the same purpose.
+ALTER TABLE accounts ADD COLUMN email_verified_at DATETIME;
+
+CREATE TABLE account_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    purpose TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_account_tokens_account ON account_tokens(account_id, purpose);
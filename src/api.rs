@@ -1,28 +1,82 @@
+mod auth;
+mod openapi;
+mod ops;
+mod validation;
+
+use crate::backup::{self, BackupConfig};
+use crate::config::LimitsConfig;
+use api_types::{
+    AccountSummary, AccountsResponse, BackupFile, BackupsResponse, BatchOpRequest, BatchOpResult,
+    BatchReport, BatchRequest, ChangePasswordRequest, ChangesParams, ClearResponse, CreateAccountRequest,
+    CreateGroupRequest, CreateNoteRequest, CreateNoteResponse, DebugInfo, DeleteAccountRequest,
+    DeleteAccountResponse, ErrorBody, ExportQuery, FieldIssue, GroupMember, GroupMembersResponse, GroupMembership, GroupSummary, GroupsResponse, ImportIssue,
+    ImportReport, ImportRowResult, JoinGroupRequest, Note, NoteChangesResponse, NoteListParams,
+    NotesResponse, PageParams, UpdateAccountRequest, UpdateNoteContentRequest, UpdateNotePositionRequest,
+    UserLookupParams, UserLookupResponse, UserProfile,
+};
 use crate::db::{
-    self, Account, Db, Group, GroupUser, GroupWithRole, SharedNote,
+    self, Account, AccountSort, AccountUpdate, BatchOutcome, Cursor, Db, Group, GroupUser, GroupWithRole,
+    MemberSort, NewNote, NoteFilter, NoteOp, NoteSort, PageRequest, SharedNote, SortKey,
+    MAX_PAGE_SIZE,
 };
+use crate::export::{self, BoardExport, ExportFormat};
+use crate::i18n::{self, Message};
+use crate::import::{self, ImportRow, ParsedRow};
+use crate::mail::AccountMailer;
+use crate::metrics::{self, Metrics};
+use crate::rate_limit::{RateLimiter, RouteGroup};
+use crate::snapshot;
+use crate::telemetry;
+use auth::Session;
+use validation::{FieldError, Validator, DEFAULT_HEIGHT, DEFAULT_WIDTH};
 use axum::{
-    extract::{Json as JsonPayload, Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, patch, post},
-    Json, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Json as JsonPayload, MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, patch, post, put},
+    Extension, Json, Router,
 };
-use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Db,
     pub database_url: String,
+    /// Bearer token for `/api/admin/*` and `/api/debug`. Admin endpoints are
+    /// disabled when unset.
+    pub admin_token: Option<String>,
+    pub backups: BackupConfig,
+    pub metrics: Arc<Metrics>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub limits: LimitsConfig,
+    /// Sends the verification and password reset mails.
+    pub mail: AccountMailer,
+    /// `host:port` probed by `/readyz` to report LLM provider reachability.
+    pub llm_endpoint: Option<String>,
 }
 
 pub fn routes(state: AppState) -> Router {
+    let state = Arc::new(state);
     Router::new()
         // accounts
         .route("/api/accounts", get(list_accounts).post(create_account))
+        .route("/api/accounts/me", get(current_account))
+        .route("/api/accounts/lookup", get(lookup_accounts))
+        .route("/api/accounts/:id", patch(update_account).delete(delete_account))
+        .route("/api/accounts/:id/password", put(change_password))
         .route("/api/accounts/:id/groups", get(list_groups_for_user))
+        // auth
+        .route("/api/auth/login", post(auth::login))
+        .route("/api/auth/logout", post(auth::logout))
+        .route("/api/auth/verify-email", post(auth::verify_email))
+        .route("/api/auth/verify-email/resend", post(auth::resend_verification))
+        .route("/api/auth/password-reset", post(auth::request_password_reset))
+        .route("/api/auth/password-reset/confirm", post(auth::reset_password))
         // groups
         .route("/api/groups", post(create_group))
         .route("/api/groups/:id", get(get_group))
@@ -31,29 +85,137 @@ pub fn routes(state: AppState) -> Router {
             "/api/groups/:id/notes",
             get(list_group_notes).post(create_group_note).delete(clear_group_notes),
         )
+        .route("/api/groups/:id/notes/changes", get(group_note_changes))
+        .route("/api/groups/:id/notes/batch", post(batch_group_notes))
+        .route("/api/groups/:id/export", get(export_group))
+        .route("/api/groups/:id/import", post(import_group_notes))
+        .route("/api/groups/:id/snapshot.svg", get(group_snapshot_svg))
+        .route("/api/groups/:id/snapshot.png", get(group_snapshot_png))
         // notes
         .route("/api/notes/:id", patch(update_note_content).delete(delete_note))
         .route("/api/notes/:id/position", patch(update_note_position))
+        // admin
+        .route("/api/admin/backups", get(list_backups).post(create_backup))
         // misc
         .route("/api/debug", get(debug))
-        .with_state(Arc::new(state))
+        .route("/api/openapi.json", get(openapi::openapi_json))
+        // ops
+        .route("/healthz", get(ops::healthz))
+        .route("/readyz", get(ops::readyz))
+        .route("/metrics", get(ops::metrics))
+        .route_layer(middleware::from_fn(negotiate_locale))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::resolve_session))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(state.metrics.clone(), metrics::track))
+        .layer(DefaultBodyLimit::max(state.limits.max_body_bytes))
+        .merge(openapi::docs())
+        .with_state(state)
 }
 
 // -------------------------------------------------------------------
 // Accounts
 
-async fn list_accounts(State(state): State<Arc<AppState>>) -> Result<Json<AccountsResponse>, ApiError> {
-    let accounts = state
-        .db
-        .list_accounts()
-        .await
-        .map_err(ApiError::internal)?
-        .into_iter()
-        .map(AccountSummary::from)
-        .collect();
-    Ok(Json(AccountsResponse { accounts }))
+/// Every account with its address, for operators.
+#[utoipa::path(
+    get,
+    path = "/api/accounts",
+    tag = "accounts",
+    security(("admin_token" = [])),
+    params(PageParams),
+    responses(
+        (status = 200, body = AccountsResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+async fn list_accounts(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<PageParams>,
+) -> Result<Json<AccountsResponse>, ApiError> {
+    require_admin(&state, &headers)?;
+    let mut v = Validator::new();
+    let req = page_request::<AccountSort>(&mut v, &params);
+    let req = v.finish_with(req)?;
+    let page = state.db.page_accounts(&req).await.map_err(ApiError::internal)?;
+    Ok(Json(AccountsResponse {
+        accounts: page.items.into_iter().map(AccountSummary::from).collect(),
+        next_cursor: page.next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/me",
+    tag = "accounts",
+    security(("session" = [])),
+    responses(
+        (status = 200, body = AccountSummary),
+        (status = 401, body = ErrorBody),
+    )
+)]
+async fn current_account(Extension(session): Extension<Session>) -> Result<Json<AccountSummary>, ApiError> {
+    let account = session.account()?;
+    Ok(Json(AccountSummary::from(account.clone())))
 }
 
+/// Finds people to invite without exposing the directory: `email` has to
+/// match an address exactly, and `q` only searches the names of people who
+/// already share a group with the caller. Only the ID and name come back.
+#[utoipa::path(
+    get,
+    path = "/api/accounts/lookup",
+    tag = "accounts",
+    security(("session" = [])),
+    params(UserLookupParams),
+    responses(
+        (status = 200, body = UserLookupResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+async fn lookup_accounts(
+    State(state): State<Arc<AppState>>,
+    Extension(session): Extension<Session>,
+    Query(params): Query<UserLookupParams>,
+) -> Result<Json<UserLookupResponse>, ApiError> {
+    let caller = session.account()?;
+    let email = params.email.as_deref().map(str::trim).filter(|e| !e.is_empty());
+    let name = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let mut v = Validator::new();
+    match (email, name) {
+        (Some(email), None) => check_email(&mut v, email),
+        (None, Some(name)) => {
+            v.check(validation::check_len("q", name, state.limits.max_name_len));
+        }
+        _ => v.push(FieldError::bad_request("q", "lookup_query_invalid")),
+    }
+    v.finish()?;
+
+    let accounts = match email {
+        Some(email) => state.db.find_account_by_email(email).await.map(Vec::from_iter),
+        None => state.db.find_group_peers(caller.id, name.unwrap_or_default(), MAX_LOOKUP_RESULTS).await,
+    }
+    .map_err(ApiError::internal)?;
+    let users = accounts.into_iter().map(|a| UserProfile { id: a.id, name: a.name }).collect();
+    Ok(Json(UserLookupResponse { users }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/accounts",
+    tag = "accounts",
+    request_body = CreateAccountRequest,
+    responses(
+        (status = 200, body = AccountSummary),
+        (status = 400, body = ErrorBody),
+        (status = 409, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn create_account(
     State(state): State<Arc<AppState>>,
     JsonPayload(payload): JsonPayload<CreateAccountRequest>,
@@ -62,49 +224,222 @@ async fn create_account(
     let email = payload.email.trim();
     let password = payload.password.trim();
 
-    if name.is_empty() {
-        return Err(ApiError::bad_request("name_empty", "名前を入力してください"));
-    }
-    if email.is_empty() {
-        return Err(ApiError::bad_request("email_empty", "メールアドレスを入力してください"));
-    }
-    if !email.contains('@') {
-        return Err(ApiError::unprocessable("email_invalid", "メールアドレスの形式が正しくありません"));
-    }
-    if password.len() < 6 {
-        return Err(ApiError::unprocessable("password_short", "パスワードは6文字以上にしてください"));
+    let mut v = Validator::new();
+    check_new_account(&mut v, &state.limits, name, email, password);
+    let locale = payload.locale.as_deref().map(str::trim).filter(|l| !l.is_empty()).and_then(|l| {
+        let locale = i18n::Locale::parse(l);
+        if locale.is_none() {
+            v.push(FieldError::unprocessable("locale", "locale_invalid"));
+        }
+        locale
+    });
+    v.finish()?;
+    let taken = state.db.find_account_by_email(email).await.map_err(ApiError::internal)?;
+    if taken.is_some() {
+        return Err(FieldError::conflict("email", "email_taken").into());
     }
 
     let hash = hash_password(password);
     let id = state
         .db
-        .create_account(name, email, &hash)
+        .create_account(name, email, &hash, locale.map(i18n::Locale::as_str))
         .await
-        .map_err(|e| ApiError::internal(format!("アカウント作成に失敗しました: {e}")))?;
+        .map_err(|e| ApiError::internal_with(Message::new("account_create_failed").arg("detail", e)))?;
 
     let account = state
         .db
         .get_account(id)
         .await
         .map_err(ApiError::internal)?
-        .ok_or_else(|| ApiError::internal("作成したアカウントが見つかりません"))?;
+        .ok_or_else(|| ApiError::internal_with(Message::new("created_account_missing")))?;
+    auth::send_verification(&state, &account).await?;
 
     Ok(Json(AccountSummary::from(account)))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/accounts/{id}",
+    tag = "accounts",
+    security(("session" = []), ("admin_token" = [])),
+    params(("id" = i64, Path, description = "アカウントID")),
+    request_body = UpdateAccountRequest,
+    responses(
+        (status = 200, body = AccountSummary),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+async fn update_account(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Extension(session): Extension<Session>,
+    JsonPayload(payload): JsonPayload<UpdateAccountRequest>,
+) -> Result<Json<AccountSummary>, ApiError> {
+    if id <= 0 {
+        return Err(ApiError::bad_request("invalid_user_id"));
+    }
+    authorize_account(&state, &headers, &session, id)?;
+    let name = payload.name.as_deref().map(str::trim);
+    let email = payload.email.as_deref().map(str::trim);
+
+    let mut v = Validator::new();
+    if let Some(name) = name {
+        check_name(&mut v, &state.limits, name);
+    }
+    if let Some(email) = email {
+        check_email(&mut v, email);
+    }
+    v.finish()?;
+    telemetry::record_account(id);
+
+    let account = fetch_account(&state.db, id).await?;
+    // Whoever controls the address can reset the password, so moving it
+    // takes the password as well.
+    if email.is_some_and(|e| e != account.email) {
+        verify_current_password(&account, payload.current_password.as_deref().unwrap_or_default().trim())?;
+    }
+    match state.db.update_account(id, name, email).await.map_err(ApiError::internal)? {
+        AccountUpdate::Updated { email_changed } => {
+            let account = fetch_account(&state.db, id).await?;
+            if email_changed {
+                auth::send_verification(&state, &account).await?;
+            }
+            Ok(Json(AccountSummary::from(account)))
+        }
+        AccountUpdate::NotFound => Err(ApiError::not_found("account_not_found")),
+        AccountUpdate::EmailTaken => Err(FieldError::conflict("email", "email_taken").into()),
+    }
+}
+
+/// Open password reset links stop working once the password has changed.
+#[utoipa::path(
+    put,
+    path = "/api/accounts/{id}/password",
+    tag = "accounts",
+    security(("session" = []), ("admin_token" = [])),
+    params(("id" = i64, Path, description = "アカウントID")),
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "パスワードを変更した"),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+async fn change_password(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Extension(session): Extension<Session>,
+    JsonPayload(payload): JsonPayload<ChangePasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    if id <= 0 {
+        return Err(ApiError::bad_request("invalid_user_id"));
+    }
+    authorize_account(&state, &headers, &session, id)?;
+    let new_password = payload.new_password.trim();
+    let mut v = Validator::new();
+    v.check(check_password("new_password", new_password));
+    v.finish()?;
+    telemetry::record_account(id);
+
+    let account = fetch_account(&state.db, id).await?;
+    verify_current_password(&account, payload.current_password.trim())?;
+    state
+        .db
+        .set_password_hash(id, &hash_password(new_password))
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Groups the account created pass to another member, an owner if there is
+/// one, who becomes their owner. Groups nobody else belongs to are deleted
+/// with their notes. Notes the account wrote elsewhere stay, without an
+/// author.
+#[utoipa::path(
+    delete,
+    path = "/api/accounts/{id}",
+    tag = "accounts",
+    security(("session" = []), ("admin_token" = [])),
+    params(("id" = i64, Path, description = "アカウントID")),
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = 200, body = DeleteAccountResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Extension(session): Extension<Session>,
+    JsonPayload(payload): JsonPayload<DeleteAccountRequest>,
+) -> Result<Json<DeleteAccountResponse>, ApiError> {
+    if id <= 0 {
+        return Err(ApiError::bad_request("invalid_user_id"));
+    }
+    authorize_account(&state, &headers, &session, id)?;
+    telemetry::record_account(id);
+    let account = fetch_account(&state.db, id).await?;
+    verify_current_password(&account, payload.current_password.trim())?;
+    let removal = state
+        .db
+        .delete_account(id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("account_not_found"))?;
+    tracing::info!(
+        account_id = id,
+        transferred = ?removal.transferred_groups,
+        deleted = ?removal.deleted_groups,
+        "account deleted"
+    );
+    Ok(Json(DeleteAccountResponse {
+        transferred_groups: removal.transferred_groups,
+        deleted_groups: removal.deleted_groups,
+    }))
+}
+
 // -------------------------------------------------------------------
 // Groups
 
+#[utoipa::path(
+    post,
+    path = "/api/groups",
+    tag = "groups",
+    request_body = CreateGroupRequest,
+    responses(
+        (status = 200, body = GroupSummary),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn create_group(
     State(state): State<Arc<AppState>>,
     JsonPayload(payload): JsonPayload<CreateGroupRequest>,
 ) -> Result<Json<GroupSummary>, ApiError> {
-    if payload.group_name.trim().is_empty() {
-        return Err(ApiError::bad_request("group_name_empty", "グループ名を入力してください"));
-    }
-    if payload.created_by <= 0 {
-        return Err(ApiError::bad_request("created_by_invalid", "作成ユーザーIDが不正です"));
+    let mut v = Validator::new();
+    let group_name = payload.group_name.trim();
+    if group_name.is_empty() {
+        v.push(FieldError::bad_request("group_name", "group_name_empty"));
     }
+    v.check(validation::check_len("group_name", group_name, state.limits.max_group_name_len));
+    v.check(validation::id("created_by", payload.created_by, "created_by_invalid"));
+    v.finish()?;
+    telemetry::record_account(payload.created_by);
 
     ensure_account_exists(&state.db, payload.created_by).await?;
 
@@ -119,33 +454,55 @@ async fn create_group(
         .get_group(id)
         .await
         .map_err(ApiError::internal)?
-        .ok_or_else(|| ApiError::internal("作成したグループが見つかりません"))?;
+        .ok_or_else(|| ApiError::internal_with(Message::new("created_group_missing")))?;
 
     Ok(Json(GroupSummary::from(group)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}",
+    tag = "groups",
+    params(("id" = i64, Path, description = "グループID")),
+    responses(
+        (status = 200, body = GroupSummary),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn get_group(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
 ) -> Result<Json<GroupSummary>, ApiError> {
     if id <= 0 {
-        return Err(ApiError::bad_request("invalid_id", "グループIDが不正です"));
+        return Err(ApiError::bad_request("invalid_id"));
     }
     let group = state
         .db
         .get_group(id)
         .await
         .map_err(ApiError::internal)?
-        .ok_or_else(|| ApiError::not_found("group_not_found", "グループが見つかりません"))?;
+        .ok_or_else(|| ApiError::not_found("group_not_found"))?;
     Ok(Json(GroupSummary::from(group)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{id}/groups",
+    tag = "accounts",
+    params(("id" = i64, Path, description = "アカウントID")),
+    responses(
+        (status = 200, body = GroupsResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn list_groups_for_user(
     State(state): State<Arc<AppState>>,
     Path(user_id): Path<i64>,
 ) -> Result<Json<GroupsResponse>, ApiError> {
     if user_id <= 0 {
-        return Err(ApiError::bad_request("invalid_user_id", "ユーザーIDが不正です"));
+        return Err(ApiError::bad_request("invalid_user_id"));
     }
     ensure_account_exists(&state.db, user_id).await?;
     let groups = state
@@ -159,24 +516,36 @@ async fn list_groups_for_user(
     Ok(Json(GroupsResponse { groups }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/groups/{id}/users",
+    tag = "groups",
+    params(("id" = i64, Path, description = "グループID")),
+    request_body = JoinGroupRequest,
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn add_user_to_group(
     State(state): State<Arc<AppState>>,
     Path(group_id): Path<i64>,
     JsonPayload(payload): JsonPayload<JoinGroupRequest>,
 ) -> Result<StatusCode, ApiError> {
     if group_id <= 0 {
-        return Err(ApiError::bad_request("invalid_group_id", "グループIDが不正です"));
-    }
-    if payload.user_id <= 0 {
-        return Err(ApiError::bad_request("invalid_user_id", "ユーザーIDが不正です"));
+        return Err(ApiError::bad_request("invalid_group_id"));
     }
-    ensure_account_exists(&state.db, payload.user_id).await?;
-    ensure_group_exists(&state.db, group_id).await?;
-
+    let mut v = Validator::new();
+    v.check(validation::id("user_id", payload.user_id, "invalid_user_id"));
     let role = payload.role.unwrap_or_else(|| "member".to_string());
     if !matches!(role.as_str(), "owner" | "member") {
-        return Err(ApiError::unprocessable("invalid_role", "role は owner か member にしてください"));
+        v.push(FieldError::unprocessable("role", "invalid_role"));
     }
+    v.finish()?;
+    ensure_account_exists(&state.db, payload.user_id).await?;
+    ensure_group_exists(&state.db, group_id).await?;
 
     state
         .db
@@ -187,52 +556,188 @@ async fn add_user_to_group(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/users",
+    tag = "groups",
+    params(("id" = i64, Path, description = "グループID"), PageParams),
+    responses(
+        (status = 200, body = GroupMembersResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn list_group_members(
     State(state): State<Arc<AppState>>,
     Path(group_id): Path<i64>,
+    Query(params): Query<PageParams>,
 ) -> Result<Json<GroupMembersResponse>, ApiError> {
     if group_id <= 0 {
-        return Err(ApiError::bad_request("invalid_group_id", "グループIDが不正です"));
+        return Err(ApiError::bad_request("invalid_group_id"));
     }
+    let mut v = Validator::new();
+    let req = page_request::<MemberSort>(&mut v, &params);
+    let req = v.finish_with(req)?;
     ensure_group_exists(&state.db, group_id).await?;
-    let members = state
+    let page = state
         .db
-        .list_group_members(group_id)
+        .page_group_members(group_id, &req)
         .await
         .map_err(ApiError::internal)?;
-    Ok(Json(GroupMembersResponse { members }))
+    Ok(Json(GroupMembersResponse {
+        members: page.items.into_iter().map(GroupMember::from).collect(),
+        next_cursor: page.next_cursor,
+    }))
 }
 
 // -------------------------------------------------------------------
 // Notes
 
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/notes",
+    tag = "notes",
+    params(("id" = i64, Path, description = "グループID"), NoteListParams),
+    responses(
+        (status = 200, body = NotesResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn list_group_notes(
     State(state): State<Arc<AppState>>,
     Path(group_id): Path<i64>,
+    Query(params): Query<NoteListParams>,
 ) -> Result<Json<NotesResponse>, ApiError> {
     if group_id <= 0 {
-        return Err(ApiError::bad_request("invalid_group_id", "グループIDが不正です"));
+        return Err(ApiError::bad_request("invalid_group_id"));
     }
+    let mut v = Validator::new();
+    let req = page_request::<NoteSort>(
+        &mut v,
+        &PageParams { limit: params.limit, cursor: params.cursor, sort: params.sort },
+    );
+    let color = params.color.as_deref().map(str::trim).filter(|c| !c.is_empty()).and_then(|c| {
+        let color = parse_color(c);
+        if color.is_none() {
+            v.push(FieldError::bad_request("color", "invalid_color").arg("color", c));
+        }
+        color
+    });
+    let updated_since = params.updated_since.as_deref().and_then(|raw| {
+        let normalized = normalize_timestamp(raw);
+        if normalized.is_none() {
+            v.push(FieldError::bad_request("updated_since", "invalid_updated_since"));
+        }
+        normalized
+    });
+    let req = v.finish_with(req)?;
+    let filter = NoteFilter {
+        author: params.author,
+        color,
+        updated_since,
+        text: params.q.filter(|q| !q.trim().is_empty()),
+    };
+
     ensure_group_exists(&state.db, group_id).await?;
-    let notes = state
+    let page = state
         .db
-        .list_notes_for_group(group_id)
+        .page_notes_for_group(group_id, &filter, &req)
         .await
         .map_err(ApiError::internal)?;
-    Ok(Json(NotesResponse { notes }))
+    Ok(Json(NotesResponse {
+        notes: page.items.into_iter().map(Note::from).collect(),
+        next_cursor: page.next_cursor,
+    }))
 }
 
+/// Incremental sync. Without `since` every note is returned; afterwards pass
+/// the `cursor` of the previous response to get only what changed since then.
+/// A note in `deleted` was removed or is no longer shared with the group.
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/notes/changes",
+    tag = "notes",
+    params(("id" = i64, Path, description = "グループID"), ChangesParams),
+    responses(
+        (status = 200, body = NoteChangesResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 410, body = ErrorBody),
+    )
+)]
+async fn group_note_changes(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<NoteChangesResponse>, ApiError> {
+    if group_id <= 0 {
+        return Err(ApiError::bad_request("invalid_group_id"));
+    }
+    let since = match params.since.as_deref() {
+        None => None,
+        Some(raw) => Some(
+            raw.parse::<i64>()
+                .ok()
+                .filter(|s| *s >= 0)
+                .ok_or_else(|| ApiError::bad_request("invalid_cursor"))?,
+        ),
+    };
+
+    ensure_group_exists(&state.db, group_id).await?;
+    // Read the cursor before the changes so that anything committed in
+    // between is sent again next time rather than skipped.
+    let latest = state.db.latest_change_id().await.map_err(ApiError::internal)?;
+    let (notes, deleted) = match since {
+        None => (
+            state.db.list_notes_for_group(group_id).await.map_err(ApiError::internal)?,
+            Vec::new(),
+        ),
+        Some(since) if since > latest => {
+            return Err(ApiError::gone("resync_required"));
+        }
+        Some(since) => state
+            .db
+            .notes_changed_since(group_id, since)
+            .await
+            .map_err(ApiError::internal)?,
+    };
+    Ok(Json(NoteChangesResponse {
+        notes: notes.into_iter().map(Note::from).collect(),
+        deleted,
+        cursor: latest.to_string(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/groups/{id}/notes",
+    tag = "notes",
+    params(("id" = i64, Path, description = "グループID")),
+    request_body = CreateNoteRequest,
+    responses(
+        (status = 200, body = CreateNoteResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn create_group_note(
     State(state): State<Arc<AppState>>,
     Path(group_id): Path<i64>,
     JsonPayload(payload): JsonPayload<CreateNoteRequest>,
 ) -> Result<Json<CreateNoteResponse>, ApiError> {
     if group_id <= 0 {
-        return Err(ApiError::bad_request("invalid_group_id", "グループIDが不正です"));
+        return Err(ApiError::bad_request("invalid_group_id"));
     }
+    let mut v = Validator::new();
+    validation::note_text(&mut v, &state.limits, payload.title.as_deref(), payload.content.as_deref());
+    let (x, y, width, height) = validation::geometry(&mut v, payload.x, payload.y, payload.width, payload.height);
+    v.finish()?;
     ensure_group_exists(&state.db, group_id).await?;
 
     if let Some(author_id) = payload.created_by {
+        telemetry::record_account(author_id);
         ensure_account_exists(&state.db, author_id).await?;
         let belongs = state
             .db
@@ -240,74 +745,93 @@ async fn create_group_note(
             .await
             .map_err(ApiError::internal)?;
         if !belongs {
-            return Err(ApiError::unprocessable(
-                "not_member",
-                "このユーザーはグループに参加していません",
-            ));
+            return Err(ApiError::unprocessable("not_member"));
         }
     }
 
     let color = normalize_color(payload.color.as_deref());
-    let width = payload.width.unwrap_or(200.0);
-    let height = payload.height.unwrap_or(150.0);
     let z_index = payload.z_index.unwrap_or(0);
 
+    let note = NewNote {
+        title: payload.title,
+        content: payload.content,
+        color,
+        x,
+        y,
+        width,
+        height,
+        z_index,
+        created_by: payload.created_by,
+    };
     let note_id = state
         .db
-        .create_note(
-            payload.title.as_deref(),
-            payload.content.as_deref(),
-            &color,
-            payload.x,
-            payload.y,
-            width,
-            height,
-            z_index,
-            payload.created_by,
-            group_id,
-            payload.can_edit.unwrap_or(false),
-        )
+        .create_note(&note, group_id, payload.can_edit.unwrap_or(false))
         .await
         .map_err(ApiError::internal)?;
 
     Ok(Json(CreateNoteResponse { id: note_id }))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/notes/{id}/position",
+    tag = "notes",
+    params(("id" = i64, Path, description = "付箋ID")),
+    request_body = UpdateNotePositionRequest,
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn update_note_position(
     State(state): State<Arc<AppState>>,
     Path(note_id): Path<i64>,
     JsonPayload(payload): JsonPayload<UpdateNotePositionRequest>,
 ) -> Result<StatusCode, ApiError> {
     if note_id <= 0 {
-        return Err(ApiError::bad_request("invalid_note_id", "付箋IDが不正です"));
+        return Err(ApiError::bad_request("invalid_note_id"));
     }
+    let mut v = Validator::new();
+    let (x, y, width, height) = validation::geometry(&mut v, payload.x, payload.y, payload.width, payload.height);
+    v.finish()?;
     let updated = state
         .db
-        .update_note_position(
-            note_id,
-            payload.x,
-            payload.y,
-            payload.width.unwrap_or(200.0),
-            payload.height.unwrap_or(150.0),
-            payload.z_index.unwrap_or(0),
-        )
+        .update_note_position(note_id, x, y, width, height, payload.z_index.unwrap_or(0))
         .await
         .map_err(ApiError::internal)?;
     if updated {
         Ok(StatusCode::NO_CONTENT)
     } else {
-        Err(ApiError::not_found("note_not_found", "付箋が見つかりません"))
+        Err(ApiError::not_found("note_not_found"))
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/notes/{id}",
+    tag = "notes",
+    params(("id" = i64, Path, description = "付箋ID")),
+    request_body = UpdateNoteContentRequest,
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
 async fn update_note_content(
     State(state): State<Arc<AppState>>,
     Path(note_id): Path<i64>,
     JsonPayload(payload): JsonPayload<UpdateNoteContentRequest>,
 ) -> Result<StatusCode, ApiError> {
     if note_id <= 0 {
-        return Err(ApiError::bad_request("invalid_note_id", "付箋IDが不正です"));
+        return Err(ApiError::bad_request("invalid_note_id"));
     }
+    let mut v = Validator::new();
+    validation::note_text(&mut v, &state.limits, payload.title.as_deref(), payload.content.as_deref());
+    v.finish()?;
 
     let color = normalize_color(payload.color.as_deref());
 
@@ -325,16 +849,27 @@ async fn update_note_content(
     if updated {
         Ok(StatusCode::NO_CONTENT)
     } else {
-        Err(ApiError::not_found("note_not_found", "付箋が見つかりません"))
+        Err(ApiError::not_found("note_not_found"))
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/notes/{id}",
+    tag = "notes",
+    params(("id" = i64, Path, description = "付箋ID")),
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn delete_note(
     State(state): State<Arc<AppState>>,
     Path(note_id): Path<i64>,
 ) -> Result<StatusCode, ApiError> {
     if note_id <= 0 {
-        return Err(ApiError::bad_request("invalid_note_id", "付箋IDが不正です"));
+        return Err(ApiError::bad_request("invalid_note_id"));
     }
     let deleted = state
         .db
@@ -344,16 +879,171 @@ async fn delete_note(
     if deleted {
         Ok(StatusCode::NO_CONTENT)
     } else {
-        Err(ApiError::not_found("note_not_found", "付箋が見つかりません"))
+        Err(ApiError::not_found("note_not_found"))
+    }
+}
+
+/// Applies every op or none. When any op is invalid or names a note outside
+/// the group, nothing is written and the report says which ops failed.
+#[utoipa::path(
+    post,
+    path = "/api/groups/{id}/notes/batch",
+    tag = "notes",
+    params(("id" = i64, Path, description = "グループID")),
+    request_body = BatchRequest,
+    responses(
+        (status = 200, body = BatchReport),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 422, body = BatchReport),
+    )
+)]
+async fn batch_group_notes(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+    JsonPayload(payload): JsonPayload<BatchRequest>,
+) -> Result<(StatusCode, Json<BatchReport>), ApiError> {
+    if group_id <= 0 {
+        return Err(ApiError::bad_request("invalid_group_id"));
+    }
+    if payload.ops.is_empty() {
+        return Err(FieldError::bad_request("ops", "batch_empty").into());
+    }
+    if payload.ops.len() > MAX_BATCH_OPS {
+        return Err(
+            FieldError::bad_request("ops", "batch_too_large").arg("max", MAX_BATCH_OPS).into(),
+        );
+    }
+    ensure_group_exists(&state.db, group_id).await?;
+
+    let members: HashSet<i64> = state
+        .db
+        .list_group_members(group_id)
+        .await
+        .map_err(ApiError::internal)?
+        .into_iter()
+        .map(|m| m.user_id)
+        .collect();
+
+    let mut results = Vec::with_capacity(payload.ops.len());
+    let mut ops = Vec::with_capacity(payload.ops.len());
+    for (index, raw) in payload.ops.into_iter().enumerate() {
+        let op_name = raw.name();
+        match validate_batch_op(raw, &members, &state.limits) {
+            Ok(op) => {
+                results.push(BatchOpResult {
+                    index,
+                    op: op_name.to_string(),
+                    status: "ok".to_string(),
+                    note_id: None,
+                    error: None,
+                });
+                ops.push(op);
+            }
+            Err(issue) => results.push(BatchOpResult {
+                index,
+                op: op_name.to_string(),
+                status: "error".to_string(),
+                note_id: None,
+                error: Some(issue),
+            }),
+        }
+    }
+    if ops.len() < results.len() {
+        return Ok(batch_rejected(results));
+    }
+
+    match state.db.apply_note_ops(group_id, &ops).await.map_err(ApiError::internal)? {
+        BatchOutcome::Applied(ids) => {
+            for (result, id) in results.iter_mut().zip(ids) {
+                result.note_id = Some(id);
+            }
+            Ok((StatusCode::OK, Json(BatchReport { applied: true, results })))
+        }
+        BatchOutcome::NoteNotFound(index) => {
+            results[index].status = "error".to_string();
+            let message = Message::new("note_not_in_group");
+            results[index].error = Some(ImportIssue::new("note_not_found", message.to_string()));
+            Ok(batch_rejected(results))
+        }
+    }
+}
+
+/// Marks every op that did not fail itself as skipped.
+fn batch_rejected(mut results: Vec<BatchOpResult>) -> (StatusCode, Json<BatchReport>) {
+    for result in results.iter_mut().filter(|r| r.error.is_none()) {
+        result.status = "skipped".to_string();
+    }
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(BatchReport { applied: false, results }))
+}
+
+fn validate_batch_op(
+    raw: BatchOpRequest,
+    members: &HashSet<i64>,
+    limits: &LimitsConfig,
+) -> Result<NoteOp, ImportIssue> {
+    let mut v = Validator::new();
+    let check_id = |v: &mut Validator, note_id: i64| {
+        v.check(validation::id("note_id", note_id, "invalid_note_id")).unwrap_or_default()
+    };
+    let op = match raw {
+        BatchOpRequest::Create(req) => {
+            validation::note_text(&mut v, limits, req.title.as_deref(), req.content.as_deref());
+            let (x, y, width, height) = validation::geometry(&mut v, req.x, req.y, req.width, req.height);
+            if req.created_by.is_some_and(|author_id| !members.contains(&author_id)) {
+                v.push(FieldError::unprocessable("created_by", "not_member"));
+            }
+            NoteOp::Create {
+                note: NewNote {
+                    title: req.title,
+                    content: req.content,
+                    color: normalize_color(req.color.as_deref()),
+                    x,
+                    y,
+                    width,
+                    height,
+                    z_index: req.z_index.unwrap_or(0),
+                    created_by: req.created_by,
+                },
+                can_edit: req.can_edit.unwrap_or(false),
+            }
+        }
+        BatchOpRequest::UpdatePosition { note_id, x, y, width, height, z_index } => {
+            let note_id = check_id(&mut v, note_id);
+            let (x, y, width, height) = validation::geometry(&mut v, x, y, width, height);
+            NoteOp::UpdatePosition { note_id, x, y, width, height, z_index: z_index.unwrap_or(0) }
+        }
+        BatchOpRequest::UpdateContent { note_id, title, content, color } => {
+            let note_id = check_id(&mut v, note_id);
+            validation::note_text(&mut v, limits, title.as_deref(), content.as_deref());
+            NoteOp::UpdateContent { note_id, title, content, color: normalize_color(color.as_deref()) }
+        }
+        BatchOpRequest::Delete { note_id } => NoteOp::Delete { note_id: check_id(&mut v, note_id) },
+    };
+    // A batch result carries one issue per op; report the first.
+    match v.into_issues().into_iter().next() {
+        Some(issue) => Err(issue),
+        None => Ok(op),
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/groups/{id}/notes",
+    tag = "notes",
+    params(("id" = i64, Path, description = "グループID")),
+    responses(
+        (status = 200, body = ClearResponse),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
 async fn clear_group_notes(
     State(state): State<Arc<AppState>>,
     Path(group_id): Path<i64>,
 ) -> Result<Json<ClearResponse>, ApiError> {
     if group_id <= 0 {
-        return Err(ApiError::bad_request("invalid_group_id", "グループIDが不正です"));
+        return Err(ApiError::bad_request("invalid_group_id"));
     }
     ensure_group_exists(&state.db, group_id).await?;
     let removed = state
@@ -364,19 +1054,317 @@ async fn clear_group_notes(
     Ok(Json(ClearResponse { removed }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/export",
+    tag = "boards",
+    params(("id" = i64, Path, description = "グループID"), ExportQuery),
+    responses(
+        (status = 200, description = "JSON / CSV / Markdown のボード", body = String),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+async fn export_group(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    if group_id <= 0 {
+        return Err(ApiError::bad_request("invalid_group_id"));
+    }
+    let format = ExportFormat::parse(query.format.as_deref()).ok_or_else(|| {
+        ApiError::bad_request("invalid_format")
+    })?;
+    let export = BoardExport::load(&state.db, group_id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("group_not_found"))?;
+    let disposition = format!("attachment; filename=\"group-{}.{}\"", group_id, format.extension());
+    let headers = [
+        (header::CONTENT_TYPE, format.content_type().to_string()),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+    let response = match format {
+        ExportFormat::Json => (headers, Json(export)).into_response(),
+        ExportFormat::Csv => (headers, export::render_csv(&export)).into_response(),
+        ExportFormat::Markdown => (headers, export::render_markdown(&export)).into_response(),
+    };
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/groups/{id}/import",
+    tag = "boards",
+    params(("id" = i64, Path, description = "グループID")),
+    request_body(content = String, description = "エクスポートした JSON、付箋の配列、または CSV", content_type = "text/csv"),
+    responses(
+        (status = 200, body = ImportReport),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+async fn import_group_notes(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportReport>, ApiError> {
+    if group_id <= 0 {
+        return Err(ApiError::bad_request("invalid_group_id"));
+    }
+    ensure_group_exists(&state.db, group_id).await?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+    let looks_like_json = body
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'{' || *b == b'[');
+    let parsed = if content_type.contains("json") || (!content_type.contains("csv") && looks_like_json) {
+        import::parse_json(&body)
+    } else {
+        let text = std::str::from_utf8(&body)
+            .map_err(|_| ApiError::bad_request("import_malformed").with_message(Message::new("import_not_utf8")))?;
+        import::parse_csv(text)
+    }
+    .map_err(|e| ApiError::bad_request("import_malformed").with_message(Message::new("import_malformed").arg("detail", e)))?;
+
+    let members: HashSet<i64> = state
+        .db
+        .list_group_members(group_id)
+        .await
+        .map_err(ApiError::internal)?
+        .into_iter()
+        .map(|m| m.user_id)
+        .collect();
+
+    let mut rows = Vec::with_capacity(parsed.len());
+    let mut slots = Vec::new();
+    let mut notes = Vec::new();
+    for ParsedRow { row, entry } in parsed {
+        let outcome = match entry {
+            Ok(raw) => validate_import_row(raw, &members, &state.limits),
+            Err(e) => Err(vec![ImportIssue::new("row_malformed", e.to_string())]),
+        };
+        match outcome {
+            Ok(note) => {
+                slots.push(rows.len());
+                notes.push(note);
+                rows.push(ImportRowResult {
+                    row,
+                    status: "accepted".to_string(),
+                    note_id: None,
+                    errors: Vec::new(),
+                });
+            }
+            Err(errors) => rows.push(ImportRowResult {
+                row,
+                status: "rejected".to_string(),
+                note_id: None,
+                errors,
+            }),
+        }
+    }
+
+    let ids = state
+        .db
+        .import_notes(group_id, &notes)
+        .await
+        .map_err(ApiError::internal)?;
+    for (slot, id) in slots.into_iter().zip(ids) {
+        rows[slot].note_id = Some(id);
+    }
+
+    Ok(Json(ImportReport {
+        accepted: notes.len(),
+        rejected: rows.len() - notes.len(),
+        rows,
+    }))
+}
+
+fn validate_import_row(
+    raw: ImportRow,
+    members: &HashSet<i64>,
+    limits: &LimitsConfig,
+) -> Result<NewNote, Vec<ImportIssue>> {
+    let mut v = Validator::new();
+
+    let color = match raw.color.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        None => normalize_color(None),
+        Some(c) => parse_color(c).unwrap_or_else(|| {
+            v.push(FieldError::unprocessable("color", "invalid_color").arg("color", c));
+            String::new()
+        }),
+    };
+    validation::note_text(&mut v, limits, raw.title.as_deref(), raw.content.as_deref());
+
+    let mut coordinate = |field: &'static str, value: Option<f64>| {
+        let value = value.ok_or_else(|| {
+            FieldError::unprocessable(field, "geometry_missing")
+        });
+        v.check(value.and_then(|value| validation::coordinate(field, value))).unwrap_or_default()
+    };
+    let x = coordinate("x", raw.x);
+    let y = coordinate("y", raw.y);
+    let width = v.check(validation::size("width", raw.width, DEFAULT_WIDTH)).unwrap_or(DEFAULT_WIDTH);
+    let height = v.check(validation::size("height", raw.height, DEFAULT_HEIGHT)).unwrap_or(DEFAULT_HEIGHT);
+
+    let errors = v.into_issues();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(NewNote {
+        title: raw.title,
+        content: raw.content,
+        color,
+        x,
+        y,
+        width,
+        height,
+        z_index: raw.z_index.unwrap_or(0),
+        // Author IDs from another instance only survive if they belong here.
+        created_by: raw.created_by.filter(|id| members.contains(id)),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/snapshot.svg",
+    tag = "boards",
+    params(("id" = i64, Path, description = "グループID")),
+    responses(
+        (status = 200, description = "SVG 画像", content_type = "image/svg+xml", body = String),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+async fn group_snapshot_svg(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let svg = render_group_svg(&state.db, group_id).await?;
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}/snapshot.png",
+    tag = "boards",
+    params(("id" = i64, Path, description = "グループID")),
+    responses(
+        (status = 200, description = "PNG 画像", content_type = "image/png"),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+async fn group_snapshot_png(
+    State(state): State<Arc<AppState>>,
+    Path(group_id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let svg = render_group_svg(&state.db, group_id).await?;
+    let png = tokio::task::spawn_blocking(move || snapshot::render_png(&svg))
+        .await
+        .map_err(ApiError::internal)?
+        .map_err(ApiError::internal)?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+async fn render_group_svg(db: &Db, group_id: i64) -> Result<String, ApiError> {
+    if group_id <= 0 {
+        return Err(ApiError::bad_request("invalid_group_id"));
+    }
+    let group = db
+        .get_group(group_id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("group_not_found"))?;
+    let notes = db
+        .list_notes_for_group(group_id)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(snapshot::render_svg(&group.group_name, &notes))
+}
+
 // -------------------------------------------------------------------
-// Debug
+// Admin
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/backups",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, body = BackupsResponse),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+async fn list_backups(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<BackupsResponse>, ApiError> {
+    require_admin(&state, &headers)?;
+    let backups = backup::list_backups(&state.backups.dir).map_err(ApiError::internal)?;
+    Ok(Json(BackupsResponse { backups }))
+}
 
-#[derive(Serialize)]
-struct DebugInfo {
-    database_url: String,
-    db_file_path: Option<String>,
-    file_exists: bool,
-    file_size: Option<u64>,
-    total_notes: i64,
+#[utoipa::path(
+    post,
+    path = "/api/admin/backups",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, body = BackupFile),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+async fn create_backup(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<BackupFile>, ApiError> {
+    require_admin(&state, &headers)?;
+    // Only SQLite files can be copied with VACUUM INTO; an in-memory one
+    // would be copied to memory as well, and Postgres has pg_dump.
+    if db::db_file_path_from_url(&state.database_url).is_none() {
+        return Err(ApiError::unprocessable("backup_unavailable"));
+    }
+    let path = backup::create_backup(&state.db, &state.backups)
+        .await
+        .map_err(|e| ApiError::internal_with(Message::new("backup_failed").arg("detail", format!("{e:#}"))))?;
+    let size = std::fs::metadata(&path).map_err(ApiError::internal)?.len();
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Json(BackupFile { file_name, size }))
 }
 
-async fn debug(State(state): State<Arc<AppState>>) -> Result<Json<DebugInfo>, ApiError> {
+// -------------------------------------------------------------------
+// Debug
+
+#[utoipa::path(
+    get,
+    path = "/api/debug",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, body = DebugInfo),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+async fn debug(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<DebugInfo>, ApiError> {
+    require_admin(&state, &headers)?;
     let total_notes = state.db.count_notes().await.map_err(ApiError::internal)?;
     let path = db::db_file_path_from_url(&state.database_url);
     let (file_exists, file_size) = if let Some(p) = path.as_deref() {
@@ -400,38 +1388,19 @@ async fn debug(State(state): State<Arc<AppState>>) -> Result<Json<DebugInfo>, Ap
 // -------------------------------------------------------------------
 // Shared helpers & DTOs
 
-#[derive(Serialize)]
-struct AccountSummary {
-    id: i64,
-    name: String,
-    email: String,
-    created_at: String,
-}
-
 impl From<Account> for AccountSummary {
     fn from(a: Account) -> Self {
         Self {
             id: a.id,
             name: a.name,
             email: a.email,
+            locale: a.locale,
+            email_verified: a.email_verified_at.is_some(),
             created_at: a.created_at,
         }
     }
 }
 
-#[derive(Serialize)]
-struct AccountsResponse {
-    accounts: Vec<AccountSummary>,
-}
-
-#[derive(Serialize)]
-struct GroupSummary {
-    id: i64,
-    group_name: String,
-    created_by: i64,
-    created_at: String,
-}
-
 impl From<Group> for GroupSummary {
     fn from(g: Group) -> Self {
         Self {
@@ -443,15 +1412,6 @@ impl From<Group> for GroupSummary {
     }
 }
 
-#[derive(Serialize)]
-struct GroupMembership {
-    id: i64,
-    group_name: String,
-    created_by: i64,
-    created_at: String,
-    role: String,
-}
-
 impl From<GroupWithRole> for GroupMembership {
     fn from(g: GroupWithRole) -> Self {
         Self {
@@ -464,172 +1424,354 @@ impl From<GroupWithRole> for GroupMembership {
     }
 }
 
-#[derive(Serialize)]
-struct GroupsResponse {
-    groups: Vec<GroupMembership>,
+impl From<GroupUser> for GroupMember {
+    fn from(m: GroupUser) -> Self {
+        Self {
+            id: m.id,
+            group_id: m.group_id,
+            user_id: m.user_id,
+            role: m.role,
+            joined_at: m.joined_at,
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct GroupMembersResponse {
-    members: Vec<GroupUser>,
+impl From<SharedNote> for Note {
+    fn from(n: SharedNote) -> Self {
+        Self {
+            id: n.id,
+            title: n.title,
+            content: n.content,
+            color: n.color,
+            x: n.x,
+            y: n.y,
+            width: n.width,
+            height: n.height,
+            z_index: n.z_index,
+            created_by: n.created_by,
+            created_at: n.created_at,
+            updated_at: n.updated_at,
+            group_id: n.group_id,
+            can_edit: n.can_edit,
+            shared_at: n.shared_at,
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct NotesResponse {
-    notes: Vec<SharedNote>,
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    /// Rendered in the request's locale.
+    message: Message,
+    /// Sent as `Retry-After` (seconds) with 429 responses.
+    retry_after: Option<u64>,
+    /// Field-level details of a validation failure.
+    fields: Vec<FieldIssue>,
 }
 
-#[derive(Serialize)]
-struct CreateNoteResponse {
-    id: i64,
+impl ApiError {
+    /// The message in the current locale.
+    pub(crate) fn message(&self) -> String {
+        self.message.to_string()
+    }
+
+    pub(crate) fn fields(&self) -> &[FieldIssue] {
+        &self.fields
+    }
+
+    fn new(status: StatusCode, code: &'static str, message: Message) -> Self {
+        Self { status, code, message, retry_after: None, fields: Vec::new() }
+    }
+    /// The constructors below take the message of the same catalog key as
+    /// the code.
+    fn bad_request(code: &'static str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, Message::new(code))
+    }
+    fn unprocessable(code: &'static str) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, code, Message::new(code))
+    }
+    fn unauthorized(code: &'static str) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, Message::new(code))
+    }
+    fn forbidden(code: &'static str) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, Message::new(code))
+    }
+    fn not_found(code: &'static str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, Message::new(code))
+    }
+    fn gone(code: &'static str) -> Self {
+        Self::new(StatusCode::GONE, code, Message::new(code))
+    }
+    fn too_many_requests(retry_after: Duration) -> Self {
+        // Round up so a client waiting exactly this long gets through.
+        let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let message = Message::new("rate_limited").arg("secs", secs);
+        Self { retry_after: Some(secs), ..Self::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", message) }
+    }
+    fn internal(e: impl std::fmt::Display) -> Self {
+        Self::internal_with(Message::new("internal").arg("detail", e))
+    }
+    fn internal_with(message: Message) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", message)
+    }
+
+    /// Uses another catalog entry than the code's own.
+    fn with_message(mut self, message: Message) -> Self {
+        self.message = message;
+        self
+    }
 }
 
-#[derive(Serialize)]
-struct ClearResponse {
-    removed: u64,
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let body = Json(ErrorBody {
+            code: self.code.to_string(),
+            message: self.message.to_string(),
+            request_id: telemetry::current_request_id(),
+            fields: self.fields,
+        });
+        let mut response = (self.status, body).into_response();
+        if let Some(secs) = self.retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, secs.into());
+        }
+        response
+    }
 }
 
-#[derive(Deserialize)]
-struct CreateAccountRequest {
-    name: String,
-    email: String,
-    password: String,
+/// Records problems with the paging parameters in `v`; `None` if there were
+/// any.
+fn page_request<S: SortKey + Default>(v: &mut Validator, params: &PageParams) -> Option<PageRequest<S>> {
+    let invalid_cursor = || FieldError::bad_request("cursor", "invalid_cursor");
+    let mut valid = true;
+    let (sort, descending) = match params.sort.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        None => (S::default(), false),
+        Some(raw) => {
+            let (name, descending) = match raw.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (raw, false),
+            };
+            let sort = S::parse(name).unwrap_or_else(|| {
+                v.push(FieldError::bad_request("sort", "invalid_sort").arg("name", name));
+                valid = false;
+                S::default()
+            });
+            (sort, descending)
+        }
+    };
+    if params.limit.is_some_and(|l| l == 0 || l > MAX_PAGE_SIZE) {
+        v.push(FieldError::bad_request("limit", "invalid_limit").arg("max", MAX_PAGE_SIZE));
+        valid = false;
+    }
+    let after = match params.cursor.as_deref().filter(|c| !c.is_empty()) {
+        None => None,
+        Some(raw) => match Cursor::decode(raw) {
+            Some(cursor) => Some(cursor),
+            None => {
+                v.push(invalid_cursor());
+                return None;
+            }
+        },
+    };
+    if !valid {
+        return None;
+    }
+    // The cursor can only be checked against a valid sort.
+    let req = PageRequest::new(sort, descending, params.limit, after);
+    if req.is_none() {
+        v.push(invalid_cursor());
+    }
+    req
 }
 
-#[derive(Deserialize)]
-struct CreateGroupRequest {
-    group_name: String,
-    created_by: i64,
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DDTHH:MM:SS[Z]`
+/// and returns the stored `YYYY-MM-DD HH:MM:SS` form.
+fn normalize_timestamp(raw: &str) -> Option<String> {
+    let raw = raw.trim().trim_end_matches('Z');
+    let normalized = match raw.len() {
+        10 => format!("{raw} 00:00:00"),
+        19 => raw.replacen('T', " ", 1),
+        _ => return None,
+    };
+    let valid = normalized.char_indices().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        10 => c == ' ',
+        13 | 16 => c == ':',
+        _ => c.is_ascii_digit(),
+    });
+    valid.then_some(normalized)
 }
 
-#[derive(Deserialize)]
-struct JoinGroupRequest {
-    user_id: i64,
-    role: Option<String>,
+/// Middleware choosing the request's locale: the one `Accept-Language` asks
+/// for, else the signed-in account's preference, else Japanese.
+async fn negotiate_locale(req: Request, next: Next) -> Response {
+    let locale = i18n::requested(req.headers())
+        .or_else(|| req.extensions().get::<Session>().and_then(Session::locale))
+        .unwrap_or_default();
+    i18n::respond_in(locale, req, next).await
 }
 
-#[derive(Deserialize)]
-struct CreateNoteRequest {
-    title: Option<String>,
-    content: Option<String>,
-    color: Option<String>,
-    x: f64,
-    y: f64,
-    width: Option<f64>,
-    height: Option<f64>,
-    z_index: Option<i64>,
-    created_by: Option<i64>,
-    can_edit: Option<bool>,
+/// Middleware applying the [`RateLimiter`] bucket of the matched route's
+/// group, for the signed-in account or else the client's address. It runs
+/// before the session is resolved, so rejected requests cost no query.
+async fn rate_limit(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let limiter = &state.rate_limiter;
+    let group = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| RouteGroup::classify(req.method(), route.as_str()))
+        .filter(|group| limiter.is_limited(*group));
+    if let Some(group) = group {
+        let client = limiter.client_key(&req, auth::bearer_digest(req.headers()).as_deref());
+        if let Err(wait) = limiter.check(group, client) {
+            tracing::warn!(?group, ?client, "rate limited");
+            // Without the session only `Accept-Language` can pick the locale.
+            let locale = i18n::requested(req.headers()).unwrap_or_default();
+            return i18n::render_in(locale, ApiError::too_many_requests(wait));
+        }
+    }
+    next.run(req).await
 }
 
-#[derive(Deserialize)]
-struct UpdateNotePositionRequest {
-    x: f64,
-    y: f64,
-    width: Option<f64>,
-    height: Option<f64>,
-    z_index: Option<i64>,
+/// Lets the signed-in account change itself, and the admin any account.
+fn authorize_account(state: &AppState, headers: &HeaderMap, session: &Session, id: i64) -> Result<(), ApiError> {
+    if require_admin(state, headers).is_ok() {
+        return Ok(());
+    }
+    if session.account()?.id != id {
+        return Err(ApiError::forbidden("account_forbidden"));
+    }
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct UpdateNoteContentRequest {
-    title: Option<String>,
-    content: Option<String>,
-    color: Option<String>,
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err(ApiError::forbidden("admin_disabled"));
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::unauthorized("admin_required"))?;
+    // Compare digests so the check does not leak the token length or prefix.
+    if Sha256::digest(provided.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+        return Err(ApiError::forbidden("admin_token_invalid"));
+    }
+    Ok(())
 }
 
-#[derive(Serialize)]
-struct ErrorBody<'a> {
-    code: &'a str,
-    message: String,
+/// Checks already-trimmed sign-up fields. Shared with `app create-account`.
+pub(crate) fn validate_new_account(
+    limits: &LimitsConfig,
+    name: &str,
+    email: &str,
+    password: &str,
+) -> Result<(), ApiError> {
+    let mut v = Validator::new();
+    check_new_account(&mut v, limits, name, email, password);
+    v.finish()
 }
 
-#[derive(Debug)]
-pub struct ApiError {
-    status: StatusCode,
-    code: &'static str,
-    message: String,
+fn check_new_account(v: &mut Validator, limits: &LimitsConfig, name: &str, email: &str, password: &str) {
+    check_name(v, limits, name);
+    check_email(v, email);
+    v.check(check_password("password", password));
 }
 
-impl ApiError {
-    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
-        Self { status, code, message: message.into() }
-    }
-    fn bad_request(code: &'static str, msg: impl Into<String>) -> Self {
-        Self::new(StatusCode::BAD_REQUEST, code, msg)
-    }
-    fn unprocessable(code: &'static str, msg: impl Into<String>) -> Self {
-        Self::new(StatusCode::UNPROCESSABLE_ENTITY, code, msg)
+fn check_name(v: &mut Validator, limits: &LimitsConfig, name: &str) {
+    if name.is_empty() {
+        v.push(FieldError::bad_request("name", "name_empty"));
     }
-    fn not_found(code: &'static str, msg: impl Into<String>) -> Self {
-        Self::new(StatusCode::NOT_FOUND, code, msg)
+    v.check(validation::check_len("name", name, limits.max_name_len));
+}
+
+fn check_email(v: &mut Validator, email: &str) {
+    if email.is_empty() {
+        v.push(FieldError::bad_request("email", "email_empty"));
+    } else if !crate::mail::is_address(email) {
+        v.push(FieldError::unprocessable("email", "email_invalid"));
     }
-    fn internal(e: impl std::fmt::Display) -> Self {
-        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", e.to_string())
+    v.check(validation::check_len("email", email, MAX_EMAIL_LEN));
+}
+
+pub(crate) fn validate_password(password: &str) -> Result<(), ApiError> {
+    Ok(check_password("password", password)?)
+}
+
+fn check_password(field: &str, password: &str) -> Result<(), FieldError> {
+    if password.len() < 6 {
+        return Err(FieldError::unprocessable(field, "password_short"));
     }
+    Ok(())
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> axum::response::Response {
-        let body = Json(ErrorBody { code: self.code, message: self.message });
-        (self.status, body).into_response()
+/// Account changes that could lock the owner out take the current password.
+fn verify_current_password(account: &Account, password: &str) -> Result<(), FieldError> {
+    if password.is_empty() {
+        return Err(FieldError::bad_request("current_password", "password_required"));
+    }
+    if hash_password(password) != account.password_hash {
+        return Err(FieldError::forbidden("current_password", "password_incorrect"));
     }
+    Ok(())
 }
 
-fn hash_password(password: &str) -> String {
+pub(crate) fn hash_password(password: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(password.as_bytes());
     let digest = hasher.finalize();
     format!("{:x}", digest)
 }
 
-fn ensure_account_exists(db: &Db, account_id: i64) -> impl std::future::Future<Output = Result<(), ApiError>> + '_ {
-    async move {
-        let exists = db
-            .get_account(account_id)
-            .await
-            .map_err(ApiError::internal)?
-            .is_some();
-        if exists {
-            Ok(())
-        } else {
-            Err(ApiError::not_found("account_not_found", "ユーザーが存在しません"))
-        }
-    }
+async fn fetch_account(db: &Db, account_id: i64) -> Result<Account, ApiError> {
+    db.get_account(account_id)
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found("account_not_found"))
 }
 
-fn ensure_group_exists(db: &Db, group_id: i64) -> impl std::future::Future<Output = Result<(), ApiError>> + '_ {
-    async move {
-        let exists = db
-            .get_group(group_id)
-            .await
-            .map_err(ApiError::internal)?
-            .is_some();
-        if exists {
-            Ok(())
-        } else {
-            Err(ApiError::not_found("group_not_found", "グループが存在しません"))
-        }
+async fn ensure_account_exists(db: &Db, account_id: i64) -> Result<(), ApiError> {
+    fetch_account(db, account_id).await.map(|_| ())
+}
+
+async fn ensure_group_exists(db: &Db, group_id: i64) -> Result<(), ApiError> {
+    let exists = db
+        .get_group(group_id)
+        .await
+        .map_err(ApiError::internal)?
+        .is_some();
+    if exists {
+        Ok(())
+    } else {
+        Err(ApiError::not_found("group_not_found"))
     }
 }
 
+const MAX_BATCH_OPS: usize = 500;
+const MAX_LOOKUP_RESULTS: u32 = 20;
+/// The longest address SMTP can carry.
+const MAX_EMAIL_LEN: usize = 254;
+
 fn normalize_color(input: Option<&str>) -> String {
-    let default = "#FFFF88".to_string();
-    let Some(raw) = input else { return default; };
+    input
+        .and_then(parse_color)
+        .unwrap_or_else(|| "#FFFF88".to_string())
+}
+
+/// Returns `None` for input that is neither `#RRGGBB` nor a palette name.
+fn parse_color(raw: &str) -> Option<String> {
     let trimmed = raw.trim();
-    if trimmed.is_empty() {
-        return default;
-    }
     if trimmed.starts_with('#') && trimmed.len() == 7 && trimmed.chars().skip(1).all(|c| c.is_ascii_hexdigit()) {
-        return trimmed.to_uppercase();
-    }
-    match trimmed.to_lowercase().as_str() {
-        "yellow" => "#FFFF88".to_string(),
-        "pink" => "#FBCFE8".to_string(),
-        "green" => "#BBF7D0".to_string(),
-        "blue" => "#BFDBFE".to_string(),
-        "orange" => "#FED7AA".to_string(),
-        "purple" => "#E9D5FF".to_string(),
-        _ => default,
+        return Some(trimmed.to_uppercase());
     }
+    let hex = match trimmed.to_lowercase().as_str() {
+        "yellow" => "#FFFF88",
+        "pink" => "#FBCFE8",
+        "green" => "#BBF7D0",
+        "blue" => "#BFDBFE",
+        "orange" => "#FED7AA",
+        "purple" => "#E9D5FF",
+        _ => return None,
+    };
+    Some(hex.to_string())
 }
@@ -0,0 +1,301 @@
+//! Sign-in, address verification and password reset, under `/api/auth` so
+//! the rate limiter's auth group covers them. Sessions are bearer tokens;
+//! the others go out by mail and each works once. Only the SHA-256 digest
+//! of any token is stored, and all of them expire. The endpoints taking an
+//! address answer 202 whether or not an account has it, so they cannot be
+//! used to find out who is registered.
+
+use super::*;
+use crate::db::TokenPurpose;
+use crate::i18n::Locale;
+use api_types::{EmailRequest, LoginRequest, LoginResponse, ResetPasswordRequest, VerifyEmailRequest};
+
+/// How long a sign-in lasts.
+const SESSION_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Unknown addresses and wrong passwords get the same answer.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+pub(super) async fn login(
+    State(state): State<Arc<AppState>>,
+    JsonPayload(payload): JsonPayload<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let email = payload.email.trim();
+    let password = payload.password.trim();
+    let mut v = Validator::new();
+    if email.is_empty() {
+        v.push(FieldError::bad_request("email", "email_empty"));
+    }
+    if password.is_empty() {
+        v.push(FieldError::bad_request("password", "password_empty"));
+    }
+    v.finish()?;
+
+    let account = state
+        .db
+        .find_account_by_email(email)
+        .await
+        .map_err(ApiError::internal)?
+        .filter(|account| hash_password(password) == account.password_hash)
+        .ok_or_else(|| ApiError::unauthorized("login_failed"))?;
+    telemetry::record_account(account.id);
+    let token = issue_token(&state.db, account.id, TokenPurpose::Session, SESSION_TTL).await?;
+    state.rate_limiter.remember_session(&token_digest(&token), account.id);
+    Ok(Json(LoginResponse { token, account: AccountSummary::from(account) }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "auth",
+    security(("session" = [])),
+    responses(
+        (status = 204, description = "セッションを終了した"),
+        (status = 401, body = ErrorBody),
+    )
+)]
+pub(super) async fn logout(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Result<StatusCode, ApiError> {
+    let token = session_token(&headers)?;
+    let revoked = state
+        .db
+        .revoke_account_token(TokenPurpose::Session, &token_digest(token))
+        .await
+        .map_err(ApiError::internal)?;
+    if !revoked {
+        return Err(ApiError::unauthorized("session_invalid"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify-email",
+    tag = "auth",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 204, description = "メールアドレスを確認済みにした"),
+        (status = 400, body = ErrorBody),
+    )
+)]
+pub(super) async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    JsonPayload(payload): JsonPayload<VerifyEmailRequest>,
+) -> Result<StatusCode, ApiError> {
+    let account_id = consume_token(&state.db, TokenPurpose::VerifyEmail, &payload.token).await?;
+    state.db.mark_email_verified(account_id).await.map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Sends a new verification mail unless the address is unknown or already
+/// confirmed. Earlier links keep working until one of them is used.
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify-email/resend",
+    tag = "auth",
+    request_body = EmailRequest,
+    responses(
+        (status = 202, description = "該当するアカウントがあれば確認メールを送った"),
+        (status = 400, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+pub(super) async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    JsonPayload(payload): JsonPayload<EmailRequest>,
+) -> Result<StatusCode, ApiError> {
+    if let Some(account) = find_account(&state.db, &payload.email).await? {
+        if account.email_verified_at.is_none() {
+            send_verification(&state, &account).await?;
+        }
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/password-reset",
+    tag = "auth",
+    request_body = EmailRequest,
+    responses(
+        (status = 202, description = "該当するアカウントがあれば再設定メールを送った"),
+        (status = 400, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+pub(super) async fn request_password_reset(
+    State(state): State<Arc<AppState>>,
+    JsonPayload(payload): JsonPayload<EmailRequest>,
+) -> Result<StatusCode, ApiError> {
+    if let Some(account) = find_account(&state.db, &payload.email).await? {
+        let token = issue_token(&state.db, account.id, TokenPurpose::ResetPassword, state.mail.reset_ttl()).await?;
+        state.mail.send_later(state.mail.password_reset(&account, &token));
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Sets the new password, spends every open reset token of the account and
+/// ends its sessions.
+/// Following the link proves the address works, so it counts as verified too.
+#[utoipa::path(
+    post,
+    path = "/api/auth/password-reset/confirm",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 204, description = "パスワードを変更した"),
+        (status = 400, body = ErrorBody),
+        (status = 422, body = ErrorBody),
+    )
+)]
+pub(super) async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    JsonPayload(payload): JsonPayload<ResetPasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    let token = payload.token.trim();
+    let password = payload.password.trim();
+    let mut v = Validator::new();
+    if token.is_empty() {
+        v.push(invalid_token());
+    }
+    v.check(check_password("password", password));
+    v.finish()?;
+
+    let account_id = consume_token(&state.db, TokenPurpose::ResetPassword, token).await?;
+    state
+        .db
+        .set_password_hash(account_id, &hash_password(password))
+        .await
+        .map_err(ApiError::internal)?;
+    state.db.mark_email_verified(account_id).await.map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// What the request's bearer token turned out to be. [`resolve_session`]
+/// works it out once per request and leaves it in the request extensions,
+/// for the locale negotiation, the rate limiter and the handlers.
+#[derive(Debug, Clone)]
+pub(super) enum Session {
+    /// No bearer token.
+    Anonymous,
+    SignedIn(Account),
+    /// Not a live session: expired, revoked or made up, or the admin token.
+    Invalid,
+    /// The lookup failed; handlers needing the account answer 500.
+    Failed(String),
+}
+
+impl Session {
+    /// The signed-in account, or the error for a request that needs one.
+    pub(super) fn account(&self) -> Result<&Account, ApiError> {
+        match self {
+            Self::SignedIn(account) => {
+                telemetry::record_account(account.id);
+                Ok(account)
+            }
+            Self::Anonymous => Err(ApiError::unauthorized("login_required")),
+            Self::Invalid => Err(ApiError::unauthorized("session_invalid")),
+            Self::Failed(e) => Err(ApiError::internal(e)),
+        }
+    }
+
+    /// The signed-in account's preferred locale, if it has chosen one.
+    pub(super) fn locale(&self) -> Option<Locale> {
+        match self {
+            Self::SignedIn(account) => account.locale.as_deref().and_then(Locale::parse),
+            _ => None,
+        }
+    }
+}
+
+/// Middleware looking up the request's [`Session`], in one query.
+pub(super) async fn resolve_session(State(state): State<Arc<AppState>>, mut req: Request, next: Next) -> Response {
+    let session = match session_token(req.headers()) {
+        Err(_) => Session::Anonymous,
+        // Never a session, so not worth a query.
+        Ok(_) if require_admin(&state, req.headers()).is_ok() => Session::Invalid,
+        Ok(token) => {
+            let digest = token_digest(token);
+            match state.db.token_account(TokenPurpose::Session, &digest).await {
+                Ok(Some(account)) => {
+                    state.rate_limiter.remember_session(&digest, account.id);
+                    Session::SignedIn(account)
+                }
+                Ok(None) => Session::Invalid,
+                Err(e) => {
+                    tracing::error!("failed to look up the session: {e:#}");
+                    Session::Failed(format!("{e:#}"))
+                }
+            }
+        }
+    };
+    req.extensions_mut().insert(session);
+    next.run(req).await
+}
+
+/// Digest of the request's bearer token, if it has one.
+pub(super) fn bearer_digest(headers: &HeaderMap) -> Option<String> {
+    session_token(headers).ok().map(token_digest)
+}
+
+fn session_token(headers: &HeaderMap) -> Result<&str, ApiError> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| ApiError::unauthorized("login_required"))
+}
+
+/// Mails `account` a fresh verification link. Also used at sign-up.
+pub(super) async fn send_verification(state: &AppState, account: &Account) -> Result<(), ApiError> {
+    let token = issue_token(&state.db, account.id, TokenPurpose::VerifyEmail, state.mail.verify_ttl()).await?;
+    state.mail.send_later(state.mail.verification(account, &token));
+    Ok(())
+}
+
+/// The account with this address, after checking it looks like one.
+async fn find_account(db: &Db, email: &str) -> Result<Option<Account>, ApiError> {
+    let email = email.trim();
+    let mut v = Validator::new();
+    check_email(&mut v, email);
+    v.finish()?;
+    db.find_account_by_email(email).await.map_err(ApiError::internal)
+}
+
+async fn issue_token(db: &Db, account_id: i64, purpose: TokenPurpose, ttl: Duration) -> Result<String, ApiError> {
+    // Two v4 UUIDs: 244 random bits.
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    db.create_account_token(account_id, purpose, &token_digest(&token), ttl)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(token)
+}
+
+async fn consume_token(db: &Db, purpose: TokenPurpose, token: &str) -> Result<i64, ApiError> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(invalid_token().into());
+    }
+    db.consume_account_token(purpose, &token_digest(token))
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| invalid_token().into())
+}
+
+fn invalid_token() -> FieldError {
+    FieldError::bad_request("token", "token_invalid")
+}
+
+fn token_digest(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
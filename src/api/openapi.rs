@@ -0,0 +1,256 @@
+//! OpenAPI 3 document for the routes in `api.rs`, served as
+//! `/api/openapi.json` with a Redoc page at `/api/docs`. Handlers and DTOs
+//! carry the annotations; this module only lists them.
+
+use super::*;
+use api_types::{
+    EmailRequest, HealthStatus, LoginRequest, LoginResponse, ReadinessCheck, ReadinessReport, ResetPasswordRequest,
+    VerifyEmailRequest,
+};
+use crate::rate_limit::RouteGroup;
+use axum::http::Method;
+use utoipa::openapi::header::HeaderBuilder;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, ObjectBuilder, Ref, ResponseBuilder, SchemaType};
+use utoipa::{Modify, OpenApi};
+use utoipa_redoc::{Redoc, Servable};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "rust-llm-api", description = "付箋ボード API"),
+    paths(
+        list_accounts,
+        create_account,
+        current_account,
+        lookup_accounts,
+        update_account,
+        change_password,
+        delete_account,
+        list_groups_for_user,
+        auth::login,
+        auth::logout,
+        auth::verify_email,
+        auth::resend_verification,
+        auth::request_password_reset,
+        auth::reset_password,
+        create_group,
+        get_group,
+        list_group_members,
+        add_user_to_group,
+        list_group_notes,
+        create_group_note,
+        clear_group_notes,
+        group_note_changes,
+        batch_group_notes,
+        export_group,
+        import_group_notes,
+        group_snapshot_svg,
+        group_snapshot_png,
+        update_note_content,
+        delete_note,
+        update_note_position,
+        list_backups,
+        create_backup,
+        debug,
+        openapi_json,
+        ops::healthz,
+        ops::readyz,
+        ops::metrics,
+    ),
+    components(schemas(
+        AccountSummary,
+        AccountsResponse,
+        UserProfile,
+        UserLookupResponse,
+        CreateAccountRequest,
+        UpdateAccountRequest,
+        ChangePasswordRequest,
+        DeleteAccountRequest,
+        DeleteAccountResponse,
+        LoginRequest,
+        LoginResponse,
+        VerifyEmailRequest,
+        EmailRequest,
+        ResetPasswordRequest,
+        GroupSummary,
+        GroupMembership,
+        GroupsResponse,
+        GroupMembersResponse,
+        GroupMember,
+        CreateGroupRequest,
+        JoinGroupRequest,
+        Note,
+        NotesResponse,
+        NoteChangesResponse,
+        CreateNoteRequest,
+        CreateNoteResponse,
+        UpdateNotePositionRequest,
+        UpdateNoteContentRequest,
+        BatchRequest,
+        BatchOpRequest,
+        BatchReport,
+        BatchOpResult,
+        ClearResponse,
+        ImportReport,
+        ImportRowResult,
+        ImportIssue,
+        BackupFile,
+        BackupsResponse,
+        DebugInfo,
+        HealthStatus,
+        ReadinessReport,
+        ReadinessCheck,
+        ErrorBody,
+        FieldIssue,
+    )),
+    modifiers(&BearerTokens, &RateLimited),
+    tags(
+        (name = "accounts"),
+        (name = "auth", description = "ログイン、メールアドレスの確認とパスワードの再設定"),
+        (name = "groups"),
+        (name = "notes"),
+        (name = "boards", description = "エクスポート / インポート / スナップショット"),
+        (name = "admin", description = "`ADMIN_TOKEN` が必要"),
+        (name = "ops", description = "ヘルスチェックと Prometheus メトリクス"),
+        (name = "misc"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the bearer schemes the operations reference: `admin_token` for
+/// the operator endpoints and `session` for those acting as the signed-in
+/// account.
+struct BearerTokens;
+
+impl Modify for BearerTokens {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            for name in ["admin_token", "session"] {
+                components.add_security_scheme(
+                    name,
+                    SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+                );
+            }
+        }
+    }
+}
+
+/// Adds the 429 answer to every operation [`RouteGroup::classify`] limits.
+struct RateLimited;
+
+impl Modify for RateLimited {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let retry_after = HeaderBuilder::new()
+            .schema(ObjectBuilder::new().schema_type(SchemaType::Integer))
+            .description(Some("再試行までの秒数"))
+            .build();
+        let response = ResponseBuilder::new()
+            .description("レート制限を超えた")
+            .header("Retry-After", retry_after)
+            .content("application/json", ContentBuilder::new().schema(Ref::from_schema_name("ErrorBody")).build())
+            .build();
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            for (kind, operation) in item.operations.iter_mut() {
+                let method = match kind {
+                    PathItemType::Get => Method::GET,
+                    PathItemType::Post => Method::POST,
+                    PathItemType::Put => Method::PUT,
+                    PathItemType::Delete => Method::DELETE,
+                    PathItemType::Patch => Method::PATCH,
+                    _ => continue,
+                };
+                if RouteGroup::classify(&method, path).is_some() {
+                    operation.responses.responses.insert("429".to_string(), response.clone().into());
+                }
+            }
+        }
+    }
+}
+
+pub fn docs() -> Redoc<utoipa::openapi::OpenApi> {
+    Redoc::with_url("/api/docs", ApiDoc::openapi())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "misc",
+    responses(
+        (status = 200, description = "この API の OpenAPI 3 ドキュメント"),
+    )
+)]
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiDoc;
+    use utoipa::OpenApi;
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    /// `(method, path)` for every `.route(...)` call in `api.rs`, with axum's
+    /// `:param` segments rewritten to OpenAPI's `{param}`.
+    fn declared_routes() -> Vec<(String, String)> {
+        let source = include_str!("../api.rs");
+        let mut routes = Vec::new();
+        for call in source.split(".route(").skip(1) {
+            let mut depth = 1;
+            let end = call
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map(|(i, _)| i)
+                .expect("unbalanced .route( call");
+            let call = &call[..end];
+            let path = call.split('"').nth(1).expect("route path literal");
+            let path = path
+                .split('/')
+                .map(|seg| match seg.strip_prefix(':') {
+                    Some(param) => format!("{{{param}}}"),
+                    None => seg.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            let handlers = call[call.find(',').expect("route handlers") + 1..].trim_start();
+            for method in METHODS {
+                if handlers.starts_with(&format!("{method}(")) || handlers.contains(&format!(".{method}(")) {
+                    routes.push((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    fn documented_routes() -> Vec<(String, String)> {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = Vec::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in METHODS {
+                if item.get(method).is_some() {
+                    routes.push((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let declared = declared_routes();
+        assert!(declared.len() > 20, "route parsing found only {declared:?}");
+        let documented = documented_routes();
+        let missing: Vec<_> = declared.iter().filter(|r| !documented.contains(r)).collect();
+        assert!(missing.is_empty(), "routes missing from the OpenAPI spec: {missing:?}");
+        let stale: Vec<_> = documented.iter().filter(|r| !declared.contains(r)).collect();
+        assert!(stale.is_empty(), "OpenAPI operations without a route: {stale:?}");
+    }
+}
@@ -0,0 +1,117 @@
+//! Probes and metrics for orchestrators and Prometheus. These routes sit
+//! outside `/api` so load balancers can reach them without knowing the API
+//! layout, and they never require the admin token.
+
+use super::*;
+use crate::db::SCHEMA_VERSION;
+use crate::metrics;
+use api_types::{HealthStatus, ReadinessCheck, ReadinessReport};
+use std::time::Duration;
+
+/// How long each readiness check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "ops",
+    responses(
+        (status = 200, description = "プロセスが応答している", body = HealthStatus),
+    )
+)]
+pub(super) async fn healthz() -> Json<HealthStatus> {
+    Json(HealthStatus { status: "ok".to_string() })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "ops",
+    responses(
+        (status = 200, description = "リクエストを受け付けられる", body = ReadinessReport),
+        (status = 503, description = "必須のチェックが失敗した", body = ReadinessReport),
+    )
+)]
+pub(super) async fn readyz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut checks = Vec::new();
+    match tokio::time::timeout(CHECK_TIMEOUT, state.db.schema_version()).await {
+        Ok(Ok(version)) => {
+            checks.push(check("database", true, Ok(())));
+            let migrations = if version == SCHEMA_VERSION {
+                Ok(())
+            } else {
+                Err(format!("schema version {version}, expected {SCHEMA_VERSION}"))
+            };
+            checks.push(check("migrations", true, migrations));
+        }
+        Ok(Err(e)) => {
+            tracing::warn!("readiness: database check failed: {e:#}");
+            checks.push(check("database", true, Err("query failed".to_string())));
+            checks.push(skipped("migrations", true));
+        }
+        Err(_) => {
+            tracing::warn!("readiness: database check timed out");
+            checks.push(check("database", true, Err("timed out".to_string())));
+            checks.push(skipped("migrations", true));
+        }
+    }
+    checks.push(match state.llm_endpoint.as_deref() {
+        Some(endpoint) => check("llm", false, probe_tcp(endpoint).await),
+        None => skipped("llm", false),
+    });
+
+    let ready = checks.iter().all(|c| !c.required || c.status != "failed");
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let report = ReadinessReport {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        checks,
+    };
+    (status, Json(report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "ops",
+    responses(
+        (status = 200, description = "Prometheus テキスト形式 (version 0.0.4)", content_type = "text/plain"),
+    )
+)]
+pub(super) async fn metrics(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, ApiError> {
+    let mut out = String::new();
+    state.metrics.render(&mut out);
+
+    let pool = state.db.pool_stats();
+    metrics::gauge(&mut out, "db_pool_connections", "Open database connections.", pool.size);
+    metrics::gauge(&mut out, "db_pool_idle_connections", "Idle database connections.", pool.idle);
+    metrics::gauge(&mut out, "db_pool_max_connections", "Configured pool size limit.", pool.max);
+
+    let totals = state.db.totals().await.map_err(ApiError::internal)?;
+    metrics::gauge(&mut out, "accounts_total", "Registered accounts.", totals.accounts);
+    metrics::gauge(&mut out, "groups_total", "Groups (boards).", totals.groups);
+    metrics::gauge(&mut out, "notes_total", "Notes across all groups.", totals.notes);
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], out))
+}
+
+fn check(name: &str, required: bool, result: Result<(), String>) -> ReadinessCheck {
+    let (status, detail) = match result {
+        Ok(()) => ("ok", None),
+        Err(detail) => ("failed", Some(detail)),
+    };
+    ReadinessCheck { name: name.to_string(), status: status.to_string(), required, detail }
+}
+
+fn skipped(name: &str, required: bool) -> ReadinessCheck {
+    ReadinessCheck { name: name.to_string(), status: "skipped".to_string(), required, detail: None }
+}
+
+/// Opens (and drops) a TCP connection to `host:port`. Enough to catch DNS
+/// and network problems without spending an API call.
+async fn probe_tcp(endpoint: &str) -> Result<(), String> {
+    match tokio::time::timeout(CHECK_TIMEOUT, tokio::net::TcpStream::connect(endpoint)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("{endpoint}: {e}")),
+        Err(_) => Err(format!("{endpoint}: timed out")),
+    }
+}
@@ -0,0 +1,195 @@
+//! Field checks for request bodies and query strings. A [`Validator`] runs
+//! every check of a request and reports all failures together, so a client
+//! can highlight each invalid input in one round trip. The length and
+//! geometry checks are shared by the single-note, batch and import paths, so
+//! the same input is rejected the same way everywhere. Messages about one
+//! field start with its path.
+
+use super::{ApiError, FieldIssue, ImportIssue};
+use crate::config::LimitsConfig;
+use crate::i18n::Message;
+use axum::http::StatusCode;
+
+pub(super) const MAX_COORDINATE: f64 = 100_000.0;
+pub(super) const MAX_NOTE_SIZE: f64 = 10_000.0;
+pub(super) const DEFAULT_WIDTH: f64 = 200.0;
+pub(super) const DEFAULT_HEIGHT: f64 = 150.0;
+
+/// One rejected field. Missing or malformed input is a 400, well-formed but
+/// unacceptable input a 422, as for [`ApiError`].
+#[derive(Debug)]
+pub(super) struct FieldError {
+    status: StatusCode,
+    field: String,
+    code: &'static str,
+    /// Has the field path as `{field}`.
+    message: Message,
+}
+
+impl FieldError {
+    fn new(status: StatusCode, field: impl Into<String>, code: &'static str) -> Self {
+        let field = field.into();
+        let message = Message::new(code).arg("field", &field);
+        Self { status, field, code, message }
+    }
+
+    pub(super) fn bad_request(field: impl Into<String>, code: &'static str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, field, code)
+    }
+
+    pub(super) fn unprocessable(field: impl Into<String>, code: &'static str) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, field, code)
+    }
+
+    pub(super) fn forbidden(field: impl Into<String>, code: &'static str) -> Self {
+        Self::new(StatusCode::FORBIDDEN, field, code)
+    }
+
+    pub(super) fn conflict(field: impl Into<String>, code: &'static str) -> Self {
+        Self::new(StatusCode::CONFLICT, field, code)
+    }
+
+    pub(super) fn arg(mut self, name: &'static str, value: impl std::fmt::Display) -> Self {
+        self.message = self.message.arg(name, value);
+        self
+    }
+
+    /// Uses another catalog entry than the code's own.
+    fn with_message(mut self, key: &'static str) -> Self {
+        self.message = Message::new(key).arg("field", &self.field);
+        self
+    }
+
+    fn issue(&self) -> FieldIssue {
+        FieldIssue { field: self.field.clone(), code: self.code.to_string(), message: self.message.to_string() }
+    }
+}
+
+impl From<FieldError> for ApiError {
+    fn from(e: FieldError) -> Self {
+        let fields = vec![e.issue()];
+        ApiError { fields, ..ApiError::new(e.status, e.code, e.message) }
+    }
+}
+
+impl From<FieldError> for ImportIssue {
+    fn from(e: FieldError) -> Self {
+        ImportIssue { field: Some(e.field), ..ImportIssue::new(e.code, e.message.to_string()) }
+    }
+}
+
+/// Collects the failures of every check of one request.
+#[derive(Debug, Default)]
+pub(super) struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the failure of `result`, if any, and passes on its value.
+    pub(super) fn check<T>(&mut self, result: Result<T, FieldError>) -> Option<T> {
+        result.map_err(|e| self.errors.push(e)).ok()
+    }
+
+    pub(super) fn push(&mut self, error: FieldError) {
+        self.errors.push(error);
+    }
+
+    /// A single failure is reported as is; several as `validation_failed`
+    /// listing all of them, a 400 if each of them is one and a 422 otherwise.
+    pub(super) fn finish(mut self) -> Result<(), ApiError> {
+        match self.errors.len() {
+            0 => Ok(()),
+            1 => Err(self.errors.remove(0).into()),
+            n => {
+                let status = if self.errors.iter().all(|e| e.status == StatusCode::BAD_REQUEST) {
+                    StatusCode::BAD_REQUEST
+                } else {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                };
+                Err(ApiError {
+                    fields: self.errors.iter().map(FieldError::issue).collect(),
+                    ..ApiError::new(status, "validation_failed", Message::new("validation_failed").arg("count", n))
+                })
+            }
+        }
+    }
+
+    /// Like [`finish`](Self::finish), then passes on `value`, the result of a
+    /// check that records its own failures in this validator.
+    pub(super) fn finish_with<T>(self, value: Option<T>) -> Result<T, ApiError> {
+        self.finish()?;
+        value.ok_or_else(|| ApiError::internal("validation failed without a reported field"))
+    }
+
+    /// The failures as import or batch issues.
+    pub(super) fn into_issues(self) -> Vec<ImportIssue> {
+        self.errors.into_iter().map(ImportIssue::from).collect()
+    }
+}
+
+pub(super) fn check_len(field: &str, value: &str, max: usize) -> Result<(), FieldError> {
+    if value.chars().count() > max {
+        return Err(FieldError::unprocessable(field, "too_long").arg("max", max));
+    }
+    Ok(())
+}
+
+/// A positive ID referring to another record.
+pub(super) fn id(field: &str, value: i64, code: &'static str) -> Result<i64, FieldError> {
+    if value <= 0 {
+        return Err(FieldError::bad_request(field, code));
+    }
+    Ok(value)
+}
+
+/// Title and content of a note, either of which may be absent.
+pub(super) fn note_text(v: &mut Validator, limits: &LimitsConfig, title: Option<&str>, content: Option<&str>) {
+    if let Some(title) = title {
+        v.check(check_len("title", title, limits.max_title_len));
+    }
+    if let Some(content) = content {
+        v.check(check_len("content", content, limits.max_content_len));
+    }
+}
+
+pub(super) fn coordinate(field: &str, value: f64) -> Result<f64, FieldError> {
+    if !value.is_finite() || value.abs() > MAX_COORDINATE {
+        return Err(FieldError::unprocessable(field, "geometry_out_of_range").arg("max", MAX_COORDINATE));
+    }
+    Ok(value)
+}
+
+/// A width or height; `default` when absent.
+pub(super) fn size(field: &str, value: Option<f64>, default: f64) -> Result<f64, FieldError> {
+    match value {
+        None => Ok(default),
+        Some(v) if !v.is_finite() || v <= 0.0 || v > MAX_NOTE_SIZE => Err(FieldError::unprocessable(
+            field,
+            "geometry_out_of_range",
+        )
+        .with_message("size_out_of_range")
+        .arg("max", MAX_NOTE_SIZE)),
+        Some(v) => Ok(v),
+    }
+}
+
+/// `(x, y, width, height)` of a note being placed, with default sizes.
+/// Invalid values are recorded in `v` and come back as placeholders.
+pub(super) fn geometry(
+    v: &mut Validator,
+    x: f64,
+    y: f64,
+    width: Option<f64>,
+    height: Option<f64>,
+) -> (f64, f64, f64, f64) {
+    (
+        v.check(coordinate("x", x)).unwrap_or_default(),
+        v.check(coordinate("y", y)).unwrap_or_default(),
+        v.check(size("width", width, DEFAULT_WIDTH)).unwrap_or(DEFAULT_WIDTH),
+        v.check(size("height", height, DEFAULT_HEIGHT)).unwrap_or(DEFAULT_HEIGHT),
+    )
+}
@@ -0,0 +1,208 @@
+use crate::db::{self, Db, SCHEMA_VERSION};
+use anyhow::{bail, Context, Result};
+use api_types::BackupFile;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::watch;
+
+const FILE_PREFIX: &str = "backup-";
+const FILE_SUFFIX: &str = ".db";
+
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// `None` disables the scheduler; backups can still be taken on demand.
+    pub interval: Option<Duration>,
+    /// Number of most recent backups kept after each run.
+    pub retention: usize,
+}
+
+/// Takes an online backup into `config.dir` and prunes old files down to the
+/// retention count. Returns the path of the new backup.
+pub async fn create_backup(db: &Db, config: &BackupConfig) -> Result<PathBuf> {
+    std::fs::create_dir_all(&config.dir)
+        .with_context(|| format!("failed to create backup dir {}", config.dir.display()))?;
+    let tag = db.timestamp_tag().await?;
+    let mut path = config.dir.join(format!("{FILE_PREFIX}{tag}{FILE_SUFFIX}"));
+    // Two backups in the same second would collide; VACUUM INTO refuses to
+    // overwrite, so pick the next free name instead.
+    let mut n = 1;
+    while path.exists() {
+        path = config.dir.join(format!("{FILE_PREFIX}{tag}-{n}{FILE_SUFFIX}"));
+        n += 1;
+    }
+    db.backup_to(&path).await?;
+    prune(&config.dir, config.retention)?;
+    Ok(path)
+}
+
+/// Newest first.
+pub fn list_backups(dir: &Path) -> Result<Vec<BackupFile>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with(FILE_PREFIX) && file_name.ends_with(FILE_SUFFIX) {
+            files.push(BackupFile { file_name, size: entry.metadata()?.len() });
+        }
+    }
+    files.sort_by(|a, b| sort_key(&b.file_name).cmp(&sort_key(&a.file_name)));
+    Ok(files)
+}
+
+/// `backup-<YYYYMMDD-HHMMSS>[-n].db` orders by timestamp, then by the
+/// collision counter.
+fn sort_key(file_name: &str) -> (&str, u32) {
+    let stem = file_name
+        .trim_start_matches(FILE_PREFIX)
+        .trim_end_matches(FILE_SUFFIX);
+    let (tag, counter) = stem.split_at(stem.len().min(15));
+    (tag, counter.trim_start_matches('-').parse().unwrap_or(0))
+}
+
+fn prune(dir: &Path, retention: usize) -> Result<()> {
+    for old in list_backups(dir)?.into_iter().skip(retention) {
+        let path = dir.join(&old.file_name);
+        std::fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        tracing::info!("removed old backup {}", path.display());
+    }
+    Ok(())
+}
+
+/// Runs scheduled backups until `stop` changes or its sender is dropped. A
+/// backup already running when the stop arrives is finished first, so await
+/// the handle before closing the database.
+pub fn spawn_scheduler(
+    db: Db,
+    config: BackupConfig,
+    mut stop: watch::Receiver<bool>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let period = config.interval?;
+    tracing::info!(
+        "scheduled backups every {}s into {} (keeping {})",
+        period.as_secs(),
+        config.dir.display(),
+        config.retention
+    );
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        // The first tick completes immediately; skip it so startup is not
+        // delayed by a backup.
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = stop.changed() => break,
+            }
+            match create_backup(&db, &config).await {
+                Ok(path) => tracing::info!("backup written to {}", path.display()),
+                Err(e) => tracing::error!("scheduled backup failed: {e:#}"),
+            }
+        }
+        tracing::info!("backup scheduler stopped");
+    }))
+}
+
+/// Replaces the database file behind `database_url` with `backup`. The server
+/// must not be running; restore refuses to start while another connection
+/// holds a lock on the database. The backup is checked for integrity and for
+/// the current `SCHEMA_VERSION` before anything is touched, and the previous
+/// file is kept next to it as `<name>.pre-restore-<timestamp>`, with its WAL
+/// checkpointed into it first.
+pub async fn restore(database_url: &str, backup: &Path) -> Result<Option<PathBuf>> {
+    let target = db::db_file_path_from_url(database_url)
+        .context("restore needs a file-backed sqlite:// DATABASE_URL")?;
+    if !backup.is_file() {
+        bail!("backup file {} does not exist", backup.display());
+    }
+
+    let mut conn = SqliteConnectOptions::new()
+        .filename(backup)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("failed to open {}", backup.display()))?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await?;
+    if integrity != "ok" {
+        bail!("backup failed integrity check: {integrity}");
+    }
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(&mut conn)
+        .await?;
+    let tag: String = sqlx::query_scalar("SELECT strftime('%Y%m%d-%H%M%S', 'now')")
+        .fetch_one(&mut conn)
+        .await?;
+    conn.close().await?;
+    if version != SCHEMA_VERSION {
+        bail!("backup has schema version {version}, this build expects {SCHEMA_VERSION}");
+    }
+
+    if target.exists() {
+        checkpoint_exclusively(&target).await?;
+    }
+    let staging = sibling(&target, ".restore-tmp");
+    std::fs::copy(backup, &staging)
+        .with_context(|| format!("failed to copy backup to {}", staging.display()))?;
+    let previous = if target.exists() {
+        let previous = sibling(&target, &format!(".pre-restore-{tag}"));
+        std::fs::rename(&target, &previous)
+            .with_context(|| format!("failed to move {} aside", target.display()))?;
+        Some(previous)
+    } else {
+        None
+    };
+    // Whatever the checkpoint left in the WAL belongs to the previous file.
+    for suffix in ["-wal", "-shm"] {
+        let stale = sibling(&target, suffix);
+        if !stale.exists() {
+            continue;
+        }
+        match &previous {
+            Some(previous) => std::fs::rename(&stale, sibling(previous, suffix))
+                .with_context(|| format!("failed to move {} aside", stale.display()))?,
+            None => std::fs::remove_file(&stale)?,
+        }
+    }
+    std::fs::rename(&staging, &target)
+        .with_context(|| format!("failed to move restored file into {}", target.display()))?;
+    Ok(previous)
+}
+
+/// Takes an exclusive lock on the live database to make sure nothing else is
+/// writing to it, then folds its WAL into the main file. Waits for no one:
+/// a lock held elsewhere means the server is still running.
+async fn checkpoint_exclusively(target: &Path) -> Result<()> {
+    let in_use = || format!("{} is in use; stop the server before restoring", target.display());
+    let mut conn = SqliteConnectOptions::new()
+        .filename(target)
+        .busy_timeout(Duration::ZERO)
+        .connect()
+        .await
+        .with_context(in_use)?;
+    sqlx::query("BEGIN EXCLUSIVE").execute(&mut conn).await.with_context(in_use)?;
+    sqlx::query("COMMIT").execute(&mut conn).await?;
+    let (busy, _, _): (i64, i64, i64) = sqlx::query_as("PRAGMA wal_checkpoint(TRUNCATE)")
+        .fetch_one(&mut conn)
+        .await
+        .with_context(in_use)?;
+    conn.close().await?;
+    if busy != 0 {
+        bail!(in_use());
+    }
+    Ok(())
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,331 @@
+//! Command line of the `app` binary: `serve` plus operator commands that work
+//! directly on the configured database through [`Db`], so an instance can be
+//! managed without going through the HTTP API.
+//!
+//! Exit codes are stable for scripts: see [`CliError`].
+
+use crate::api::{hash_password, validate_new_account, validate_password};
+use crate::backup;
+use crate::config::Config;
+use crate::db::{Db, NewNote, SCHEMA_VERSION};
+use crate::export::{BoardExport, ExportFormat};
+use crate::i18n::{self, Locale};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+pub const USAGE: &str = "\
+usage: app [--config <file>] [--print-config] [<command>]
+
+commands:
+  serve                          run the HTTP server (default)
+  migrate                        bring the database schema up to date
+  create-account <name> <email>  create an account; password is read from stdin
+  reset-password <id|email>      set a new password, read from stdin
+  list-groups                    list every group as tab-separated values
+  export-group <id> [--format json|csv|md]
+                                 write a board export to stdout
+  backup                         write a SQLite backup into the backup dir
+  restore <backup-file>          replace the SQLite database (server stopped)
+  seed-demo                      create demo accounts, a group and notes
+
+exit codes: 0 ok, 1 failure, 2 usage, 3 not found, 4 invalid input or conflict,
+            5 invalid configuration";
+
+pub struct Args {
+    pub config_file: Option<PathBuf>,
+    pub print_config: bool,
+    pub command: Command,
+}
+
+pub enum Command {
+    Serve,
+    Help,
+    Admin(AdminCommand),
+}
+
+/// Commands that run against the database and exit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    Migrate,
+    CreateAccount { name: String, email: String },
+    /// `account` is an ID or an email address.
+    ResetPassword { account: String },
+    ListGroups,
+    /// `format` has been checked by [`parse_args`].
+    ExportGroup { group_id: i64, format: Option<String> },
+    Backup,
+    Restore { file: PathBuf },
+    SeedDemo,
+}
+
+impl AdminCommand {
+    /// Whether the command reads a password from its input.
+    pub fn reads_password(&self) -> bool {
+        matches!(self, Self::CreateAccount { .. } | Self::ResetPassword { .. })
+    }
+}
+
+/// A failed command: a message for stderr and the process exit code.
+#[derive(Debug)]
+pub struct CliError {
+    code: u8,
+    message: String,
+}
+
+impl CliError {
+    /// Database, I/O and other unexpected failures.
+    pub const FAILURE: u8 = 1;
+    pub const USAGE: u8 = 2;
+    pub const NOT_FOUND: u8 = 3;
+    /// Rejected input or a conflict with existing data.
+    pub const INVALID: u8 = 4;
+    pub const CONFIG: u8 = 5;
+
+    pub fn new(code: u8, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// Prints the error to stderr and returns the exit code to end with.
+    pub fn report(&self) -> ExitCode {
+        eprintln!("error: {}", self.message);
+        if self.code == Self::USAGE {
+            eprintln!("\n{USAGE}");
+        }
+        ExitCode::from(self.code)
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<anyhow::Error> for CliError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(Self::FAILURE, format!("{e:#}"))
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(Self::FAILURE, e.to_string())
+    }
+}
+
+impl From<crate::api::ApiError> for CliError {
+    fn from(e: crate::api::ApiError) -> Self {
+        match e.fields() {
+            [] | [_] => Self::new(Self::INVALID, e.message()),
+            fields => {
+                let messages: Vec<_> = fields.iter().map(|f| f.message.as_str()).collect();
+                Self::new(Self::INVALID, messages.join("; "))
+            }
+        }
+    }
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, CliError> {
+    let usage = |msg: &str| CliError::new(CliError::USAGE, msg);
+    let mut config_file = None;
+    let mut print_config = false;
+    let mut format = None;
+    let mut words = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_file = Some(PathBuf::from(args.next().ok_or_else(|| usage("--config needs a file"))?)),
+            "--print-config" => print_config = true,
+            "--format" => format = Some(args.next().ok_or_else(|| usage("--format needs a value"))?),
+            "-h" | "--help" => words = vec!["help".to_string()],
+            flag if flag.starts_with("--") => return Err(usage(&format!("unknown option {flag}"))),
+            _ => words.push(arg),
+        }
+    }
+
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let command = match words.as_slice() {
+        [] | ["serve"] => Command::Serve,
+        ["help"] => Command::Help,
+        ["migrate"] => Command::Admin(AdminCommand::Migrate),
+        ["create-account", name, email] => Command::Admin(AdminCommand::CreateAccount {
+            name: name.trim().to_string(),
+            email: email.trim().to_string(),
+        }),
+        ["reset-password", account] => {
+            Command::Admin(AdminCommand::ResetPassword { account: account.trim().to_string() })
+        }
+        ["list-groups"] => Command::Admin(AdminCommand::ListGroups),
+        ["export-group", id] => {
+            let group_id = id.parse().ok().filter(|id| *id > 0).ok_or_else(|| usage("group id must be a positive integer"))?;
+            if ExportFormat::parse(format.as_deref()).is_none() {
+                return Err(usage("--format must be json, csv or md"));
+            }
+            Command::Admin(AdminCommand::ExportGroup { group_id, format: format.take() })
+        }
+        ["backup"] => Command::Admin(AdminCommand::Backup),
+        ["restore", file] => Command::Admin(AdminCommand::Restore { file: PathBuf::from(file) }),
+        ["seed-demo"] => Command::Admin(AdminCommand::SeedDemo),
+        [command, ..] => return Err(usage(&format!("unknown command or wrong arguments: {command}"))),
+    };
+    if format.is_some() {
+        return Err(usage("--format only applies to export-group"));
+    }
+    Ok(Args { config_file, print_config, command })
+}
+
+/// Runs `command` against `config.database_url`. Passwords are read from the
+/// first line of `input`; results go to `out`.
+pub async fn run(
+    command: AdminCommand,
+    config: &Config,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    // Validation messages shared with the API come out in English like the
+    // rest of the CLI's.
+    i18n::scope(Locale::En, run_command(command, config, input, out)).await
+}
+
+async fn run_command(
+    command: AdminCommand,
+    config: &Config,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    // Restore swaps the database file, so it must not hold a connection.
+    if let AdminCommand::Restore { file } = &command {
+        let previous = backup::restore(&config.database_url, file).await?;
+        match previous {
+            Some(p) => writeln!(out, "restored {}; previous database kept at {}", file.display(), p.display())?,
+            None => writeln!(out, "restored {}", file.display())?,
+        }
+        return Ok(());
+    }
+
+    let db = Db::init(&config.database_url).await?;
+    match command {
+        AdminCommand::Migrate => writeln!(out, "schema version {SCHEMA_VERSION}")?,
+        AdminCommand::CreateAccount { name, email } => {
+            let password = read_password(input)?;
+            validate_new_account(&config.limits, &name, &email, &password)?;
+            if db.find_account_by_email(&email).await?.is_some() {
+                return Err(CliError::new(CliError::INVALID, format!("an account with email {email} already exists")));
+            }
+            let id = db.create_account(&name, &email, &hash_password(&password), None).await?;
+            writeln!(out, "{id}")?;
+        }
+        AdminCommand::ResetPassword { account } => {
+            let found = match account.parse::<i64>() {
+                Ok(id) => db.get_account(id).await?,
+                Err(_) => db.find_account_by_email(&account).await?,
+            };
+            let account = found
+                .ok_or_else(|| CliError::new(CliError::NOT_FOUND, format!("no account {account}")))?;
+            let password = read_password(input)?;
+            validate_password(&password)?;
+            db.set_password_hash(account.id, &hash_password(&password)).await?;
+            writeln!(out, "{}", account.id)?;
+        }
+        AdminCommand::ListGroups => {
+            writeln!(out, "id\tname\towner\tmembers\tnotes\tcreated_at")?;
+            for g in db.list_groups().await? {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    g.id,
+                    g.group_name.replace(['\t', '\n'], " "),
+                    g.created_by,
+                    g.member_count,
+                    g.note_count,
+                    g.created_at
+                )?;
+            }
+        }
+        AdminCommand::ExportGroup { group_id, format } => {
+            let format = ExportFormat::parse(format.as_deref())
+                .ok_or_else(|| CliError::new(CliError::USAGE, "--format must be json, csv or md"))?;
+            let export = BoardExport::load(&db, group_id)
+                .await?
+                .ok_or_else(|| CliError::new(CliError::NOT_FOUND, format!("no group {group_id}")))?;
+            let text = export.render(format);
+            out.write_all(text.as_bytes())?;
+            if !text.ends_with('\n') {
+                writeln!(out)?;
+            }
+        }
+        AdminCommand::Backup => {
+            let path = backup::create_backup(&db, &config.backups()).await?;
+            writeln!(out, "{}", path.display())?;
+        }
+        AdminCommand::SeedDemo => seed_demo(&db, out).await?,
+        AdminCommand::Restore { .. } => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+fn read_password(input: &mut dyn BufRead) -> Result<String, CliError> {
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let password = line.trim().to_string();
+    if password.is_empty() {
+        return Err(CliError::new(CliError::INVALID, "no password given on stdin"));
+    }
+    Ok(password)
+}
+
+const DEMO_PASSWORD: &str = "demo1234";
+const DEMO_ACCOUNTS: [(&str, &str); 3] = [
+    ("Alice", "alice@demo.example"),
+    ("Bob", "bob@demo.example"),
+    ("Carol", "carol@demo.example"),
+];
+
+/// Three accounts sharing one board. Refuses to run twice.
+async fn seed_demo(db: &Db, out: &mut dyn Write) -> Result<(), CliError> {
+    for (_, email) in DEMO_ACCOUNTS {
+        if db.find_account_by_email(email).await?.is_some() {
+            return Err(CliError::new(CliError::INVALID, format!("demo data already present ({email} exists)")));
+        }
+    }
+    let hash = hash_password(DEMO_PASSWORD);
+    let mut ids = Vec::new();
+    for (name, email) in DEMO_ACCOUNTS {
+        ids.push(db.create_account(name, email, &hash, None).await?);
+    }
+    let group_id = db.create_group("デモボード", ids[0]).await?;
+    for &id in &ids[1..] {
+        db.add_user_to_group(group_id, id, "member").await?;
+    }
+    let notes = [
+        ("今週の目標", "API のドキュメントを整える", "#FFFF88", 40.0, 40.0, ids[0]),
+        ("買い物", "付箋・ペン・コーヒー", "#FBCFE8", 280.0, 60.0, ids[1]),
+        ("アイデア", "ボードを PNG で共有する", "#BFDBFE", 520.0, 40.0, ids[2]),
+        ("完了", "デモデータを投入", "#BBF7D0", 160.0, 260.0, ids[0]),
+    ];
+    for (z, (title, content, color, x, y, author)) in notes.into_iter().enumerate() {
+        let note = NewNote {
+            title: Some(title.to_string()),
+            content: Some(content.to_string()),
+            color: color.to_string(),
+            x,
+            y,
+            width: 200.0,
+            height: 150.0,
+            z_index: z as i64,
+            created_by: Some(author),
+        };
+        db.create_note(&note, group_id, true).await?;
+    }
+    writeln!(out, "group {group_id}")?;
+    for ((_, email), id) in DEMO_ACCOUNTS.iter().zip(&ids) {
+        writeln!(out, "account {id} {email} (password {DEMO_PASSWORD})")?;
+    }
+    Ok(())
+}
@@ -1,10 +1,26 @@
-use anyhow::Result;
-use serde::Serialize;
-use sqlx::{sqlite::SqlitePoolOptions, FromRow, Pool, Sqlite, Transaction};
+mod listing;
+mod migrations;
+mod postgres;
+mod sqlite;
 
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::FromRow;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use listing::{
+    AccountSort, Cursor, MemberSort, NoteFilter, NoteSort, Page, PageRequest, SortKey, MAX_PAGE_SIZE,
+};
+pub use migrations::SCHEMA_VERSION;
+
+/// Handle to the configured storage backend. Cheap to clone; all query
+/// methods come from [`Store`] through `Deref`.
 #[derive(Clone)]
 pub struct Db {
-    pub pool: Pool<Sqlite>,
+    store: Arc<dyn Store>,
 }
 
 #[derive(FromRow, Debug, Clone, Serialize)]
@@ -15,6 +31,10 @@ pub struct Account {
     #[allow(dead_code)]
     #[serde(skip_serializing)]
     pub password_hash: String,
+    /// `ja` or `en`; `None` when the account has not chosen.
+    pub locale: Option<String>,
+    /// When the account confirmed its address; `None` until then.
+    pub email_verified_at: Option<String>,
     pub created_at: String,
 }
 
@@ -26,6 +46,74 @@ pub struct Group {
     pub created_at: String,
 }
 
+/// What an account token (see [`Store::create_account_token`]) is good for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    /// Sent back as a bearer token by a signed-in client.
+    Session,
+}
+
+impl TokenPurpose {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VerifyEmail => "verify_email",
+            Self::ResetPassword => "reset_password",
+            Self::Session => "session",
+        }
+    }
+}
+
+/// Result of [`Store::update_account`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountUpdate {
+    Updated {
+        /// The address differed from the stored one.
+        email_changed: bool,
+    },
+    NotFound,
+    /// Another account has the requested address.
+    EmailTaken,
+}
+
+/// What became of the groups a deleted account had created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountRemoval {
+    /// Handed to another member, who is now their owner.
+    pub transferred_groups: Vec<i64>,
+    /// Deleted along with the notes shared only with them, as nobody else
+    /// was a member.
+    pub deleted_groups: Vec<i64>,
+}
+
+/// Row counts reported as gauges on `/metrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub accounts: i64,
+    pub groups: i64,
+    pub notes: i64,
+}
+
+/// Connection pool occupancy, for `/metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+/// A group with its size, for operator listings.
+#[derive(FromRow, Debug, Clone, Serialize)]
+pub struct GroupOverview {
+    pub id: i64,
+    pub group_name: String,
+    pub created_by: i64,
+    pub created_at: String,
+    pub member_count: i64,
+    pub note_count: i64,
+}
+
 #[derive(FromRow, Debug, Clone, Serialize)]
 pub struct GroupWithRole {
     pub id: i64,
@@ -79,356 +167,164 @@ pub struct SharedNote {
     pub shared_at: String,
 }
 
-impl Db {
-    pub async fn init(database_url: &str) -> Result<Self> {
-        if let Some(path) = db_file_path_from_url(database_url) {
-            if let Some(parent) = path.parent() {
-                if !parent.as_os_str().is_empty() {
-                    std::fs::create_dir_all(parent)?;
-                }
-            }
-            if !path.exists() {
-                std::fs::File::create(&path)?;
-            }
-        }
-
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
-            .await?;
-
-        sqlx::query("PRAGMA foreign_keys = ON;").execute(&pool).await?;
-
-        // Reset schema to match the specification
-        sqlx::query("DROP TABLE IF EXISTS note_shares;").execute(&pool).await?;
-        sqlx::query("DROP TABLE IF EXISTS notes;").execute(&pool).await?;
-        sqlx::query("DROP TABLE IF EXISTS group_users;").execute(&pool).await?;
-        sqlx::query("DROP TABLE IF EXISTS groups;").execute(&pool).await?;
-        sqlx::query("DROP TABLE IF EXISTS accounts;").execute(&pool).await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE accounts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                email TEXT NOT NULL UNIQUE,
-                password_hash TEXT NOT NULL,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
-            );
-            "#,
-        )
-        .execute(&pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE groups (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                group_name TEXT NOT NULL,
-                created_by INTEGER NOT NULL,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (created_by) REFERENCES accounts(id) ON DELETE RESTRICT
-            );
-            "#,
-        )
-        .execute(&pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE group_users (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                group_id INTEGER NOT NULL,
-                user_id INTEGER NOT NULL,
-                role TEXT NOT NULL DEFAULT 'member',
-                joined_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
-                FOREIGN KEY (user_id) REFERENCES accounts(id) ON DELETE CASCADE,
-                UNIQUE(group_id, user_id)
-            );
-            "#,
-        )
-        .execute(&pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE notes (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                title TEXT,
-                content TEXT,
-                color TEXT NOT NULL DEFAULT '#FFFF88',
-                x REAL NOT NULL,
-                y REAL NOT NULL,
-                width REAL NOT NULL DEFAULT 200,
-                height REAL NOT NULL DEFAULT 150,
-                z_index INTEGER NOT NULL DEFAULT 0,
-                created_by INTEGER,
-                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (created_by) REFERENCES accounts(id) ON DELETE SET NULL
-            );
-            "#,
-        )
-        .execute(&pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE note_shares (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                note_id INTEGER NOT NULL,
-                group_id INTEGER NOT NULL,
-                can_edit INTEGER NOT NULL DEFAULT 0,
-                shared_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (note_id) REFERENCES notes(id) ON DELETE CASCADE,
-                FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
-                UNIQUE(note_id, group_id)
-            );
-            "#,
-        )
-        .execute(&pool)
-        .await?;
-
-        Ok(Self { pool })
-    }
-
-    // Accounts --------------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct NewNote {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub color: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub z_index: i64,
+    pub created_by: Option<i64>,
+}
 
-    pub async fn create_account(&self, name: &str, email: &str, password_hash: &str) -> Result<i64> {
-        let res = sqlx::query(
-            r#"
-            INSERT INTO accounts (name, email, password_hash)
-            VALUES (?, ?, ?)
-            "#,
-        )
-        .bind(name)
-        .bind(email)
-        .bind(password_hash)
-        .execute(&self.pool)
-        .await?;
-        Ok(res.last_insert_rowid())
-    }
+/// One step of [`Store::apply_note_ops`].
+#[derive(Debug, Clone)]
+pub enum NoteOp {
+    Create {
+        note: NewNote,
+        can_edit: bool,
+    },
+    UpdatePosition {
+        note_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        z_index: i64,
+    },
+    UpdateContent {
+        note_id: i64,
+        title: Option<String>,
+        content: Option<String>,
+        color: String,
+    },
+    Delete {
+        note_id: i64,
+    },
+}
 
-    pub async fn list_accounts(&self) -> Result<Vec<Account>> {
-        let rows = sqlx::query_as::<_, Account>(
-            r#"
-            SELECT id, name, email, password_hash, created_at
-            FROM accounts
-            ORDER BY created_at ASC
-            "#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// Every op was applied; holds the ID of the note each op created or touched.
+    Applied(Vec<i64>),
+    /// The op at this index names a note that is not shared with the group,
+    /// so the whole batch was rolled back.
+    NoteNotFound(usize),
+}
 
-    pub async fn get_account(&self, account_id: i64) -> Result<Option<Account>> {
-        let row = sqlx::query_as::<_, Account>(
-            r#"
-            SELECT id, name, email, password_hash, created_at
-            FROM accounts
-            WHERE id = ?
-            "#,
-        )
-        .bind(account_id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(row)
+impl Db {
+    /// Picks the backend from the `DATABASE_URL` scheme and brings the schema
+    /// up to [`SCHEMA_VERSION`].
+    pub async fn init(database_url: &str) -> Result<Self> {
+        let store: Arc<dyn Store> = if database_url.starts_with("sqlite:") {
+            Arc::new(sqlite::SqliteStore::connect(database_url).await?)
+        } else if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            Arc::new(postgres::PgStore::connect(database_url).await?)
+        } else {
+            bail!("unsupported DATABASE_URL scheme (expected sqlite: or postgres://)");
+        };
+        Ok(Self { store })
     }
+}
 
-    // Groups ----------------------------------------------------------
-
-    pub async fn create_group(&self, group_name: &str, created_by: i64) -> Result<i64> {
-        let mut tx = self.pool.begin().await?;
-        let group_res = sqlx::query(
-            r#"
-            INSERT INTO groups (group_name, created_by)
-            VALUES (?, ?)
-            "#,
-        )
-        .bind(group_name)
-        .bind(created_by)
-        .execute(&mut *tx)
-        .await?;
-        let group_id = group_res.last_insert_rowid();
-
-        sqlx::query(
-            r#"
-            INSERT INTO group_users (group_id, user_id, role)
-            VALUES (?, ?, 'owner')
-            "#,
-        )
-        .bind(group_id)
-        .bind(created_by)
-        .execute(&mut *tx)
-        .await?;
-
-        tx.commit().await?;
-        Ok(group_id)
-    }
+impl std::ops::Deref for Db {
+    type Target = dyn Store;
 
-    pub async fn list_groups_for_user(&self, user_id: i64) -> Result<Vec<GroupWithRole>> {
-        let rows = sqlx::query_as::<_, GroupWithRole>(
-            r#"
-            SELECT g.id, g.group_name, g.created_by, g.created_at, gu.role
-            FROM groups g
-            INNER JOIN group_users gu ON gu.group_id = g.id
-            WHERE gu.user_id = ?
-            ORDER BY g.created_at ASC
-            "#,
-        )
-        .bind(user_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
+    fn deref(&self) -> &Self::Target {
+        &*self.store
     }
+}
 
-    pub async fn add_user_to_group(&self, group_id: i64, user_id: i64, role: &str) -> Result<i64> {
-        let res = sqlx::query(
-            r#"
-            INSERT OR IGNORE INTO group_users (group_id, user_id, role)
-            VALUES (?, ?, ?)
-            "#,
-        )
-        .bind(group_id)
-        .bind(user_id)
-        .bind(role)
-        .execute(&self.pool)
-        .await?;
-        Ok(res.last_insert_rowid())
-    }
+/// Every query the application runs. Implemented once per backend in
+/// `db/sqlite.rs` and `db/postgres.rs`.
+#[async_trait]
+pub trait Store: Send + Sync {
+    // Accounts --------------------------------------------------------
 
-    pub async fn list_group_members(&self, group_id: i64) -> Result<Vec<GroupUser>> {
-        let rows = sqlx::query_as::<_, GroupUser>(
-            r#"
-            SELECT id, group_id, user_id, role, joined_at
-            FROM group_users
-            WHERE group_id = ?
-            ORDER BY joined_at ASC
-            "#,
-        )
-        .bind(group_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
-    }
+    async fn create_account(&self, name: &str, email: &str, password_hash: &str, locale: Option<&str>)
+        -> Result<i64>;
+    async fn list_accounts(&self) -> Result<Vec<Account>>;
+    async fn get_account(&self, account_id: i64) -> Result<Option<Account>>;
+    async fn find_account_by_email(&self, email: &str) -> Result<Option<Account>>;
+    /// Also spends the account's open password reset tokens and sessions.
+    /// Returns `false` when the account does not exist.
+    async fn set_password_hash(&self, account_id: i64, password_hash: &str) -> Result<bool>;
+    /// Changes the given fields. A new address needs verifying again, so it
+    /// clears `email_verified_at` and spends the account's open verification
+    /// and reset tokens; sessions stay.
+    async fn update_account(&self, account_id: i64, name: Option<&str>, email: Option<&str>)
+        -> Result<AccountUpdate>;
+    /// Other accounts in a group with `account_id` whose name contains
+    /// `name` (ASCII case-insensitively), by name, at most `limit`.
+    async fn find_group_peers(&self, account_id: i64, name: &str, limit: u32) -> Result<Vec<Account>>;
+    /// Deletes the account in one transaction. Each group it created passes
+    /// to the longest-standing other owner, else the longest-standing member
+    /// (who becomes an owner); groups without other members are deleted.
+    /// `None` if the account does not exist.
+    async fn delete_account(&self, account_id: i64) -> Result<Option<AccountRemoval>>;
+    async fn page_accounts(&self, req: &PageRequest<AccountSort>) -> Result<Page<Account>>;
+    /// Stores the digest of a token that expires `ttl` from now.
+    async fn create_account_token(
+        &self,
+        account_id: i64,
+        purpose: TokenPurpose,
+        token_hash: &str,
+        ttl: Duration,
+    ) -> Result<()>;
+    /// Spends the unexpired, unused token with this digest together with the
+    /// account's other open tokens of the same purpose, and returns its
+    /// account. `None` if there is no such token.
+    async fn consume_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<i64>>;
+    /// The account of an unexpired, unused token, which stays usable.
+    async fn token_account(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<Account>>;
+    /// Spends one token. Returns `false` when it was not open.
+    async fn revoke_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<bool>;
+    /// Keeps the first verification time. Returns `false` when the account
+    /// does not exist.
+    async fn mark_email_verified(&self, account_id: i64) -> Result<bool>;
 
-    pub async fn get_group(&self, group_id: i64) -> Result<Option<Group>> {
-        let row = sqlx::query_as::<_, Group>(
-            r#"
-            SELECT id, group_name, created_by, created_at
-            FROM groups
-            WHERE id = ?
-            "#,
-        )
-        .bind(group_id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(row)
-    }
+    // Groups ----------------------------------------------------------
 
-    pub async fn is_user_in_group(&self, group_id: i64, user_id: i64) -> Result<bool> {
-        let exists: Option<i64> = sqlx::query_scalar(
-            r#"
-            SELECT 1
-            FROM group_users
-            WHERE group_id = ? AND user_id = ?
-            "#,
-        )
-        .bind(group_id)
-        .bind(user_id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(exists.is_some())
-    }
+    /// Creates the group and makes `created_by` its owner.
+    async fn create_group(&self, group_name: &str, created_by: i64) -> Result<i64>;
+    async fn list_groups_for_user(&self, user_id: i64) -> Result<Vec<GroupWithRole>>;
+    /// Every group, oldest first, with member and note counts.
+    async fn list_groups(&self) -> Result<Vec<GroupOverview>>;
+    /// Existing memberships are left untouched.
+    async fn add_user_to_group(&self, group_id: i64, user_id: i64, role: &str) -> Result<()>;
+    async fn list_group_members(&self, group_id: i64) -> Result<Vec<GroupUser>>;
+    async fn page_group_members(
+        &self,
+        group_id: i64,
+        req: &PageRequest<MemberSort>,
+    ) -> Result<Page<GroupUser>>;
+    async fn get_group(&self, group_id: i64) -> Result<Option<Group>>;
+    async fn is_user_in_group(&self, group_id: i64, user_id: i64) -> Result<bool>;
 
     // Notes -----------------------------------------------------------
 
-    pub async fn create_note(
+    async fn create_note(&self, note: &NewNote, group_id: i64, can_edit: bool) -> Result<i64>;
+    /// Inserts all notes into the group in a single transaction; either every
+    /// note is stored or none is.
+    async fn import_notes(&self, group_id: i64, notes: &[NewNote]) -> Result<Vec<i64>>;
+    async fn list_notes_for_group(&self, group_id: i64) -> Result<Vec<SharedNote>>;
+    /// `(id, name)` of each account that created a note shared with the group.
+    async fn note_author_names(&self, group_id: i64) -> Result<Vec<(i64, String)>>;
+    async fn page_notes_for_group(
         &self,
-        title: Option<&str>,
-        content: Option<&str>,
-        color: &str,
-        x: f64,
-        y: f64,
-        width: f64,
-        height: f64,
-        z_index: i64,
-        created_by: Option<i64>,
         group_id: i64,
-        can_edit: bool,
-    ) -> Result<i64> {
-        let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await?;
-
-        let note_res = sqlx::query(
-            r#"
-            INSERT INTO notes (title, content, color, x, y, width, height, z_index, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(title)
-        .bind(content)
-        .bind(color)
-        .bind(x)
-        .bind(y)
-        .bind(width)
-        .bind(height)
-        .bind(z_index)
-        .bind(created_by)
-        .execute(&mut *tx)
-        .await?;
-
-        let note_id = note_res.last_insert_rowid();
-
-        sqlx::query(
-            r#"
-            INSERT INTO note_shares (note_id, group_id, can_edit)
-            VALUES (?, ?, ?)
-            "#,
-        )
-        .bind(note_id)
-        .bind(group_id)
-        .bind(if can_edit { 1 } else { 0 })
-        .execute(&mut *tx)
-        .await?;
-
-        tx.commit().await?;
-        Ok(note_id)
-    }
-
-    pub async fn list_notes_for_group(&self, group_id: i64) -> Result<Vec<SharedNote>> {
-        let rows = sqlx::query_as::<_, SharedNote>(
-            r#"
-            SELECT
-                n.id,
-                n.title,
-                n.content,
-                n.color,
-                n.x,
-                n.y,
-                n.width,
-                n.height,
-                n.z_index,
-                n.created_by,
-                n.created_at,
-                n.updated_at,
-                ns.group_id,
-                ns.can_edit as can_edit,
-                ns.shared_at
-            FROM notes n
-            INNER JOIN note_shares ns ON ns.note_id = n.id
-            WHERE ns.group_id = ?
-            ORDER BY n.z_index ASC, n.updated_at ASC
-            "#,
-        )
-        .bind(group_id)
-        .fetch_all(&self.pool)
-        .await?;
-        Ok(rows)
-    }
-
-    pub async fn update_note_position(
+        filter: &NoteFilter,
+        req: &PageRequest<NoteSort>,
+    ) -> Result<Page<SharedNote>>;
+    /// Notes shared with the group whose change-log entry is newer than
+    /// `since`, plus the IDs of logged notes no longer shared with it.
+    async fn notes_changed_since(&self, group_id: i64, since: i64) -> Result<(Vec<SharedNote>, Vec<i64>)>;
+    /// Newest change-log id across all groups; `0` when nothing has changed yet.
+    async fn latest_change_id(&self) -> Result<i64>;
+    async fn update_note_position(
         &self,
         note_id: i64,
         x: f64,
@@ -436,98 +332,50 @@ impl Db {
         width: f64,
         height: f64,
         z_index: i64,
-    ) -> Result<bool> {
-        let res = sqlx::query(
-            r#"
-            UPDATE notes
-            SET x = ?, y = ?, width = ?, height = ?, z_index = ?, updated_at = CURRENT_TIMESTAMP
-            WHERE id = ?
-            "#,
-        )
-        .bind(x)
-        .bind(y)
-        .bind(width)
-        .bind(height)
-        .bind(z_index)
-        .bind(note_id)
-        .execute(&self.pool)
-        .await?;
-        Ok(res.rows_affected() > 0)
-    }
-
-    pub async fn update_note_content(
+    ) -> Result<bool>;
+    async fn update_note_content(
         &self,
         note_id: i64,
         title: Option<&str>,
         content: Option<&str>,
         color: &str,
-    ) -> Result<bool> {
-        let res = sqlx::query(
-            r#"
-            UPDATE notes
-            SET title = ?, content = ?, color = ?, updated_at = CURRENT_TIMESTAMP
-            WHERE id = ?
-            "#,
-        )
-        .bind(title)
-        .bind(content)
-        .bind(color)
-        .bind(note_id)
-        .execute(&self.pool)
-        .await?;
-        Ok(res.rows_affected() > 0)
-    }
-
-    pub async fn delete_note(&self, note_id: i64) -> Result<bool> {
-        let res = sqlx::query(
-            r#"
-            DELETE FROM notes WHERE id = ?
-            "#,
-        )
-        .bind(note_id)
-        .execute(&self.pool)
-        .await?;
-        Ok(res.rows_affected() > 0)
-    }
-
-    pub async fn clear_notes_for_group(&self, group_id: i64) -> Result<u64> {
-        let mut tx = self.pool.begin().await?;
-
-        let note_ids: Vec<i64> = sqlx::query_scalar(
-            r#"
-            SELECT note_id
-            FROM note_shares
-            WHERE group_id = ?
-            "#,
-        )
-        .bind(group_id)
-        .fetch_all(&mut *tx)
-        .await?;
-
-        let mut removed: u64 = 0;
-        for note_id in note_ids {
-            let res = sqlx::query("DELETE FROM notes WHERE id = ?")
-                .bind(note_id)
-                .execute(&mut *tx)
-                .await?;
-            removed += res.rows_affected();
-        }
-
-        tx.commit().await?;
-        Ok(removed)
-    }
+    ) -> Result<bool>;
+    async fn delete_note(&self, note_id: i64) -> Result<bool>;
+    /// Applies the ops in order in one transaction. Updates and deletes only
+    /// match notes shared with `group_id`.
+    async fn apply_note_ops(&self, group_id: i64, ops: &[NoteOp]) -> Result<BatchOutcome>;
+    async fn clear_notes_for_group(&self, group_id: i64) -> Result<u64>;
+    async fn count_notes(&self) -> Result<i64>;
+    async fn totals(&self) -> Result<Totals>;
+
+    // Maintenance -----------------------------------------------------
+
+    /// The applied schema version, read from the database on every call so
+    /// `/readyz` notices a database swapped underneath the server.
+    async fn schema_version(&self) -> Result<i64>;
+    fn pool_stats(&self) -> PoolStats;
+    /// Writes a consistent copy of the live database to `path`.
+    async fn backup_to(&self, path: &Path) -> Result<()>;
+    /// `YYYYMMDD-HHMMSS` in UTC, taken from the database clock like every
+    /// other timestamp we store.
+    async fn timestamp_tag(&self) -> Result<String>;
+    /// Flushes pending writes and closes every pooled connection. Called
+    /// once at shutdown; queries after this fail.
+    async fn close(&self) -> Result<()>;
+}
 
-    pub async fn count_notes(&self) -> Result<i64> {
-        let c: (i64,) = sqlx::query_as("SELECT COUNT(*) as c FROM notes")
-            .fetch_one(&self.pool)
-            .await?;
-        Ok(c.0)
-    }
+/// The statement broke a unique index.
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error().is_some_and(|e| e.is_unique_violation())
+}
 
+/// `sqlite::memory:`, `sqlite://:memory:` or any URL with `mode=memory`.
+pub fn is_memory_url(url: &str) -> bool {
+    url.starts_with("sqlite::memory:") || url.starts_with("sqlite://:memory:") || url.contains("mode=memory")
 }
 
 pub fn db_file_path_from_url(url: &str) -> Option<std::path::PathBuf> {
-    if url.starts_with("sqlite::memory:") {
+    if is_memory_url(url) {
         return None;
     }
     if let Some(rest) = url.strip_prefix("sqlite://") {
@@ -0,0 +1,363 @@
+//! Keyset pagination shared by both backends. Queries are built once with `?`
+//! placeholders; the Postgres store renumbers them before binding.
+
+use super::{Account, GroupUser, SharedNote};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write;
+
+pub const MAX_PAGE_SIZE: u32 = 500;
+
+/// Sort orders a list endpoint accepts. `columns` must end with a unique
+/// column so that the cursor identifies exactly one position.
+pub trait SortKey: Copy {
+    type Row;
+
+    fn parse(name: &str) -> Option<Self>;
+    fn name(self) -> &'static str;
+    fn columns(self) -> &'static [&'static str];
+    fn values(self, row: &Self::Row) -> Vec<Value>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountSort {
+    #[default]
+    CreatedAt,
+    Name,
+}
+
+impl SortKey for AccountSort {
+    type Row = Account;
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "created_at" => Some(Self::CreatedAt),
+            "name" => Some(Self::Name),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::CreatedAt => "created_at",
+            Self::Name => "name",
+        }
+    }
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Self::CreatedAt => &["created_at", "id"],
+            Self::Name => &["name", "id"],
+        }
+    }
+
+    fn values(self, row: &Account) -> Vec<Value> {
+        match self {
+            Self::CreatedAt => vec![row.created_at.clone().into(), row.id.into()],
+            Self::Name => vec![row.name.clone().into(), row.id.into()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemberSort {
+    #[default]
+    JoinedAt,
+    UserId,
+}
+
+impl SortKey for MemberSort {
+    type Row = GroupUser;
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "joined_at" => Some(Self::JoinedAt),
+            "user_id" => Some(Self::UserId),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::JoinedAt => "joined_at",
+            Self::UserId => "user_id",
+        }
+    }
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Self::JoinedAt => &["joined_at", "id"],
+            Self::UserId => &["user_id"],
+        }
+    }
+
+    fn values(self, row: &GroupUser) -> Vec<Value> {
+        match self {
+            Self::JoinedAt => vec![row.joined_at.clone().into(), row.id.into()],
+            Self::UserId => vec![row.user_id.into()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoteSort {
+    /// Board stacking order, the same order `list_notes_for_group` uses.
+    #[default]
+    ZIndex,
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+impl SortKey for NoteSort {
+    type Row = SharedNote;
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "z_index" => Some(Self::ZIndex),
+            "created_at" => Some(Self::CreatedAt),
+            "updated_at" => Some(Self::UpdatedAt),
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::ZIndex => "z_index",
+            Self::CreatedAt => "created_at",
+            Self::UpdatedAt => "updated_at",
+            Self::Title => "title",
+        }
+    }
+
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Self::ZIndex => &["n.z_index", "n.updated_at", "n.id"],
+            Self::CreatedAt => &["n.created_at", "n.id"],
+            Self::UpdatedAt => &["n.updated_at", "n.id"],
+            Self::Title => &["COALESCE(n.title, '')", "n.id"],
+        }
+    }
+
+    fn values(self, row: &SharedNote) -> Vec<Value> {
+        match self {
+            Self::ZIndex => vec![row.z_index.into(), row.updated_at.clone().into(), row.id.into()],
+            Self::CreatedAt => vec![row.created_at.clone().into(), row.id.into()],
+            Self::UpdatedAt => vec![row.updated_at.clone().into(), row.id.into()],
+            Self::Title => vec![row.title.clone().unwrap_or_default().into(), row.id.into()],
+        }
+    }
+}
+
+/// Opaque continuation token: the sort it was issued for plus the key values
+/// of the last row returned, as hex-encoded JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(rename = "s")]
+    sort: String,
+    #[serde(rename = "d")]
+    descending: bool,
+    #[serde(rename = "k")]
+    keys: Vec<Value>,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        json.iter().fold(String::with_capacity(json.len() * 2), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+    }
+
+    pub fn decode(raw: &str) -> Option<Self> {
+        if raw.len() % 2 != 0 {
+            return None;
+        }
+        let bytes = (0..raw.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(raw.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageRequest<S> {
+    pub sort: S,
+    pub descending: bool,
+    /// `None` returns every remaining row.
+    pub limit: Option<u32>,
+    pub after: Option<Cursor>,
+}
+
+impl<S: SortKey> PageRequest<S> {
+    /// Fails when the cursor was issued for a different sort order or does
+    /// not carry one value per sort column.
+    pub fn new(sort: S, descending: bool, limit: Option<u32>, after: Option<Cursor>) -> Option<Self> {
+        if let Some(c) = &after {
+            let valid = c.sort == sort.name()
+                && c.descending == descending
+                && c.keys.len() == sort.columns().len()
+                && c.keys.iter().all(|k| Bind::from_value(k).is_some());
+            if !valid {
+                return None;
+            }
+        }
+        Some(Self { sort, descending, limit, after })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NoteFilter {
+    pub author: Option<i64>,
+    /// Normalised `#RRGGBB`.
+    pub color: Option<String>,
+    /// Inclusive lower bound, `YYYY-MM-DD HH:MM:SS` UTC.
+    pub updated_since: Option<String>,
+    /// Case-insensitive substring of the title or content.
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Bind {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Bind {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(Self::Text(s.clone())),
+            Value::Number(n) => n.as_i64().map(Self::Int).or_else(|| n.as_f64().map(Self::Float)),
+            _ => None,
+        }
+    }
+}
+
+pub struct Sql {
+    pub text: String,
+    pub binds: Vec<Bind>,
+}
+
+impl Sql {
+    fn new(select: &str) -> Self {
+        Self { text: select.to_string(), binds: Vec::new() }
+    }
+
+    fn and(&mut self, clause: &str, binds: impl IntoIterator<Item = Bind>) {
+        self.text.push_str("\nAND ");
+        self.text.push_str(clause);
+        self.binds.extend(binds);
+    }
+
+    fn finish<S: SortKey>(mut self, req: &PageRequest<S>) -> Self {
+        let columns = req.sort.columns();
+        let dir = if req.descending { "DESC" } else { "ASC" };
+        if let Some(cursor) = &req.after {
+            let op = if req.descending { "<" } else { ">" };
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            let keys = cursor.keys.iter().filter_map(Bind::from_value);
+            self.and(&format!("({}) {} ({})", columns.join(", "), op, placeholders), keys);
+        }
+        let order: Vec<String> = columns.iter().map(|c| format!("{c} {dir}")).collect();
+        self.text.push_str(&format!("\nORDER BY {}", order.join(", ")));
+        if let Some(limit) = req.limit {
+            // One extra row tells us whether another page exists.
+            self.text.push_str(&format!("\nLIMIT {}", limit + 1));
+        }
+        self
+    }
+}
+
+pub fn accounts_sql(req: &PageRequest<AccountSort>) -> Sql {
+    Sql::new(
+        r#"
+        SELECT id, name, email, password_hash, locale, email_verified_at, created_at
+        FROM accounts
+        WHERE 1 = 1"#,
+    )
+    .finish(req)
+}
+
+pub fn members_sql(group_id: i64, req: &PageRequest<MemberSort>) -> Sql {
+    let mut sql = Sql::new(
+        r#"
+        SELECT id, group_id, user_id, role, joined_at
+        FROM group_users
+        WHERE 1 = 1"#,
+    );
+    sql.and("group_id = ?", [Bind::Int(group_id)]);
+    sql.finish(req)
+}
+
+pub fn notes_sql(group_id: i64, filter: &NoteFilter, req: &PageRequest<NoteSort>) -> Sql {
+    let mut sql = Sql::new(
+        r#"
+        SELECT
+            n.id, n.title, n.content, n.color, n.x, n.y, n.width, n.height, n.z_index,
+            n.created_by, n.created_at, n.updated_at,
+            ns.group_id, ns.can_edit AS can_edit, ns.shared_at
+        FROM notes n
+        INNER JOIN note_shares ns ON ns.note_id = n.id
+        WHERE 1 = 1"#,
+    );
+    sql.and("ns.group_id = ?", [Bind::Int(group_id)]);
+    if let Some(author) = filter.author {
+        sql.and("n.created_by = ?", [Bind::Int(author)]);
+    }
+    if let Some(color) = &filter.color {
+        sql.and("n.color = ?", [Bind::Text(color.clone())]);
+    }
+    if let Some(since) = &filter.updated_since {
+        sql.and("n.updated_at >= ?", [Bind::Text(since.clone())]);
+    }
+    if let Some(text) = &filter.text {
+        let pattern = format!("%{}%", escape_like(&text.to_lowercase()));
+        sql.and(
+            r"(LOWER(COALESCE(n.title, '')) LIKE ? ESCAPE '\' OR LOWER(COALESCE(n.content, '')) LIKE ? ESCAPE '\')",
+            [Bind::Text(pattern.clone()), Bind::Text(pattern)],
+        );
+    }
+    sql.finish(req)
+}
+
+pub(super) fn escape_like(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Trims the look-ahead row fetched by `Sql::finish` and derives the cursor
+/// for the next page from the last row kept.
+pub fn into_page<S: SortKey>(mut rows: Vec<S::Row>, req: &PageRequest<S>) -> Page<S::Row> {
+    let Some(limit) = req.limit else {
+        return Page { items: rows, next_cursor: None };
+    };
+    if rows.len() <= limit as usize {
+        return Page { items: rows, next_cursor: None };
+    }
+    rows.truncate(limit as usize);
+    let next_cursor = rows.last().map(|last| {
+        Cursor {
+            sort: req.sort.name().to_string(),
+            descending: req.descending,
+            keys: req.sort.values(last),
+        }
+        .encode()
+    });
+    Page { items: rows, next_cursor }
+}
@@ -0,0 +1,39 @@
+/// One step of the schema history. Both backends share the same numbered
+/// steps; only the SQL dialect differs.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sqlite: &'static str,
+    pub postgres: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sqlite: include_str!("../../migrations/sqlite/0001_initial.sql"),
+        postgres: include_str!("../../migrations/postgres/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "note change log for incremental sync",
+        sqlite: include_str!("../../migrations/sqlite/0002_note_changes.sql"),
+        postgres: include_str!("../../migrations/postgres/0002_note_changes.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "preferred locale of accounts",
+        sqlite: include_str!("../../migrations/sqlite/0003_account_locale.sql"),
+        postgres: include_str!("../../migrations/postgres/0003_account_locale.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "email verification and password reset tokens",
+        sqlite: include_str!("../../migrations/sqlite/0004_account_tokens.sql"),
+        postgres: include_str!("../../migrations/postgres/0004_account_tokens.sql"),
+    },
+];
+
+/// Version of the newest migration; stored in SQLite's `user_version` pragma
+/// and in the `schema_version` table on Postgres.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
@@ -0,0 +1,1002 @@
+use super::listing::{self, Bind, Sql};
+use super::migrations::MIGRATIONS;
+use super::{
+    AccountSort, MemberSort, NoteFilter, NoteSort, Page, PageRequest,
+    db_file_path_from_url, is_memory_url, is_unique_violation, Account, AccountRemoval, AccountUpdate, BatchOutcome, Group,
+    GroupOverview, GroupUser, GroupWithRole, NewNote, NoteOp, PoolStats, SharedNote, Store, TokenPurpose, Totals, SCHEMA_VERSION,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use sqlx::sqlite::{SqlitePoolOptions, SqliteRow};
+use sqlx::{FromRow, Pool, Sqlite, Transaction};
+use std::path::Path;
+use std::time::Duration;
+use tracing::instrument;
+
+pub struct SqliteStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStore {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        if let Some(path) = db_file_path_from_url(database_url) {
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    std::fs::create_dir_all(parent)?;
+                }
+            }
+            if !path.exists() {
+                std::fs::File::create(&path)?;
+            }
+        }
+
+        // Every connection to an in-memory URL opens its own empty database,
+        // so keep exactly one connection open for the life of the pool.
+        let options = if is_memory_url(database_url) {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(5)
+        };
+        let pool = options.connect(database_url).await?;
+
+        sqlx::query("PRAGMA foreign_keys = ON;").execute(&pool).await?;
+        migrate(&pool).await?;
+
+        Ok(Self { pool })
+    }
+}
+
+async fn schema_version(pool: &Pool<Sqlite>) -> Result<i64> {
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(version)
+}
+
+async fn migrate(pool: &Pool<Sqlite>) -> Result<()> {
+    let current = schema_version(pool).await?;
+    if current > SCHEMA_VERSION {
+        bail!("database schema version {current} is newer than this build ({SCHEMA_VERSION})");
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        tracing::info!("applying migration {} ({})", migration.version, migration.description);
+        let mut tx = pool.begin().await?;
+        sqlx::raw_sql(migration.sqlite).execute(&mut *tx).await?;
+        sqlx::query(&format!("PRAGMA user_version = {};", migration.version))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+/// Runs a query built by `listing`.
+async fn fetch_sql<T>(pool: &Pool<Sqlite>, sql: &Sql) -> Result<Vec<T>>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let mut query = sqlx::query_as::<_, T>(&sql.text);
+    for bind in &sql.binds {
+        query = match bind {
+            Bind::Int(v) => query.bind(*v),
+            Bind::Float(v) => query.bind(*v),
+            Bind::Text(v) => query.bind(v.clone()),
+        };
+    }
+    Ok(query.fetch_all(pool).await?)
+}
+
+async fn insert_note(
+    tx: &mut Transaction<'_, Sqlite>,
+    note: &NewNote,
+    group_id: i64,
+    can_edit: bool,
+) -> Result<i64> {
+    let note_res = sqlx::query(
+        r#"
+        INSERT INTO notes (title, content, color, x, y, width, height, z_index, created_by)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&note.title)
+    .bind(&note.content)
+    .bind(&note.color)
+    .bind(note.x)
+    .bind(note.y)
+    .bind(note.width)
+    .bind(note.height)
+    .bind(note.z_index)
+    .bind(note.created_by)
+    .execute(&mut **tx)
+    .await?;
+
+    let note_id = note_res.last_insert_rowid();
+
+    sqlx::query(
+        r#"
+        INSERT INTO note_shares (note_id, group_id, can_edit)
+        VALUES (?, ?, ?)
+        "#,
+    )
+    .bind(note_id)
+    .bind(group_id)
+    .bind(if can_edit { 1 } else { 0 })
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(note_id)
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    // Accounts --------------------------------------------------------
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn create_account(
+        &self,
+        name: &str,
+        email: &str,
+        password_hash: &str,
+        locale: Option<&str>,
+    ) -> Result<i64> {
+        let res = sqlx::query(
+            r#"
+            INSERT INTO accounts (name, email, password_hash, locale)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(name)
+        .bind(email)
+        .bind(password_hash)
+        .bind(locale)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn list_accounts(&self) -> Result<Vec<Account>> {
+        let rows = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT id, name, email, password_hash, locale, email_verified_at, created_at
+            FROM accounts
+            ORDER BY created_at ASC, id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn get_account(&self, account_id: i64) -> Result<Option<Account>> {
+        let row = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT id, name, email, password_hash, locale, email_verified_at, created_at
+            FROM accounts
+            WHERE id = ?
+            "#,
+        )
+        .bind(account_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn find_account_by_email(&self, email: &str) -> Result<Option<Account>> {
+        let row = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT id, name, email, password_hash, locale, email_verified_at, created_at
+            FROM accounts
+            WHERE email = ?
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn set_password_hash(&self, account_id: i64, password_hash: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query("UPDATE accounts SET password_hash = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE account_tokens SET used_at = datetime('now')
+            WHERE account_id = ? AND purpose IN (?, ?) AND used_at IS NULL
+            "#,
+        )
+        .bind(account_id)
+        .bind(TokenPurpose::ResetPassword.as_str())
+        .bind(TokenPurpose::Session.as_str())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn update_account(
+        &self,
+        account_id: i64,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<AccountUpdate> {
+        let mut tx = self.pool.begin().await?;
+        let current: Option<String> = sqlx::query_scalar("SELECT email FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(current) = current else {
+            return Ok(AccountUpdate::NotFound);
+        };
+        if let Some(name) = name {
+            sqlx::query("UPDATE accounts SET name = ? WHERE id = ?")
+                .bind(name)
+                .bind(account_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let new_email = email.filter(|e| *e != current);
+        if let Some(email) = new_email {
+            let taken: Option<i64> = sqlx::query_scalar("SELECT id FROM accounts WHERE email = ?")
+                .bind(email)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if taken.is_some() {
+                return Ok(AccountUpdate::EmailTaken);
+            }
+            let changed = sqlx::query("UPDATE accounts SET email = ?, email_verified_at = NULL WHERE id = ?")
+                .bind(email)
+                .bind(account_id)
+                .execute(&mut *tx)
+                .await;
+            match changed {
+                // Another account took the address since the check above.
+                Err(e) if is_unique_violation(&e) => return Ok(AccountUpdate::EmailTaken),
+                changed => changed?,
+            };
+            // Links mailed to the old address must not verify or reset the new one.
+            sqlx::query(
+                r#"
+                UPDATE account_tokens SET used_at = datetime('now')
+                WHERE account_id = ? AND purpose <> ? AND used_at IS NULL
+                "#,
+            )
+            .bind(account_id)
+            .bind(TokenPurpose::Session.as_str())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(AccountUpdate::Updated { email_changed: new_email.is_some() })
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn find_group_peers(&self, account_id: i64, name: &str, limit: u32) -> Result<Vec<Account>> {
+        let rows = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT a.id, a.name, a.email, a.password_hash, a.locale, a.email_verified_at, a.created_at
+            FROM accounts a
+            WHERE a.id <> ?
+              AND LOWER(a.name) LIKE ? ESCAPE '\'
+              AND EXISTS (
+                  SELECT 1
+                  FROM group_users theirs
+                  INNER JOIN group_users mine ON mine.group_id = theirs.group_id
+                  WHERE theirs.user_id = a.id AND mine.user_id = ?
+              )
+            ORDER BY a.name ASC, a.id ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(format!("%{}%", listing::escape_like(&name.to_lowercase())))
+        .bind(account_id)
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn delete_account(&self, account_id: i64) -> Result<Option<AccountRemoval>> {
+        let mut tx = self.pool.begin().await?;
+        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        let group_ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM groups WHERE created_by = ? ORDER BY id")
+            .bind(account_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut removal = AccountRemoval::default();
+        for group_id in group_ids {
+            let heir: Option<i64> = sqlx::query_scalar(
+                r#"
+                SELECT user_id
+                FROM group_users
+                WHERE group_id = ? AND user_id <> ?
+                ORDER BY CASE WHEN role = 'owner' THEN 0 ELSE 1 END, joined_at ASC, id ASC
+                LIMIT 1
+                "#,
+            )
+            .bind(group_id)
+            .bind(account_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            match heir {
+                Some(heir) => {
+                    sqlx::query("UPDATE groups SET created_by = ? WHERE id = ?")
+                        .bind(heir)
+                        .bind(group_id)
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query("UPDATE group_users SET role = 'owner' WHERE group_id = ? AND user_id = ?")
+                        .bind(group_id)
+                        .bind(heir)
+                        .execute(&mut *tx)
+                        .await?;
+                    removal.transferred_groups.push(group_id);
+                }
+                None => {
+                    // Notes also shared with another group stay there.
+                    sqlx::query(
+                        r#"
+                        DELETE FROM notes
+                        WHERE id IN (SELECT note_id FROM note_shares WHERE group_id = ?)
+                          AND NOT EXISTS (
+                              SELECT 1 FROM note_shares s WHERE s.note_id = notes.id AND s.group_id <> ?
+                          )
+                        "#,
+                    )
+                    .bind(group_id)
+                    .bind(group_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    sqlx::query("DELETE FROM groups WHERE id = ?")
+                        .bind(group_id)
+                        .execute(&mut *tx)
+                        .await?;
+                    removal.deleted_groups.push(group_id);
+                }
+            }
+        }
+
+        // Memberships and tokens go with the account; its notes stay, without an author.
+        sqlx::query("DELETE FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(removal))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn page_accounts(&self, req: &PageRequest<AccountSort>) -> Result<Page<Account>> {
+        let rows = fetch_sql(&self.pool, &listing::accounts_sql(req)).await?;
+        Ok(listing::into_page(rows, req))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn create_account_token(
+        &self,
+        account_id: i64,
+        purpose: TokenPurpose,
+        token_hash: &str,
+        ttl: Duration,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_tokens (account_id, purpose, token_hash, expires_at)
+            VALUES (?, ?, ?, datetime('now', ?))
+            "#,
+        )
+        .bind(account_id)
+        .bind(purpose.as_str())
+        .bind(token_hash)
+        .bind(format!("+{} seconds", ttl.as_secs()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn consume_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<i64>> {
+        let mut tx = self.pool.begin().await?;
+        // Claiming the token is a single write, so two requests racing with
+        // the same token cannot both get it.
+        let account_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE account_tokens SET used_at = datetime('now')
+            WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > datetime('now')
+            RETURNING account_id
+            "#,
+        )
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(account_id) = account_id {
+            sqlx::query(
+                r#"
+                UPDATE account_tokens SET used_at = datetime('now')
+                WHERE account_id = ? AND purpose = ? AND used_at IS NULL
+                "#,
+            )
+            .bind(account_id)
+            .bind(purpose.as_str())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(account_id)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn token_account(&self, purpose: TokenPurpose, token_hash: &str) -> Result<Option<Account>> {
+        let row = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT a.id, a.name, a.email, a.password_hash, a.locale, a.email_verified_at, a.created_at
+            FROM account_tokens t
+            INNER JOIN accounts a ON a.id = t.account_id
+            WHERE t.token_hash = ? AND t.purpose = ? AND t.used_at IS NULL AND t.expires_at > datetime('now')
+            "#,
+        )
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn revoke_account_token(&self, purpose: TokenPurpose, token_hash: &str) -> Result<bool> {
+        let res = sqlx::query(
+            r#"
+            UPDATE account_tokens SET used_at = datetime('now')
+            WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > datetime('now')
+            "#,
+        )
+        .bind(token_hash)
+        .bind(purpose.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn mark_email_verified(&self, account_id: i64) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE accounts SET email_verified_at = COALESCE(email_verified_at, datetime('now')) WHERE id = ?",
+        )
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    // Groups ----------------------------------------------------------
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn create_group(&self, group_name: &str, created_by: i64) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let group_res = sqlx::query(
+            r#"
+            INSERT INTO groups (group_name, created_by)
+            VALUES (?, ?)
+            "#,
+        )
+        .bind(group_name)
+        .bind(created_by)
+        .execute(&mut *tx)
+        .await?;
+        let group_id = group_res.last_insert_rowid();
+
+        sqlx::query(
+            r#"
+            INSERT INTO group_users (group_id, user_id, role)
+            VALUES (?, ?, 'owner')
+            "#,
+        )
+        .bind(group_id)
+        .bind(created_by)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(group_id)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn list_groups_for_user(&self, user_id: i64) -> Result<Vec<GroupWithRole>> {
+        let rows = sqlx::query_as::<_, GroupWithRole>(
+            r#"
+            SELECT g.id, g.group_name, g.created_by, g.created_at, gu.role
+            FROM groups g
+            INNER JOIN group_users gu ON gu.group_id = g.id
+            WHERE gu.user_id = ?
+            ORDER BY g.created_at ASC, g.id ASC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn list_groups(&self) -> Result<Vec<GroupOverview>> {
+        let rows = sqlx::query_as::<_, GroupOverview>(
+            r#"
+            SELECT g.id, g.group_name, g.created_by, g.created_at,
+                   (SELECT COUNT(*) FROM group_users gu WHERE gu.group_id = g.id) AS member_count,
+                   (SELECT COUNT(*) FROM note_shares ns WHERE ns.group_id = g.id) AS note_count
+            FROM groups g
+            ORDER BY g.created_at ASC, g.id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn add_user_to_group(&self, group_id: i64, user_id: i64, role: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO group_users (group_id, user_id, role)
+            VALUES (?, ?, ?)
+            "#,
+        )
+        .bind(group_id)
+        .bind(user_id)
+        .bind(role)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn list_group_members(&self, group_id: i64) -> Result<Vec<GroupUser>> {
+        let rows = sqlx::query_as::<_, GroupUser>(
+            r#"
+            SELECT id, group_id, user_id, role, joined_at
+            FROM group_users
+            WHERE group_id = ?
+            ORDER BY joined_at ASC, id ASC
+            "#,
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn page_group_members(
+        &self,
+        group_id: i64,
+        req: &PageRequest<MemberSort>,
+    ) -> Result<Page<GroupUser>> {
+        let rows = fetch_sql(&self.pool, &listing::members_sql(group_id, req)).await?;
+        Ok(listing::into_page(rows, req))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn get_group(&self, group_id: i64) -> Result<Option<Group>> {
+        let row = sqlx::query_as::<_, Group>(
+            r#"
+            SELECT id, group_name, created_by, created_at
+            FROM groups
+            WHERE id = ?
+            "#,
+        )
+        .bind(group_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn is_user_in_group(&self, group_id: i64, user_id: i64) -> Result<bool> {
+        let exists: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT 1
+            FROM group_users
+            WHERE group_id = ? AND user_id = ?
+            "#,
+        )
+        .bind(group_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(exists.is_some())
+    }
+
+    // Notes -----------------------------------------------------------
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn create_note(&self, note: &NewNote, group_id: i64, can_edit: bool) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let note_id = insert_note(&mut tx, note, group_id, can_edit).await?;
+        tx.commit().await?;
+        Ok(note_id)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn import_notes(&self, group_id: i64, notes: &[NewNote]) -> Result<Vec<i64>> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(notes.len());
+        for note in notes {
+            ids.push(insert_note(&mut tx, note, group_id, false).await?);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn list_notes_for_group(&self, group_id: i64) -> Result<Vec<SharedNote>> {
+        let rows = sqlx::query_as::<_, SharedNote>(
+            r#"
+            SELECT
+                n.id,
+                n.title,
+                n.content,
+                n.color,
+                n.x,
+                n.y,
+                n.width,
+                n.height,
+                n.z_index,
+                n.created_by,
+                n.created_at,
+                n.updated_at,
+                ns.group_id,
+                ns.can_edit as can_edit,
+                ns.shared_at
+            FROM notes n
+            INNER JOIN note_shares ns ON ns.note_id = n.id
+            WHERE ns.group_id = ?
+            ORDER BY n.z_index ASC, n.updated_at ASC, n.id ASC
+            "#,
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn note_author_names(&self, group_id: i64) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query_as::<_, (i64, String)>(
+            r#"
+            SELECT DISTINCT a.id, a.name
+            FROM accounts a
+            INNER JOIN notes n ON n.created_by = a.id
+            INNER JOIN note_shares ns ON ns.note_id = n.id
+            WHERE ns.group_id = ?
+            "#,
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn page_notes_for_group(
+        &self,
+        group_id: i64,
+        filter: &NoteFilter,
+        req: &PageRequest<NoteSort>,
+    ) -> Result<Page<SharedNote>> {
+        let rows = fetch_sql(&self.pool, &listing::notes_sql(group_id, filter, req)).await?;
+        Ok(listing::into_page(rows, req))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn notes_changed_since(&self, group_id: i64, since: i64) -> Result<(Vec<SharedNote>, Vec<i64>)> {
+        let notes = sqlx::query_as::<_, SharedNote>(
+            r#"
+            SELECT
+                n.id,
+                n.title,
+                n.content,
+                n.color,
+                n.x,
+                n.y,
+                n.width,
+                n.height,
+                n.z_index,
+                n.created_by,
+                n.created_at,
+                n.updated_at,
+                ns.group_id,
+                ns.can_edit as can_edit,
+                ns.shared_at
+            FROM notes n
+            INNER JOIN note_shares ns ON ns.note_id = n.id
+            WHERE ns.group_id = ?
+              AND n.id IN (SELECT note_id FROM note_changes WHERE group_id = ? AND id > ?)
+            ORDER BY n.z_index ASC, n.updated_at ASC, n.id ASC
+            "#,
+        )
+        .bind(group_id)
+        .bind(group_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let deleted: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT c.note_id
+            FROM note_changes c
+            WHERE c.group_id = ? AND c.id > ?
+              AND NOT EXISTS (
+                  SELECT 1 FROM note_shares ns
+                  WHERE ns.note_id = c.note_id AND ns.group_id = c.group_id
+              )
+            ORDER BY c.note_id
+            "#,
+        )
+        .bind(group_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok((notes, deleted))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn latest_change_id(&self) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM note_changes")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn update_note_position(
+        &self,
+        note_id: i64,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        z_index: i64,
+    ) -> Result<bool> {
+        let res = sqlx::query(
+            r#"
+            UPDATE notes
+            SET x = ?, y = ?, width = ?, height = ?, z_index = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(x)
+        .bind(y)
+        .bind(width)
+        .bind(height)
+        .bind(z_index)
+        .bind(note_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn update_note_content(
+        &self,
+        note_id: i64,
+        title: Option<&str>,
+        content: Option<&str>,
+        color: &str,
+    ) -> Result<bool> {
+        let res = sqlx::query(
+            r#"
+            UPDATE notes
+            SET title = ?, content = ?, color = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(title)
+        .bind(content)
+        .bind(color)
+        .bind(note_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn delete_note(&self, note_id: i64) -> Result<bool> {
+        let res = sqlx::query(
+            r#"
+            DELETE FROM notes WHERE id = ?
+            "#,
+        )
+        .bind(note_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn apply_note_ops(&self, group_id: i64, ops: &[NoteOp]) -> Result<BatchOutcome> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(ops.len());
+        for (index, op) in ops.iter().enumerate() {
+            let (note_id, affected) = match op {
+                NoteOp::Create { note, can_edit } => {
+                    ids.push(insert_note(&mut tx, note, group_id, *can_edit).await?);
+                    continue;
+                }
+                NoteOp::UpdatePosition { note_id, x, y, width, height, z_index } => {
+                    let res = sqlx::query(
+                        r#"
+                        UPDATE notes
+                        SET x = ?, y = ?, width = ?, height = ?, z_index = ?, updated_at = CURRENT_TIMESTAMP
+                        WHERE id = ?
+                          AND EXISTS (SELECT 1 FROM note_shares WHERE note_id = notes.id AND group_id = ?)
+                        "#,
+                    )
+                    .bind(x)
+                    .bind(y)
+                    .bind(width)
+                    .bind(height)
+                    .bind(z_index)
+                    .bind(note_id)
+                    .bind(group_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    (*note_id, res.rows_affected())
+                }
+                NoteOp::UpdateContent { note_id, title, content, color } => {
+                    let res = sqlx::query(
+                        r#"
+                        UPDATE notes
+                        SET title = ?, content = ?, color = ?, updated_at = CURRENT_TIMESTAMP
+                        WHERE id = ?
+                          AND EXISTS (SELECT 1 FROM note_shares WHERE note_id = notes.id AND group_id = ?)
+                        "#,
+                    )
+                    .bind(title)
+                    .bind(content)
+                    .bind(color)
+                    .bind(note_id)
+                    .bind(group_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    (*note_id, res.rows_affected())
+                }
+                NoteOp::Delete { note_id } => {
+                    let res = sqlx::query(
+                        r#"
+                        DELETE FROM notes
+                        WHERE id = ?
+                          AND EXISTS (SELECT 1 FROM note_shares WHERE note_id = notes.id AND group_id = ?)
+                        "#,
+                    )
+                    .bind(note_id)
+                    .bind(group_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    (*note_id, res.rows_affected())
+                }
+            };
+            if affected == 0 {
+                // Dropping the transaction rolls back the ops already applied.
+                return Ok(BatchOutcome::NoteNotFound(index));
+            }
+            ids.push(note_id);
+        }
+        tx.commit().await?;
+        Ok(BatchOutcome::Applied(ids))
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn clear_notes_for_group(&self, group_id: i64) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let note_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT note_id
+            FROM note_shares
+            WHERE group_id = ?
+            "#,
+        )
+        .bind(group_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut removed: u64 = 0;
+        for note_id in note_ids {
+            let res = sqlx::query("DELETE FROM notes WHERE id = ?")
+                .bind(note_id)
+                .execute(&mut *tx)
+                .await?;
+            removed += res.rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn count_notes(&self) -> Result<i64> {
+        let c: (i64,) = sqlx::query_as("SELECT COUNT(*) as c FROM notes")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(c.0)
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn totals(&self) -> Result<Totals> {
+        let (accounts, groups, notes): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT (SELECT COUNT(*) FROM accounts),
+                   (SELECT COUNT(*) FROM groups),
+                   (SELECT COUNT(*) FROM notes)
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Totals { accounts, groups, notes })
+    }
+
+    // Maintenance -----------------------------------------------------
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn schema_version(&self) -> Result<i64> {
+        schema_version(&self.pool).await
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections(),
+        }
+    }
+
+    /// Folds the WAL back into the main file first, so the database is
+    /// self-contained once the process exits. A no-op outside WAL mode.
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn close(&self) -> Result<()> {
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool).await?;
+        self.pool.close().await;
+        Ok(())
+    }
+
+    /// Uses `VACUUM INTO`, so readers and writers are not blocked for long.
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn backup_to(&self, path: &Path) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(db.system = "sqlite"))]
+    async fn timestamp_tag(&self) -> Result<String> {
+        let tag: String = sqlx::query_scalar("SELECT strftime('%Y%m%d-%H%M%S', 'now')")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(tag)
+    }
+}
@@ -0,0 +1,223 @@
+use crate::db::{Db, SharedNote};
+use crate::i18n::Message;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn parse(raw: Option<&str>) -> Option<Self> {
+        match raw.map(|s| s.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("json") => Some(Self::Json),
+            Some("csv") => Some(Self::Csv),
+            Some("md") | Some("markdown") => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BoardExport {
+    pub group_id: i64,
+    pub group_name: String,
+    pub notes: Vec<ExportNote>,
+}
+
+#[derive(Serialize)]
+pub struct ExportNote {
+    pub id: i64,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub color: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub z_index: i64,
+    pub created_by: Option<i64>,
+    pub author_name: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl BoardExport {
+    pub fn new(
+        group_id: i64,
+        group_name: String,
+        notes: Vec<SharedNote>,
+        author_names: &HashMap<i64, String>,
+    ) -> Self {
+        let notes = notes
+            .into_iter()
+            .map(|n| ExportNote {
+                author_name: n.created_by.and_then(|id| author_names.get(&id).cloned()),
+                id: n.id,
+                title: n.title,
+                content: n.content,
+                color: n.color,
+                x: n.x,
+                y: n.y,
+                width: n.width,
+                height: n.height,
+                z_index: n.z_index,
+                created_by: n.created_by,
+                created_at: n.created_at,
+                updated_at: n.updated_at,
+            })
+            .collect();
+        Self { group_id, group_name, notes }
+    }
+
+    /// Reads the group's notes and author names. `None` when the group does
+    /// not exist.
+    pub async fn load(db: &Db, group_id: i64) -> anyhow::Result<Option<Self>> {
+        let Some(group) = db.get_group(group_id).await? else {
+            return Ok(None);
+        };
+        let notes = db.list_notes_for_group(group_id).await?;
+        let author_names: HashMap<i64, String> = db.note_author_names(group_id).await?.into_iter().collect();
+        Ok(Some(Self::new(group.id, group.group_name, notes, &author_names)))
+    }
+
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self).expect("board export serializes"),
+            ExportFormat::Csv => render_csv(self),
+            ExportFormat::Markdown => render_markdown(self),
+        }
+    }
+}
+
+pub const CSV_HEADER: [&str; 13] = [
+    "id",
+    "title",
+    "content",
+    "color",
+    "x",
+    "y",
+    "width",
+    "height",
+    "z_index",
+    "created_by",
+    "author_name",
+    "created_at",
+    "updated_at",
+];
+
+pub fn render_csv(export: &BoardExport) -> String {
+    let mut out = String::new();
+    push_csv_row(&mut out, CSV_HEADER.iter().map(|s| s.to_string()));
+    for n in &export.notes {
+        push_csv_row(
+            &mut out,
+            [
+                n.id.to_string(),
+                n.title.clone().unwrap_or_default(),
+                n.content.clone().unwrap_or_default(),
+                n.color.clone(),
+                n.x.to_string(),
+                n.y.to_string(),
+                n.width.to_string(),
+                n.height.to_string(),
+                n.z_index.to_string(),
+                n.created_by.map(|id| id.to_string()).unwrap_or_default(),
+                n.author_name.clone().unwrap_or_default(),
+                n.created_at.clone(),
+                n.updated_at.clone(),
+            ]
+            .into_iter(),
+        );
+    }
+    out
+}
+
+fn push_csv_row(out: &mut String, fields: impl Iterator<Item = String>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Notes are laid out top-to-bottom, left-to-right, then bucketed by color in
+/// the order each color first appears on the board. The placeholders for an
+/// empty board and an untitled note are in the current locale.
+pub fn render_markdown(export: &BoardExport) -> String {
+    let mut notes: Vec<&ExportNote> = export.notes.iter().collect();
+    notes.sort_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let mut sections: Vec<(&str, Vec<&ExportNote>)> = Vec::new();
+    for note in notes {
+        match sections.iter_mut().find(|(color, _)| *color == note.color) {
+            Some((_, bucket)) => bucket.push(note),
+            None => sections.push((&note.color, vec![note])),
+        }
+    }
+
+    let untitled = Message::new("export_untitled").to_string();
+    let mut out = format!("# {}\n", export.group_name);
+    if sections.is_empty() {
+        out.push_str(&format!("\n_{}_\n", Message::new("export_no_notes")));
+    }
+    for (color, bucket) in sections {
+        match color_name(color) {
+            Some(name) => out.push_str(&format!("\n## {} ({})\n\n", name, color)),
+            None => out.push_str(&format!("\n## {}\n\n", color)),
+        }
+        for note in bucket {
+            let title = note.title.as_deref().map(str::trim).filter(|t| !t.is_empty());
+            out.push_str(&format!("- **{}**", title.unwrap_or(&untitled)));
+            if let Some(author) = &note.author_name {
+                out.push_str(&format!(" — {}", author));
+            }
+            out.push('\n');
+            if let Some(content) = note.content.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+                for line in content.lines() {
+                    out.push_str(&format!("  {}\n", line));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn color_name(hex: &str) -> Option<&'static str> {
+    match hex {
+        "#FFFF88" => Some("yellow"),
+        "#FBCFE8" => Some("pink"),
+        "#BBF7D0" => Some("green"),
+        "#BFDBFE" => Some("blue"),
+        "#FED7AA" => Some("orange"),
+        "#E9D5FF" => Some("purple"),
+        _ => None,
+    }
+}
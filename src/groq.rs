@@ -1,7 +1,21 @@
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde_json::{json, Value};
+use tracing::field::Empty;
+use tracing::{instrument, Span};
 
+/// One chat completion. The `llm.chat` span carries the model and, once the
+/// response is in, the token counts Groq reports under `usage`.
+#[instrument(
+    name = "llm.chat",
+    skip_all,
+    fields(
+        gen_ai.system = "groq",
+        gen_ai.request.model = model,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+    )
+)]
 pub async fn summarize(client: &Client, api_key: &str, model: &str, text: &str) -> Result<String> {
     let prompt = format!(
         "要約してください。重要な点を3〜5行で箇条書きにして、日本語で短く。\n\n本文:\n{}",
@@ -30,6 +44,15 @@ pub async fn summarize(client: &Client, api_key: &str, model: &str, text: &str)
     }
 
     let v: Value = res.json().await.context("failed to deserialize Groq JSON")?;
+    if let Some(usage) = v.get("usage") {
+        let span = Span::current();
+        if let Some(n) = usage.get("prompt_tokens").and_then(Value::as_u64) {
+            span.record("gen_ai.usage.input_tokens", n);
+        }
+        if let Some(n) = usage.get("completion_tokens").and_then(Value::as_u64) {
+            span.record("gen_ai.usage.output_tokens", n);
+        }
+    }
     let choices = v.get("choices")
         .and_then(|c| c.as_array())
         .context("Groq API response missing 'choices' array")?;
@@ -0,0 +1,302 @@
+//! Localized API messages. Error codes stay the same in every language;
+//! only `message` is translated. Each request's locale is negotiated from
+//! `Accept-Language`, then the signed-in account's preference (Japanese when
+//! neither names a supported one), and kept in a task-local, so messages
+//! render in it wherever they are built.
+//!
+//! Messages are catalog entries keyed by error code, with `{name}`
+//! placeholders filled from [`Message::arg`]. A code whose wording differs by
+//! context has extra keys (`size_out_of_range` next to
+//! `geometry_out_of_range`).
+
+use axum::extract::Request;
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Ja,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::Ja, Locale::En];
+
+    /// A language tag such as `en` or `ja-JP`; only the primary subtag counts.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "ja" => Some(Self::Ja),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ja => "ja",
+            Self::En => "en",
+        }
+    }
+
+    /// The supported language the client prefers most, by `q` weight and
+    /// then by order. `None` if it names none of them (a bare `*` included).
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let Some(locale) = parts.next().and_then(Self::parse) else {
+                continue;
+            };
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or(0.0);
+            if q > 0.0 && best.map_or(true, |(_, best_q)| q > best_q) {
+                best = Some((locale, q));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+/// Locale of the request handled by the current task; Japanese outside one.
+pub fn current() -> Locale {
+    LOCALE.try_with(|l| *l).unwrap_or_default()
+}
+
+/// Runs `f` with messages rendered in `locale`.
+pub async fn scope<F: Future>(locale: Locale, f: F) -> F::Output {
+    LOCALE.scope(locale, f).await
+}
+
+/// The supported locale the request's `Accept-Language` prefers, if any.
+pub fn requested(headers: &HeaderMap) -> Option<Locale> {
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+}
+
+/// Handles the rest of the request in `locale`, for the middleware that
+/// negotiated it. The response says which one was used in
+/// `Content-Language`.
+pub async fn respond_in(locale: Locale, req: Request, next: Next) -> Response {
+    let res = scope(locale, next.run(req)).await;
+    with_content_language(locale, res)
+}
+
+/// Renders `response` in `locale`, for middleware answering before the
+/// locale has been negotiated.
+pub fn render_in(locale: Locale, response: impl IntoResponse) -> Response {
+    let res = LOCALE.sync_scope(locale, || response.into_response());
+    with_content_language(locale, res)
+}
+
+fn with_content_language(locale: Locale, mut res: Response) -> Response {
+    res.headers_mut().insert(header::CONTENT_LANGUAGE, header::HeaderValue::from_static(locale.as_str()));
+    res
+}
+
+/// A catalog entry with its arguments. `Display` renders it in the current
+/// locale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    key: &'static str,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self { key, args: Vec::new() }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    pub fn render(&self, locale: Locale) -> String {
+        let Some(template) = template(self.key, locale) else {
+            tracing::warn!(key = self.key, "message missing from the catalog");
+            return self.key.to_string();
+        };
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let Some(end) = after.find('}') else {
+                break;
+            };
+            let name = &after[..end];
+            match self.args.iter().find(|(n, _)| *n == name) {
+                Some((_, value)) => out.push_str(value),
+                None => out.push_str(&rest[start..start + end + 2]),
+            }
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(current()))
+    }
+}
+
+fn template(key: &str, locale: Locale) -> Option<&'static str> {
+    let (_, ja, en) = CATALOG.iter().find(|(k, _, _)| *k == key)?;
+    Some(match locale {
+        Locale::Ja => ja,
+        Locale::En => en,
+    })
+}
+
+/// `(key, ja, en)`, sorted by key.
+const CATALOG: &[(&str, &str, &str)] = &[
+    ("account_create_failed", "アカウント作成に失敗しました: {detail}", "Could not create the account: {detail}"),
+    ("account_forbidden", "他のアカウントは変更できません", "You can only change your own account"),
+    ("account_not_found", "ユーザーが存在しません", "The account does not exist"),
+    ("admin_disabled", "管理者機能は無効です (ADMIN_TOKEN 未設定)", "Admin features are disabled (ADMIN_TOKEN is not set)"),
+    ("admin_required", "管理者トークンが必要です", "An admin token is required"),
+    ("admin_token_invalid", "管理者トークンが正しくありません", "The admin token is incorrect"),
+    ("backup_failed", "バックアップに失敗しました: {detail}", "Backup failed: {detail}"),
+    ("backup_unavailable", "バックアップできるのは SQLite のデータベースファイルだけです", "Only SQLite database files can be backed up"),
+    ("batch_empty", "ops を 1 件以上指定してください", "ops must contain at least one operation"),
+    ("batch_too_large", "ops は {max} 件以下にしてください", "ops must contain at most {max} operations"),
+    ("created_account_missing", "作成したアカウントが見つかりません", "The account just created could not be found"),
+    ("created_by_invalid", "作成ユーザーIDが不正です", "created_by is not a valid account ID"),
+    ("created_group_missing", "作成したグループが見つかりません", "The group just created could not be found"),
+    ("duration_hours", "{count} 時間", "{count} hours"),
+    ("duration_minutes", "{count} 分", "{count} minutes"),
+    ("email_empty", "メールアドレスを入力してください", "Enter an email address"),
+    ("email_invalid", "メールアドレスの形式が正しくありません", "The email address is not valid"),
+    ("email_taken", "このメールアドレスは既に使われています", "This email address is already in use"),
+    ("export_no_notes", "付箋はありません", "No notes"),
+    ("export_untitled", "(無題)", "(untitled)"),
+    ("geometry_missing", "{field} がありません", "{field} is missing"),
+    ("geometry_out_of_range", "{field} は ±{max} の範囲にしてください", "{field} must be between -{max} and {max}"),
+    ("group_name_empty", "グループ名を入力してください", "Enter a group name"),
+    ("group_not_found", "グループが見つかりません", "The group was not found"),
+    ("import_header_missing", "ヘッダー行がありません", "The header row is missing"),
+    ("import_header_xy", "ヘッダーに x と y の列が必要です", "The header needs x and y columns"),
+    ("import_json", "{detail}", "{detail}"),
+    ("import_malformed", "取り込みデータを読み取れません: {detail}", "Could not read the import data: {detail}"),
+    ("import_not_collection", "JSON は配列またはオブジェクトにしてください", "The JSON must be an array or an object"),
+    ("import_not_number", "{column} の値 \"{value}\" を数値として読み取れません", "The {column} value \"{value}\" is not a number"),
+    ("import_not_utf8", "CSV は UTF-8 で送信してください", "Send CSV encoded as UTF-8"),
+    ("import_notes_missing", "\"notes\" 配列がありません", "The \"notes\" array is missing"),
+    ("import_unclosed_quote", "閉じられていない引用符があります", "A quoted field is not closed"),
+    ("internal", "{detail}", "{detail}"),
+    ("invalid_color", "色 \"{color}\" は指定できません", "The color \"{color}\" is not supported"),
+    ("invalid_cursor", "cursor が不正です", "The cursor is invalid"),
+    ("invalid_format", "format は json / csv / md のいずれかにしてください", "format must be json, csv or md"),
+    ("invalid_group_id", "グループIDが不正です", "The group ID is invalid"),
+    ("invalid_id", "グループIDが不正です", "The group ID is invalid"),
+    ("invalid_limit", "limit は 1〜{max} にしてください", "limit must be between 1 and {max}"),
+    ("invalid_note_id", "付箋IDが不正です", "The note ID is invalid"),
+    ("invalid_role", "role は owner か member にしてください", "role must be owner or member"),
+    ("invalid_sort", "sort に \"{name}\" は指定できません", "Cannot sort by \"{name}\""),
+    (
+        "invalid_updated_since",
+        "updated_since は YYYY-MM-DD または YYYY-MM-DDTHH:MM:SS 形式 (UTC) にしてください",
+        "updated_since must be YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS (UTC)",
+    ),
+    ("invalid_user_id", "ユーザーIDが不正です", "The user ID is invalid"),
+    ("locale_invalid", "locale は ja か en にしてください", "locale must be ja or en"),
+    ("login_failed", "メールアドレスかパスワードが正しくありません", "The email address or password is incorrect"),
+    ("login_required", "ログインしてください", "Sign in first"),
+    ("lookup_query_invalid", "email と q のどちらか一方を指定してください", "Give either email or q, not both"),
+    (
+        "mail_reset_body",
+        "{name} さん\n\nパスワードの再設定を受け付けました。次のリンクから新しいパスワードを設定してください。リンクの有効期限は {expires}です。\n\n{link}\n\nリンクを開けない場合は、次のコードを再設定画面に入力してください。\n{token}\n\n心当たりがない場合はこのメールを破棄してください。パスワードは変更されません。\n",
+        "Hello {name},\n\nWe received a request to reset your password. Set a new one with the link below. The link expires in {expires}.\n\n{link}\n\nIf the link does not open, enter this code on the reset page:\n{token}\n\nIf you did not ask for this, ignore this mail. Your password stays the same.\n",
+    ),
+    ("mail_reset_subject", "パスワードの再設定", "Reset your password"),
+    (
+        "mail_verify_body",
+        "{name} さん\n\nご登録ありがとうございます。次のリンクを開いてメールアドレスを確認してください。リンクの有効期限は {expires}です。\n\n{link}\n\nリンクを開けない場合は、次のコードを確認画面に入力してください。\n{token}\n\n心当たりがない場合はこのメールを破棄してください。\n",
+        "Hello {name},\n\nThanks for signing up. Open the link below to confirm your email address. The link expires in {expires}.\n\n{link}\n\nIf the link does not open, enter this code on the confirmation page:\n{token}\n\nIf you did not sign up, ignore this mail.\n",
+    ),
+    ("mail_verify_subject", "メールアドレスの確認", "Confirm your email address"),
+    ("name_empty", "名前を入力してください", "Enter a name"),
+    ("not_member", "このユーザーはグループに参加していません", "This account is not a member of the group"),
+    ("note_not_found", "付箋が見つかりません", "The note was not found"),
+    (
+        "note_not_in_group",
+        "付箋が見つからないか、このグループに共有されていません",
+        "The note does not exist or is not shared with this group",
+    ),
+    ("password_empty", "パスワードを入力してください", "Enter a password"),
+    ("password_incorrect", "現在のパスワードが正しくありません", "The current password is incorrect"),
+    ("password_required", "現在のパスワードを入力してください", "Enter your current password"),
+    ("password_short", "パスワードは6文字以上にしてください", "The password must be at least 6 characters"),
+    ("rate_limited", "リクエストが多すぎます。{secs} 秒後に再試行してください", "Too many requests. Retry in {secs} seconds"),
+    ("resync_required", "cursor が無効です。since を付けずに再取得してください", "The cursor has expired. Fetch again without since"),
+    ("session_invalid", "ログインの有効期限が切れています。ログインし直してください", "The sign-in has expired. Sign in again"),
+    ("size_out_of_range", "{field} は 0 より大きく {max} 以下にしてください", "{field} must be greater than 0 and at most {max}"),
+    ("token_invalid", "リンクが無効か、有効期限が切れています", "The link is invalid or has expired"),
+    ("too_long", "{field} は {max} 文字以内にしてください", "{field} must be at most {max} characters"),
+    ("validation_failed", "入力内容に {count} 件の問題があります", "The request has {count} invalid fields"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(template: &str) -> Vec<&str> {
+        let mut names: Vec<_> = template.split('{').skip(1).filter_map(|s| s.split_once('}')).map(|(n, _)| n).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    #[test]
+    fn catalog_is_sorted_and_consistent() {
+        for pair in CATALOG.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} must come before {}", pair[1].0, pair[0].0);
+        }
+        for (key, ja, en) in CATALOG {
+            assert_eq!(placeholders(ja), placeholders(en), "placeholders of {key}");
+            assert!(en.is_ascii(), "English text of {key}");
+        }
+    }
+
+    #[test]
+    fn renders_arguments_in_either_locale() {
+        let message = Message::new("too_long").arg("field", "title").arg("max", 200);
+        assert_eq!(message.render(Locale::Ja), "title は 200 文字以内にしてください");
+        assert_eq!(message.render(Locale::En), "title must be at most 200 characters");
+        assert_eq!(Message::new("no_such_key").render(Locale::En), "no_such_key");
+    }
+
+    #[test]
+    fn accept_language_negotiation() {
+        let pick = Locale::from_accept_language;
+        assert_eq!(pick("en-US,en;q=0.9"), Some(Locale::En));
+        assert_eq!(pick("fr-FR, en;q=0.5, ja;q=0.8"), Some(Locale::Ja));
+        assert_eq!(pick("ja;q=0, en;q=0.1"), Some(Locale::En));
+        assert_eq!(pick("EN_gb"), Some(Locale::En));
+        assert_eq!(pick("de, *"), None);
+        assert_eq!(pick(""), None);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::i18n::Message;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// One entry of an import payload before validation. Every field is optional
+/// so that missing values can be reported per row instead of failing the
+/// whole request.
+#[derive(Deserialize, Debug, Default)]
+pub struct ImportRow {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub color: Option<String>,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub z_index: Option<i64>,
+    pub created_by: Option<i64>,
+}
+
+/// An entry with where it came from, so the report can point at it.
+#[derive(Debug)]
+pub struct ParsedRow {
+    /// The line a CSV record starts on (the header is line 1), or the 1-based
+    /// position of a JSON entry in its array.
+    pub row: usize,
+    pub entry: Result<ImportRow, Message>,
+}
+
+/// Accepts either the `export?format=json` document or a bare array of notes.
+pub fn parse_json(body: &[u8]) -> Result<Vec<ParsedRow>, Message> {
+    let value: Value = serde_json::from_slice(body).map_err(json_error)?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut obj) => match obj.remove("notes") {
+            Some(Value::Array(items)) => items,
+            _ => return Err(Message::new("import_notes_missing")),
+        },
+        _ => return Err(Message::new("import_not_collection")),
+    };
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| ParsedRow {
+            row: index + 1,
+            entry: serde_json::from_value::<ImportRow>(item).map_err(json_error),
+        })
+        .collect())
+}
+
+/// serde's own wording, which is English in every locale.
+fn json_error(e: serde_json::Error) -> Message {
+    Message::new("import_json").arg("detail", e)
+}
+
+/// Columns are matched by header name; unknown columns (such as `id` or
+/// `author_name` from a CSV export) are ignored.
+pub fn parse_csv(text: &str) -> Result<Vec<ParsedRow>, Message> {
+    let mut records = split_csv(text.trim_start_matches('\u{feff}'))?.into_iter();
+    let (_, header) = records.next().ok_or_else(|| Message::new("import_header_missing"))?;
+    let header: Vec<String> = header
+        .into_iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    if !header.iter().any(|h| h == "x") || !header.iter().any(|h| h == "y") {
+        return Err(Message::new("import_header_xy"));
+    }
+
+    Ok(records
+        .filter(|(_, record)| !record.iter().all(|f| f.trim().is_empty()))
+        .map(|(line, record)| ParsedRow { row: line, entry: parse_record(&header, record) })
+        .collect())
+}
+
+fn parse_record(header: &[String], record: Vec<String>) -> Result<ImportRow, Message> {
+    let mut row = ImportRow::default();
+    for (name, field) in header.iter().zip(record) {
+        let text = Some(field).filter(|f| !f.is_empty());
+        match name.as_str() {
+            "title" => row.title = text,
+            "content" => row.content = text,
+            "color" => row.color = text,
+            "x" => row.x = parse_field(name, text)?,
+            "y" => row.y = parse_field(name, text)?,
+            "width" => row.width = parse_field(name, text)?,
+            "height" => row.height = parse_field(name, text)?,
+            "z_index" => row.z_index = parse_field(name, text)?,
+            "created_by" => row.created_by = parse_field(name, text)?,
+            _ => {}
+        }
+    }
+    Ok(row)
+}
+
+fn parse_field<T: std::str::FromStr>(name: &str, text: Option<String>) -> Result<Option<T>, Message> {
+    match text {
+        None => Ok(None),
+        Some(t) => t
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| Message::new("import_not_number").arg("column", name).arg("value", &t)),
+    }
+}
+
+/// Minimal RFC 4180 reader: quoted fields may contain commas, doubled quotes
+/// and line breaks. Each record comes with the line it starts on.
+fn split_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, Message> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    let mut start = 1;
+
+    while let Some(c) = chars.next() {
+        if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
+            line += 1;
+        }
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                start = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(Message::new("import_unclosed_quote"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    Ok(records)
+}
@@ -0,0 +1,17 @@
+//! The server as a library: `main.rs` wires it to the environment, and the
+//! integration tests in `tests/` build the router directly.
+
+pub mod api;
+pub mod backup;
+pub mod cli;
+pub mod config;
+pub mod i18n;
+pub mod mail;
+pub mod db;
+pub mod metrics;
+pub mod rate_limit;
+pub mod security;
+pub mod telemetry;
+mod export;
+mod import;
+mod snapshot;
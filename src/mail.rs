@@ -0,0 +1,275 @@
+//! Outgoing mail. A [`Mailer`] delivers messages: over SMTP in production, or
+//! to the log or a directory of `.eml` files when there is no mail server
+//! (development and tests). [`AccountMailer`] writes the address
+//! verification and password reset mails on top of it, in the recipient's
+//! language.
+
+use crate::config::{MailConfig, MailTransport};
+use crate::db::Account;
+use crate::i18n::{self, Locale, Message};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::task::TaskTracker;
+
+/// A plain-text message to one recipient.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<()>;
+}
+
+/// The mailer selected by `mail.transport`.
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>> {
+    Ok(match config.transport {
+        MailTransport::Log => Arc::new(LogMailer),
+        MailTransport::File => Arc::new(FileMailer::new(config.dir.clone(), config.from.clone())),
+        #[cfg(feature = "smtp")]
+        MailTransport::Smtp => Arc::new(smtp::SmtpMailer::new(&config.smtp, &config.from)?),
+        // Rejected by Config::validate.
+        #[cfg(not(feature = "smtp"))]
+        MailTransport::Smtp => anyhow::bail!("this build has no SMTP support (rebuild with --features smtp)"),
+    })
+}
+
+/// `local@domain`, strict enough to catch typos rather than a full RFC 5322
+/// parser: no spaces or quoting in the local part, and a domain of letters,
+/// digits and inner hyphens between dots.
+pub fn is_address(address: &str) -> bool {
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| !c.is_whitespace() && !c.is_control() && !"\"(),:;<>@[\\]".contains(c));
+    let domain_ok = !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    local_ok && domain_ok
+}
+
+/// `address` or `Display Name <address>`, as accepted for `mail.from`.
+pub fn is_mailbox(mailbox: &str) -> bool {
+    let mailbox = mailbox.trim();
+    match mailbox.strip_suffix('>').and_then(|m| m.rsplit_once('<')) {
+        Some((name, address)) => !name.contains(['<', '>', '"']) && is_address(address),
+        None => is_address(mailbox),
+    }
+}
+
+/// Logs the recipient and subject of every mail at `info`. The body is left
+/// out: its links carry tokens that would let anyone reading the log verify
+/// the address or reset the password.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> Result<()> {
+        tracing::info!(to = %mail.to, subject = %mail.subject, "mail not sent (log transport)");
+        Ok(())
+    }
+}
+
+/// Writes each mail to its own `.eml` file in `dir`, named so that a
+/// directory listing sorts them in the order they were sent. Headers and
+/// body are unencoded UTF-8 (RFC 6532), which mail clients open and tests
+/// can search as is.
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+    /// Orders mails sent within the same millisecond.
+    sent: AtomicU64,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf, from: String) -> Self {
+        Self { dir, from, sent: AtomicU64::new(0) }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> Result<()> {
+        let body = mail.body.replace("\r\n", "\n").replace('\n', "\r\n");
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{body}",
+            self.from, mail.to, mail.subject,
+        );
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let seq = self.sent.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!("{millis:013}-{seq:06}-{}.eml", uuid::Uuid::new_v4().simple()));
+        tokio::fs::write(&path, message)
+            .await
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "smtp")]
+mod smtp {
+    use super::{Mail, Mailer};
+    use crate::config::{SmtpConfig, SmtpTls};
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use lettre::message::{header::ContentType, Mailbox};
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+    use std::time::Duration;
+
+    pub struct SmtpMailer {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+    }
+
+    impl SmtpMailer {
+        pub fn new(config: &SmtpConfig, from: &str) -> Result<Self> {
+            let from = from.parse().with_context(|| format!("invalid mail.from {from:?}"))?;
+            let builder = match config.tls {
+                SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+                SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+                SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            };
+            let mut builder = builder.port(config.port).timeout(Some(Duration::from_secs(10)));
+            if let (Some(username), Some(password)) = (&config.username, &config.password) {
+                builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+            Ok(Self { transport: builder.build(), from })
+        }
+    }
+
+    #[async_trait]
+    impl Mailer for SmtpMailer {
+        async fn send(&self, mail: &Mail) -> Result<()> {
+            let to: Mailbox = mail.to.parse().with_context(|| format!("invalid recipient {:?}", mail.to))?;
+            let message = lettre::Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(&mail.subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(mail.body.clone())?;
+            self.transport.send(message).await.context("SMTP delivery failed")?;
+            Ok(())
+        }
+    }
+}
+
+/// Writes and sends the mails about an account.
+#[derive(Clone)]
+pub struct AccountMailer {
+    mailer: Arc<dyn Mailer>,
+    /// The sends still running, for [`AccountMailer::drain`].
+    sends: TaskTracker,
+    link_base_url: String,
+    verify_ttl: Duration,
+    reset_ttl: Duration,
+}
+
+impl Default for AccountMailer {
+    /// Logs mails, with the default link base and lifetimes.
+    fn default() -> Self {
+        Self::new(Arc::new(LogMailer), &MailConfig::default())
+    }
+}
+
+impl AccountMailer {
+    pub fn new(mailer: Arc<dyn Mailer>, config: &MailConfig) -> Self {
+        Self {
+            mailer,
+            sends: TaskTracker::new(),
+            link_base_url: config.link_base_url.trim_end_matches('/').to_string(),
+            verify_ttl: Duration::from_secs(config.verify_ttl_minutes * 60),
+            reset_ttl: Duration::from_secs(config.reset_ttl_minutes * 60),
+        }
+    }
+
+    /// How long an address verification token stays valid.
+    pub fn verify_ttl(&self) -> Duration {
+        self.verify_ttl
+    }
+
+    /// How long a password reset token stays valid.
+    pub fn reset_ttl(&self) -> Duration {
+        self.reset_ttl
+    }
+
+    pub fn verification(&self, account: &Account, token: &str) -> Mail {
+        let keys = ("mail_verify_subject", "mail_verify_body");
+        self.account_mail(account, keys, "/verify-email", token, self.verify_ttl)
+    }
+
+    pub fn password_reset(&self, account: &Account, token: &str) -> Mail {
+        let keys = ("mail_reset_subject", "mail_reset_body");
+        self.account_mail(account, keys, "/reset-password", token, self.reset_ttl)
+    }
+
+    /// Sends `mail` in the background, so the response neither waits for the
+    /// mail server nor reveals through its timing whether a mail went out.
+    /// Failures are logged.
+    pub fn send_later(&self, mail: Mail) {
+        let mailer = self.mailer.clone();
+        self.sends.spawn(async move {
+            if let Err(e) = mailer.send(&mail).await {
+                tracing::error!(subject = %mail.subject, "failed to send mail: {e:#}");
+            }
+        });
+    }
+
+    /// Waits up to `timeout` for the mails still being sent, at shutdown
+    /// once no request can start another. Returns `false` if some were cut
+    /// off.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.sends.close();
+        tokio::time::timeout(timeout, self.sends.wait()).await.is_ok()
+    }
+
+    /// Rendered in the account's language, or the request's if it has not
+    /// chosen one.
+    fn account_mail(
+        &self,
+        account: &Account,
+        (subject, body): (&'static str, &'static str),
+        path: &str,
+        token: &str,
+        ttl: Duration,
+    ) -> Mail {
+        let locale = account.locale.as_deref().and_then(Locale::parse).unwrap_or_else(i18n::current);
+        let body = Message::new(body)
+            .arg("name", &account.name)
+            .arg("link", format!("{}{path}?token={token}", self.link_base_url))
+            .arg("token", token)
+            .arg("expires", expires_in(ttl).render(locale))
+            .render(locale);
+        Mail { to: account.email.clone(), subject: Message::new(subject).render(locale), body }
+    }
+}
+
+/// Whole hours when the lifetime is a multiple of one, minutes otherwise.
+fn expires_in(ttl: Duration) -> Message {
+    let minutes = ttl.as_secs() / 60;
+    if minutes % 60 == 0 {
+        Message::new("duration_hours").arg("count", minutes / 60)
+    } else {
+        Message::new("duration_minutes").arg("count", minutes)
+    }
+}
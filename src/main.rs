@@ -1,50 +1,188 @@
-mod api;
-mod db;
-
+use app::cli::{self, CliError, Command};
+use app::config::Config;
+use app::rate_limit::RateLimiter;
+use app::config::MailTransport;
+use app::mail::{self, AccountMailer};
+use app::{api, backup, db, security, telemetry};
 use dotenv::dotenv;
 use std::env;
-use anyhow::{Context, Result};
+use std::io::IsTerminal;
 use std::net::SocketAddr;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::process::ExitCode;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use anyhow::Result;
 use tower_http::services::{ServeDir, ServeFile};
-use tower_http::cors::{Any, CorsLayer};
-use axum::http::Method;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+async fn main() -> ExitCode {
     dotenv().ok();
-    // Use a simple relative DB file by default to avoid path/permission issues
-    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://app.db".to_string());
 
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => e.report(),
+    }
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::parse_args(env::args().skip(1))?;
+    if let Command::Help = args.command {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    let config = Config::load(args.config_file.as_deref())
+        .map_err(|e| CliError::new(CliError::CONFIG, format!("{e:#}")))?;
+    if args.print_config {
+        print!("{}", config.to_redacted_toml()?);
+        return Ok(());
+    }
+    let _telemetry = telemetry::init(&config)?;
+
+    match args.command {
+        Command::Serve => Ok(serve(config).await?),
+        Command::Help => unreachable!("handled above"),
+        Command::Admin(command) => {
+            let stdin = std::io::stdin();
+            if command.reads_password() && stdin.is_terminal() {
+                eprint!("password: ");
+            }
+            cli::run(command, &config, &mut stdin.lock(), &mut std::io::stdout().lock()).await
+        }
+    }
+}
+
+async fn serve(config: Config) -> Result<()> {
     // DB
-    let db = db::Db::init(&database_url).await?;
+    let db = db::Db::init(&config.database_url).await?;
+    let backups = config.backups();
+    // Flipped once on shutdown; background tasks watch it.
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let scheduler = backup::spawn_scheduler(db.clone(), backups.clone(), stop_rx.clone());
+
+    if config.mail.transport == MailTransport::Log {
+        tracing::warn!("mail.transport is log: verification and password reset mails are only logged");
+    }
 
     // API router
-    let api_router = api::routes(api::AppState { db, database_url: database_url.clone() });
+    let llm_endpoint = (config.groq.probe_on_ready && config.groq.api_key.is_some())
+        .then(|| "api.groq.com:443".to_string());
+    let mailer = AccountMailer::new(mail::from_config(&config.mail)?, &config.mail);
+    let api_router = api::routes(api::AppState {
+        db: db.clone(),
+        database_url: config.database_url.clone(),
+        admin_token: config.admin_token.clone(),
+        backups,
+        metrics: Default::default(),
+        rate_limiter: Arc::new(RateLimiter::new(&config.rate_limit)),
+        limits: config.limits.clone(),
+        mail: mailer.clone(),
+        llm_endpoint,
+    });
 
     // Static files under ./public with SPA-ish index fallback
     let static_service = ServeDir::new("public").not_found_service(ServeFile::new("public/index.html"));
-    // CORS (dev use: allow any origin/method/header)
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PATCH, Method::OPTIONS])
-        .allow_headers(Any);
-
     let app = axum::Router::new()
         .merge(api_router)
-        .nest_service("/", static_service)
-        .layer(cors);
+        .nest_service("/", static_service);
+    let app = telemetry::apply(app);
+    let app = security::apply(app, &config);
 
     // Bind
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port: u16 = env::var("PORT").ok().and_then(|s| s.parse().ok()).unwrap_or(8080);
-    let addr: SocketAddr = format!("{}:{}", host, port).parse().context("invalid HOST/PORT")?;
+    let addr = config.listen_addr()?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
     tracing::info!("listening on http://{}", addr);
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
+    let mut server = tokio::spawn(async move {
+        let mut stop = stop_rx;
+        // Connect info gives the rate limiter the client address.
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                let _ = stop.changed().await;
+            })
+            .await
+    });
+
+    // Run until a signal, then stop accepting connections and give in-flight
+    // requests `shutdown_timeout` to finish. A second signal cuts them off.
+    tokio::select! {
+        res = &mut server => res??,
+        _ = shutdown_signal() => {
+            let timeout = config.shutdown_timeout();
+            tracing::info!("shutting down; draining requests for up to {}s", timeout.as_secs());
+            let _ = stop_tx.send(true);
+            tokio::select! {
+                res = tokio::time::timeout(timeout, &mut server) => match res {
+                    Ok(res) => res??,
+                    Err(_) => {
+                        tracing::warn!("drain timeout reached; dropping remaining requests");
+                        abort(server).await?;
+                    }
+                },
+                _ = shutdown_signal() => {
+                    tracing::warn!("second signal; dropping remaining requests");
+                    abort(server).await?;
+                }
+            }
+        }
+    }
+
+    // Background tasks finish their current run before the pool goes away.
+    let _ = stop_tx.send(true);
+    if let Some(scheduler) = scheduler {
+        if let Err(e) = scheduler.await {
+            tracing::error!("backup scheduler failed: {e}");
+        }
+    }
+    // The tokens in mails still being sent are already stored; let the mails
+    // go out too.
+    let timeout = config.shutdown_timeout();
+    tokio::select! {
+        sent = mailer.drain(timeout) => if !sent {
+            tracing::warn!("mail timeout reached; dropping unsent mails");
+        },
+        _ = shutdown_signal() => tracing::warn!("second signal; dropping unsent mails"),
+    }
+    db.close().await?;
+    tracing::info!("shutdown complete");
     Ok(())
 }
+
+/// Aborts the server task and waits until it is gone, so the listener is
+/// closed before the pool is. Requests already running hold on to their pool
+/// connections until they end; `Db::close` waits for those.
+async fn abort(server: JoinHandle<std::io::Result<()>>) -> Result<()> {
+    server.abort();
+    match server.await {
+        Ok(res) => Ok(res?),
+        Err(e) if e.is_cancelled() => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on Unix (what `docker stop` sends).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
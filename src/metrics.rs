@@ -0,0 +1,105 @@
+//! Request counters and latency histograms per route, rendered in the
+//! Prometheus text format by `/metrics`. Routes are labelled with their
+//! template (`/api/groups/:id`), so label cardinality stays bounded.
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds in seconds of the latency histogram buckets.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub struct Metrics {
+    started: Instant,
+    routes: Mutex<BTreeMap<(String, String), RouteStats>>,
+}
+
+#[derive(Default)]
+struct RouteStats {
+    by_status: BTreeMap<u16, u64>,
+    /// Non-cumulative counts per bucket; the last slot is `+Inf`.
+    buckets: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self { started: Instant::now(), routes: Mutex::default() }
+    }
+}
+
+impl Metrics {
+    pub fn record(&self, method: &Method, route: &str, status: StatusCode, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let mut routes = self.routes.lock().unwrap();
+        let stats = routes.entry((route.to_string(), method.to_string())).or_default();
+        *stats.by_status.entry(status.as_u16()).or_default() += 1;
+        let bucket = BUCKETS.iter().position(|&le| secs <= le).unwrap_or(BUCKETS.len());
+        stats.buckets[bucket] += 1;
+        stats.sum += secs;
+        stats.count += 1;
+    }
+
+    /// Appends the request metrics and process uptime to `out`.
+    pub fn render(&self, out: &mut String) {
+        gauge(out, "process_uptime_seconds", "Seconds since the server started.", self.started.elapsed().as_secs_f64());
+
+        let routes = self.routes.lock().unwrap();
+        header(out, "http_requests_total", "counter", "HTTP requests by route template, method and status.");
+        for ((route, method), stats) in routes.iter() {
+            for (status, n) in &stats.by_status {
+                let _ = writeln!(
+                    out,
+                    "http_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {n}",
+                    escape(route)
+                );
+            }
+        }
+
+        header(out, "http_request_duration_seconds", "histogram", "HTTP request latency by route template and method.");
+        for ((route, method), stats) in routes.iter() {
+            let labels = format!("method=\"{method}\",route=\"{}\"", escape(route));
+            let mut cumulative = 0;
+            for (i, n) in stats.buckets.iter().enumerate() {
+                cumulative += n;
+                let le = BUCKETS.get(i).map_or("+Inf".to_string(), f64::to_string);
+                let _ = writeln!(out, "http_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+            }
+            let _ = writeln!(out, "http_request_duration_seconds_sum{{{labels}}} {}", stats.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count{{{labels}}} {}", stats.count);
+        }
+    }
+}
+
+/// Middleware recording every request that matched a route.
+pub async fn track(State(metrics): State<Arc<Metrics>>, req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = req.method().clone();
+    let route = req.extensions().get::<MatchedPath>().map(|p| p.as_str().to_string());
+    let res = next.run(req).await;
+    if let Some(route) = route {
+        metrics.record(&method, &route, res.status(), started.elapsed());
+    }
+    res
+}
+
+pub fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// A single unlabelled gauge with its HELP and TYPE lines.
+pub fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, "gauge", help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,255 @@
+//! Token-bucket rate limiting per client and route group. Each client gets
+//! one bucket per group holding up to `burst` requests and refilling at
+//! `per_minute`; an empty bucket means `429 rate_limited` with `Retry-After`.
+//!
+//! Clients are keyed by the signed-in account when the request carries a
+//! session the limiter has seen lately, and otherwise by IP address (the
+//! connection's, or the last `X-Forwarded-For` entry when trusted). The
+//! limiter runs before the session is looked up, so a flood of requests
+//! costs no queries; it learns which account a session belongs to at sign-in
+//! and whenever a request's session is resolved. Routes outside `/api`
+//! (probes, metrics, static files) are never limited.
+
+use crate::config::{RateLimit, RateLimitConfig};
+use axum::extract::{ConnectInfo, Request};
+use axum::http::Method;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets and sessions are pruned once this many are tracked, so a scan
+/// from many addresses cannot grow the maps without bound.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// How long a session is counted against its account after it was last
+/// resolved. A session revoked in the meantime only keeps using the
+/// account's bucket; it is still rejected.
+const SESSION_MEMORY: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Default,
+    Auth,
+}
+
+impl RouteGroup {
+    /// The group limiting `method route` (a route template such as
+    /// `/api/groups/:id`), or `None` for unlimited routes.
+    pub fn classify(method: &Method, route: &str) -> Option<Self> {
+        if !route.starts_with("/api/") || route == "/api/openapi.json" {
+            return None;
+        }
+        // Sign-up and every account change that checks a password.
+        if route.starts_with("/api/auth/") || (method != Method::GET && route.starts_with("/api/accounts")) {
+            Some(Self::Auth)
+        } else {
+            Some(Self::Default)
+        }
+    }
+}
+
+/// Who a request is counted against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    /// The signed-in account, from whatever address it connects.
+    Account(i64),
+    Ip(IpAddr),
+    /// Neither a session nor connect info (in-process tests); these requests
+    /// share one bucket.
+    Unknown,
+}
+
+/// Shared by all requests. The default value limits nothing.
+#[derive(Default)]
+pub struct RateLimiter {
+    limits: HashMap<RouteGroup, RateLimit>,
+    trust_forwarded_for: bool,
+    buckets: Mutex<HashMap<(RouteGroup, ClientKey), Bucket>>,
+    /// Account and time last seen of each session, by token digest.
+    sessions: Mutex<HashMap<String, (i64, Instant)>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let mut limits = HashMap::new();
+        if config.enabled {
+            let groups = [(RouteGroup::Default, config.default), (RouteGroup::Auth, config.auth)];
+            limits.extend(groups.into_iter().filter(|(_, limit)| limit.per_minute > 0));
+        }
+        Self {
+            limits,
+            trust_forwarded_for: config.trust_forwarded_for,
+            buckets: Mutex::default(),
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Whether requests in `group` are limited at all, so callers can skip
+    /// working out the client.
+    pub fn is_limited(&self, group: RouteGroup) -> bool {
+        self.limits.contains_key(&group)
+    }
+
+    /// Counts later requests with the session whose token has this digest
+    /// against `account_id`.
+    pub fn remember_session(&self, token_digest: &str, account_id: i64) {
+        if self.limits.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= PRUNE_THRESHOLD {
+            sessions.retain(|_, (_, seen)| now.duration_since(*seen) < SESSION_MEMORY);
+        }
+        sessions.insert(token_digest.to_string(), (account_id, now));
+    }
+
+    /// Who `req` is counted against: the account of a remembered session
+    /// (`token_digest` is that of its bearer token), else its address.
+    pub fn client_key(&self, req: &Request, token_digest: Option<&str>) -> ClientKey {
+        let account = token_digest.and_then(|digest| {
+            let sessions = self.sessions.lock().unwrap();
+            let (account_id, seen) = sessions.get(digest)?;
+            (seen.elapsed() < SESSION_MEMORY).then_some(*account_id)
+        });
+        match account {
+            Some(account_id) => ClientKey::Account(account_id),
+            None => self.client_ip(req).map_or(ClientKey::Unknown, ClientKey::Ip),
+        }
+    }
+
+    /// The address of the client sending `req`. `None` when the server was
+    /// not started with connect info (in-process tests).
+    pub fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        if self.trust_forwarded_for {
+            // Earlier entries are whatever the client sent; only the one our
+            // proxy appended can be trusted.
+            let forwarded = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip())
+    }
+
+    /// Takes a token from the client's bucket, or returns how long until one
+    /// is available.
+    pub fn check(&self, group: RouteGroup, client: ClientKey) -> Result<(), Duration> {
+        self.check_at(group, client, Instant::now())
+    }
+
+    fn check_at(&self, group: RouteGroup, client: ClientKey, now: Instant) -> Result<(), Duration> {
+        let Some(limit) = self.limits.get(&group) else {
+            return Ok(());
+        };
+        let per_sec = f64::from(limit.per_minute) / 60.0;
+        let burst = f64::from(limit.burst);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // Full buckets carry no state worth keeping.
+            buckets.retain(|(group, _), bucket| {
+                let limit = &self.limits[group];
+                let refilled = now.duration_since(bucket.updated).as_secs_f64() * f64::from(limit.per_minute) / 60.0;
+                bucket.tokens + refilled < f64::from(limit.burst)
+            });
+        }
+        let bucket = buckets.entry((group, client)).or_insert(Bucket { tokens: burst, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig { auth: RateLimit { per_minute, burst }, ..RateLimitConfig::default() })
+    }
+
+    #[test]
+    fn bucket_drains_and_refills() {
+        let limiter = limiter(60, 2);
+        let ip = ClientKey::Ip(IpAddr::from([10, 0, 0, 1]));
+        let start = Instant::now();
+        assert!(limiter.check_at(RouteGroup::Auth, ip, start).is_ok());
+        assert!(limiter.check_at(RouteGroup::Auth, ip, start).is_ok());
+        let wait = limiter.check_at(RouteGroup::Auth, ip, start).unwrap_err();
+        assert_eq!(wait.as_secs_f64().ceil(), 1.0);
+
+        // Another client and another group have their own buckets.
+        assert!(limiter.check_at(RouteGroup::Auth, ClientKey::Ip(IpAddr::from([10, 0, 0, 2])), start).is_ok());
+        assert!(limiter.check_at(RouteGroup::Auth, ClientKey::Account(1), start).is_ok());
+        assert!(limiter.check_at(RouteGroup::Default, ip, start).is_ok());
+
+        // One token per second; never more than the burst.
+        assert!(limiter.check_at(RouteGroup::Auth, ip, start + Duration::from_secs(1)).is_ok());
+        let later = start + Duration::from_secs(600);
+        assert!(limiter.check_at(RouteGroup::Auth, ip, later).is_ok());
+        assert!(limiter.check_at(RouteGroup::Auth, ip, later).is_ok());
+        assert!(limiter.check_at(RouteGroup::Auth, ip, later).is_err());
+    }
+
+    #[test]
+    fn zero_rate_or_disabled_means_unlimited() {
+        let limiter = limiter(0, 0);
+        for _ in 0..100 {
+            assert!(limiter.check(RouteGroup::Auth, ClientKey::Unknown).is_ok());
+        }
+        let config = RateLimitConfig { enabled: false, ..RateLimitConfig::default() };
+        let limiter = RateLimiter::new(&config);
+        for _ in 0..1000 {
+            assert!(limiter.check(RouteGroup::Default, ClientKey::Unknown).is_ok());
+        }
+    }
+
+    #[test]
+    fn remembered_sessions_and_forwarded_addresses() {
+        let limiter = RateLimiter::new(&RateLimitConfig { trust_forwarded_for: true, ..RateLimitConfig::default() });
+        let request = |forwarded_for: &str| {
+            Request::builder().header("x-forwarded-for", forwarded_for).body(axum::body::Body::empty()).unwrap()
+        };
+        let req = request("198.51.100.1, 203.0.113.7");
+        let proxied = ClientKey::Ip(IpAddr::from([203, 0, 113, 7]));
+        assert_eq!(limiter.client_key(&req, None), proxied);
+        assert_eq!(limiter.client_key(&req, Some("digest")), proxied);
+        limiter.remember_session("digest", 42);
+        assert_eq!(limiter.client_key(&req, Some("digest")), ClientKey::Account(42));
+        assert_eq!(limiter.client_key(&req, Some("other")), proxied);
+        assert_eq!(limiter.client_key(&request("not an address"), None), ClientKey::Unknown);
+    }
+
+    #[test]
+    fn routes_are_grouped() {
+        let group = |method: Method, route: &str| RouteGroup::classify(&method, route);
+        assert_eq!(group(Method::POST, "/api/accounts"), Some(RouteGroup::Auth));
+        assert_eq!(group(Method::GET, "/api/accounts"), Some(RouteGroup::Default));
+        assert_eq!(group(Method::PUT, "/api/accounts/:id/password"), Some(RouteGroup::Auth));
+        assert_eq!(group(Method::DELETE, "/api/accounts/:id"), Some(RouteGroup::Auth));
+        assert_eq!(group(Method::GET, "/api/accounts/:id/groups"), Some(RouteGroup::Default));
+        assert_eq!(group(Method::POST, "/api/auth/password-reset"), Some(RouteGroup::Auth));
+        assert_eq!(group(Method::POST, "/api/groups/:id/notes"), Some(RouteGroup::Default));
+        assert_eq!(group(Method::GET, "/healthz"), None);
+        assert_eq!(group(Method::GET, "/metrics"), None);
+        assert_eq!(group(Method::GET, "/api/openapi.json"), None);
+    }
+}
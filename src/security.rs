@@ -0,0 +1,101 @@
+//! Browser-facing hardening applied around the whole app (API and static
+//! files): CORS from the configured origin allowlist, and a middleware adding
+//! CSP, `X-Content-Type-Options`, `Referrer-Policy` and HSTS to every
+//! response.
+
+use crate::config::{Config, CorsConfig, SecurityConfig};
+use crate::telemetry::REQUEST_ID_HEADER;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Same-origin everything; inline styles are allowed for the note colours in
+/// SVG snapshots.
+pub const DEFAULT_CSP: &str = "default-src 'self'; img-src 'self' data: blob:; \
+     style-src 'self' 'unsafe-inline'; object-src 'none'; base-uri 'self'; \
+     frame-ancestors 'none'; form-action 'self'";
+
+/// The Redoc page at `/api/docs` loads its bundle and fonts from CDNs and
+/// starts with an inline script.
+const DOCS_CSP: &str = "default-src 'self'; \
+     script-src 'self' 'unsafe-inline' https://cdn.redoc.ly; \
+     style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+     font-src 'self' https://fonts.gstatic.com; img-src 'self' data: https:; \
+     worker-src 'self' blob:; object-src 'none'; frame-ancestors 'none'";
+
+const DOCS_PATH: &str = "/api/docs";
+
+/// Wraps `router` with the CORS and security-header layers from `config`.
+pub fn apply(router: Router, config: &Config) -> Router {
+    let headers = Arc::new(SecurityHeaders::new(&config.security));
+    router
+        .layer(middleware::from_fn_with_state(headers, security_headers))
+        .layer(cors_layer(&config.cors))
+}
+
+pub fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let origins = &config.allowed_origins;
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        // Validated as origins by Config::validate.
+        AllowOrigin::list(origins.iter().map(|o| HeaderValue::from_str(o).expect("validated origin")))
+    };
+    // Listed explicitly: `*` is not allowed together with credentials.
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::ACCEPT,
+            header::ACCEPT_LANGUAGE,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            REQUEST_ID_HEADER,
+        ])
+        .expose_headers([header::CONTENT_DISPOSITION, REQUEST_ID_HEADER])
+        .allow_credentials(config.allow_credentials)
+}
+
+struct SecurityHeaders {
+    csp: Option<HeaderValue>,
+    hsts: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    fn new(config: &SecurityConfig) -> Self {
+        let csp = Some(config.content_security_policy.as_str())
+            .filter(|p| !p.is_empty())
+            // Validated by Config::validate.
+            .map(|p| HeaderValue::from_str(p).expect("validated CSP"));
+        let hsts = (config.hsts_max_age_secs > 0).then(|| {
+            HeaderValue::from_str(&format!("max-age={}; includeSubDomains", config.hsts_max_age_secs))
+                .expect("numeric header value")
+        });
+        Self { csp, hsts }
+    }
+}
+
+/// Headers a handler already set are left alone.
+async fn security_headers(State(config): State<Arc<SecurityHeaders>>, req: Request, next: Next) -> Response {
+    let is_docs = req.uri().path() == DOCS_PATH;
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    let mut set = |name: HeaderName, value: HeaderValue| {
+        headers.entry(name).or_insert(value);
+    };
+    set(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    set(header::REFERRER_POLICY, HeaderValue::from_static("strict-origin-when-cross-origin"));
+    if is_docs {
+        set(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(DOCS_CSP));
+    } else if let Some(csp) = &config.csp {
+        set(header::CONTENT_SECURITY_POLICY, csp.clone());
+    }
+    if let Some(hsts) = &config.hsts {
+        set(header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+    }
+    res
+}
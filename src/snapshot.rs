@@ -0,0 +1,223 @@
+use crate::db::SharedNote;
+use anyhow::{Context, Result};
+use resvg::{tiny_skia, usvg};
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+
+const PADDING: f64 = 40.0;
+const NOTE_PADDING: f64 = 12.0;
+const TITLE_SIZE: f64 = 16.0;
+const CONTENT_SIZE: f64 = 13.0;
+const LINE_GAP: f64 = 1.4;
+const EMPTY_WIDTH: f64 = 800.0;
+const EMPTY_HEIGHT: f64 = 600.0;
+/// Upper bound for either side of the PNG; larger boards are scaled down.
+const MAX_PNG_SIDE: f64 = 4096.0;
+const FONT_FAMILY: &str =
+    "'Noto Sans CJK JP', 'Hiragino Sans', 'Yu Gothic', 'DejaVu Sans', sans-serif";
+
+/// Draws the notes in the order given, so callers should pass them sorted by
+/// `z_index` (as `list_notes_for_group` does).
+pub fn render_svg(group_name: &str, notes: &[SharedNote]) -> String {
+    let (min_x, min_y, width, height) = if notes.is_empty() {
+        (0.0, 0.0, EMPTY_WIDTH, EMPTY_HEIGHT)
+    } else {
+        let min_x = notes.iter().map(|n| n.x).fold(f64::INFINITY, f64::min) - PADDING;
+        let min_y = notes.iter().map(|n| n.y).fold(f64::INFINITY, f64::min) - PADDING;
+        let max_x = notes.iter().map(|n| n.x + n.width).fold(f64::NEG_INFINITY, f64::max) + PADDING;
+        let max_y = notes.iter().map(|n| n.y + n.height).fold(f64::NEG_INFINITY, f64::max) + PADDING;
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    };
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="{x} {y} {w} {h}" font-family="{font}">"#,
+        x = min_x,
+        y = min_y,
+        w = width,
+        h = height,
+        font = FONT_FAMILY,
+    );
+    let _ = write!(svg, "<title>{}</title>", escape(group_name));
+    let _ = write!(
+        svg,
+        r##"<rect x="{min_x}" y="{min_y}" width="{width}" height="{height}" fill="#F8FAFC"/>"##
+    );
+
+    for note in notes {
+        let _ = write!(
+            svg,
+            r#"<clipPath id="clip-{id}"><rect x="{x}" y="{y}" width="{w}" height="{h}"/></clipPath>"#,
+            id = note.id,
+            x = note.x,
+            y = note.y,
+            w = note.width,
+            h = note.height,
+        );
+        let _ = write!(
+            svg,
+            r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="4" fill="#000000" fill-opacity="0.12"/>"##,
+            x = note.x + 3.0,
+            y = note.y + 3.0,
+            w = note.width,
+            h = note.height,
+        );
+        let _ = write!(
+            svg,
+            r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="4" fill="{fill}" stroke="#000000" stroke-opacity="0.15"/>"##,
+            x = note.x,
+            y = note.y,
+            w = note.width,
+            h = note.height,
+            fill = escape(&note.color),
+        );
+
+        let text_width = (note.width - NOTE_PADDING * 2.0).max(0.0);
+        let mut lines: Vec<(f64, &str, String)> = Vec::new();
+        if let Some(title) = note.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            for line in wrap(title, text_width, TITLE_SIZE) {
+                lines.push((TITLE_SIZE, "bold", line));
+            }
+        }
+        if let Some(content) = note.content.as_deref().filter(|c| !c.trim().is_empty()) {
+            for line in wrap(content, text_width, CONTENT_SIZE) {
+                lines.push((CONTENT_SIZE, "normal", line));
+            }
+        }
+
+        let _ = write!(svg, r##"<g clip-path="url(#clip-{})" fill="#1F2937">"##, note.id);
+        let mut baseline = note.y + NOTE_PADDING;
+        for (size, weight, line) in lines {
+            baseline += size * LINE_GAP;
+            if baseline > note.y + note.height {
+                break;
+            }
+            let _ = write!(
+                svg,
+                r#"<text x="{x}" y="{y}" font-size="{size}" font-weight="{weight}" xml:space="preserve">{text}</text>"#,
+                x = note.x + NOTE_PADDING,
+                y = baseline - size * (LINE_GAP - 1.0),
+                text = escape(&line),
+            );
+        }
+        svg.push_str("</g>");
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+pub fn render_png(svg: &str) -> Result<Vec<u8>> {
+    let options = usvg::Options {
+        fontdb: font_database(),
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).context("failed to parse snapshot SVG")?;
+    let size = tree.size();
+    let scale = (MAX_PNG_SIDE / size.width() as f64)
+        .min(MAX_PNG_SIDE / size.height() as f64)
+        .min(1.0) as f32;
+    let mut pixmap = tiny_skia::Pixmap::new(
+        ((size.width() * scale).ceil() as u32).max(1),
+        ((size.height() * scale).ceil() as u32).max(1),
+    )
+    .context("failed to allocate snapshot pixmap")?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    pixmap.encode_png().context("failed to encode snapshot PNG")
+}
+
+/// Loading system fonts takes a noticeable amount of time, so it is done once.
+fn font_database() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+/// Greedy line wrapping using an estimated glyph width: full-width characters
+/// take one em, everything else roughly half. Latin words are kept together
+/// when they fit on a line.
+fn wrap(text: &str, max_width: f64, font_size: f64) -> Vec<String> {
+    let mut out = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0.0;
+        for token in tokens(paragraph) {
+            let token_width: f64 = token.chars().map(|c| glyph_width(c, font_size)).sum();
+            if line_width + token_width > max_width && !line.is_empty() {
+                out.push(std::mem::take(&mut line).trim_end().to_string());
+                line_width = 0.0;
+                if token.trim().is_empty() {
+                    continue;
+                }
+            }
+            if line_width + token_width <= max_width {
+                line.push_str(token);
+                line_width += token_width;
+                continue;
+            }
+            // A single word wider than the note is broken between characters.
+            for c in token.chars() {
+                let w = glyph_width(c, font_size);
+                if line_width + w > max_width && !line.is_empty() {
+                    out.push(std::mem::take(&mut line));
+                    line_width = 0.0;
+                }
+                line.push(c);
+                line_width += w;
+            }
+        }
+        out.push(line.trim_end().to_string());
+    }
+    out
+}
+
+/// Splits into runs of ASCII word characters, single whitespace characters
+/// and single non-ASCII characters (which may break anywhere).
+fn tokens(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        if c.is_ascii() && !c.is_ascii_whitespace() {
+            start.get_or_insert(i);
+            continue;
+        }
+        if let Some(s) = start.take() {
+            out.push(&text[s..i]);
+        }
+        out.push(&text[i..i + c.len_utf8()]);
+    }
+    if let Some(s) = start {
+        out.push(&text[s..]);
+    }
+    out
+}
+
+fn glyph_width(c: char, font_size: f64) -> f64 {
+    if c.is_ascii() {
+        font_size * 0.55
+    } else {
+        font_size
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if c.is_control() && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
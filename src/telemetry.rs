@@ -0,0 +1,197 @@
+//! Logging and per-request tracing. Every request gets an ID, taken from a
+//! well-formed incoming `X-Request-Id` or generated, which is echoed in the
+//! response header and in error bodies, and carried by a `request` span
+//! together with the account, group and note IDs it touches.
+//!
+//! With the `otlp` cargo feature and `otlp.endpoint` set, spans (requests,
+//! database queries, LLM calls) are also exported to an OpenTelemetry
+//! collector over OTLP/HTTP.
+
+use crate::config::{Config, LogFormat};
+use anyhow::Result;
+use axum::extract::{MatchedPath, Request};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::field::Empty;
+use tracing::{Level, Span};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied request ID that is kept as is.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Flushes exported spans when dropped; keep it alive for the whole run.
+#[must_use = "dropping the guard stops span export"]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+/// Installs the global subscriber. Logs go to stderr so command output on
+/// stdout stays pipeable; `RUST_LOG` sets the filter (default `info`).
+pub fn init(config: &Config) -> Result<Telemetry> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    let json = config.log.format == LogFormat::Json;
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with((!json).then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr)))
+        .with(json.then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(false)
+                .with_writer(std::io::stderr)
+        }));
+
+    #[cfg(feature = "otlp")]
+    {
+        let otlp = &config.otlp;
+        let provider = otlp.endpoint.as_deref().map(|e| otlp_provider(e, &otlp.service_name)).transpose()?;
+        registry.with(provider.as_ref().map(otlp_layer)).init();
+        if let Some(endpoint) = &otlp.endpoint {
+            tracing::info!("exporting spans to {endpoint}");
+        }
+        Ok(Telemetry { provider })
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        Ok(Telemetry {})
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush spans: {e}");
+            }
+        }
+    }
+}
+
+/// Batches spans and posts them to `endpoint` (an OTLP/HTTP traces URL) on
+/// the Tokio runtime.
+#[cfg(feature = "otlp")]
+pub fn otlp_provider(endpoint: &str, service_name: &str) -> Result<opentelemetry_sdk::trace::TracerProvider> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let resource = opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new(
+        "service.name",
+        service_name.to_string(),
+    )]);
+    Ok(opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(resource)
+        .build())
+}
+
+/// The subscriber layer feeding `provider`.
+#[cfg(feature = "otlp")]
+pub fn otlp_layer<S>(
+    provider: &opentelemetry_sdk::trace::TracerProvider,
+) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+}
+
+/// Wraps `router` with request IDs and a span plus a completion log line per
+/// request.
+pub fn apply(router: Router) -> Router {
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(make_span)
+        .on_request(())
+        .on_response(DefaultOnResponse::new().level(Level::INFO).latency_unit(LatencyUnit::Millis));
+    router.layer(trace).layer(middleware::from_fn(request_id))
+}
+
+/// ID of the request being handled by the current task, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Records the account acting in the current request when it is not part of
+/// the path (e.g. `created_by` in a body).
+pub fn record_account(account_id: i64) {
+    Span::current().record("account_id", account_id);
+}
+
+async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let value = HeaderValue::from_str(&id).expect("request ID is a valid header value");
+    req.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    let mut res = REQUEST_ID.scope(id, next.run(req)).await;
+    res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    res
+}
+
+/// Client IDs end up in logs, so only short tokens of `[A-Za-z0-9._-]` are
+/// trusted; anything else is replaced.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+fn make_span(req: &Request) -> Span {
+    let request_id = req.headers().get(&REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let route = req.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.uri().path(),
+        route = route.unwrap_or(""),
+        request_id,
+        account_id = Empty,
+        group_id = Empty,
+        note_id = Empty,
+    );
+    if let Some(route) = route {
+        record_path_ids(&span, route, req.uri().path());
+    }
+    span
+}
+
+/// Fills the ID fields from `:id` segments of the route template, named by
+/// the collection segment before them (`/api/groups/:id` -> `group_id`).
+fn record_path_ids(span: &Span, route: &str, path: &str) {
+    let mut collection = "";
+    for (pattern, value) in route.split('/').zip(path.split('/')) {
+        let field = match collection {
+            "accounts" => "account_id",
+            "groups" => "group_id",
+            "notes" => "note_id",
+            _ => "",
+        };
+        if pattern.starts_with(':') && !field.is_empty() {
+            if let Ok(id) = value.parse::<i64>() {
+                span.record(field, id);
+            }
+        }
+        collection = pattern;
+    }
+}
@@ -0,0 +1,1382 @@
+//! Drives every route of `api::routes` in-process with `oneshot`, on a fresh
+//! database per test (see `common`).
+
+mod common;
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use common::TestDb;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use sqlx::Connection;
+use tower::ServiceExt;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+struct TestApp {
+    router: Router,
+    db: TestDb,
+    backups: tempfile::TempDir,
+}
+
+struct Reply {
+    status: StatusCode,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|e| panic!("{e}: {}", String::from_utf8_lossy(&self.body)))
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8(self.body.clone()).unwrap()
+    }
+
+    #[track_caller]
+    fn assert_error(&self, status: StatusCode, code: &str) {
+        assert_eq!(self.status, status, "{}", String::from_utf8_lossy(&self.body));
+        let body = self.json();
+        assert_eq!(body["code"], code);
+        assert!(body["message"].as_str().is_some_and(|m| !m.is_empty()));
+    }
+}
+
+impl TestApp {
+    async fn new() -> Self {
+        Self::with_admin_token(Some(ADMIN_TOKEN)).await
+    }
+
+    async fn with_admin_token(token: Option<&str>) -> Self {
+        let db = TestDb::new().await;
+        let backups = tempfile::tempdir().unwrap();
+        let router = api::routes(AppState {
+            db: db.clone(),
+            database_url: db.url.clone(),
+            admin_token: token.map(str::to_string),
+            backups: BackupConfig { dir: backups.path().to_path_buf(), interval: None, retention: 2 },
+            metrics: Default::default(),
+            rate_limiter: Default::default(),
+            limits: Default::default(),
+            mail: Default::default(),
+            llm_endpoint: None,
+        });
+        Self { router, db, backups }
+    }
+
+    async fn send(&self, request: Request<Body>) -> Reply {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec();
+        Reply { status, content_type, body }
+    }
+
+    async fn call(&self, method: Method, uri: &str, body: Option<Value>) -> Reply {
+        self.request(method, uri, None, body).await
+    }
+
+    /// A call with `token` as the bearer, for an account's own routes.
+    async fn call_as(&self, method: Method, uri: &str, token: &str, body: Value) -> Reply {
+        self.request(method, uri, Some(token), Some(body)).await
+    }
+
+    async fn request(&self, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> Reply {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        };
+        self.send(request.unwrap()).await
+    }
+
+    async fn get(&self, uri: &str) -> Reply {
+        self.call(Method::GET, uri, None).await
+    }
+
+    async fn post(&self, uri: &str, body: Value) -> Reply {
+        self.call(Method::POST, uri, Some(body)).await
+    }
+
+    async fn patch(&self, uri: &str, body: Value) -> Reply {
+        self.call(Method::PATCH, uri, Some(body)).await
+    }
+
+    async fn delete(&self, uri: &str) -> Reply {
+        self.call(Method::DELETE, uri, None).await
+    }
+
+    async fn admin(&self, method: Method, uri: &str, token: Option<&str>) -> Reply {
+        self.request(method, uri, token, None).await
+    }
+
+    /// `GET` on the admin-only account directory.
+    async fn directory(&self, uri: &str) -> Reply {
+        self.admin(Method::GET, uri, Some(ADMIN_TOKEN)).await
+    }
+
+    async fn session(&self, method: Method, uri: &str, token: &str) -> Reply {
+        self.admin(method, uri, Some(token)).await
+    }
+
+    async fn login(&self, name: &str) -> String {
+        self.login_with(name, "secret123").await
+    }
+
+    async fn login_with(&self, name: &str, password: &str) -> String {
+        let reply = self.post("/api/auth/login", json!({ "email": format!("{name}@example.com"), "password": password })).await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+        reply.json()["token"].as_str().unwrap().to_string()
+    }
+
+    // Fixtures ---------------------------------------------------------
+
+    async fn account(&self, name: &str) -> i64 {
+        let reply = self
+            .post(
+                "/api/accounts",
+                json!({ "name": name, "email": format!("{name}@example.com"), "password": "secret123" }),
+            )
+            .await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+        reply.json()["id"].as_i64().unwrap()
+    }
+
+    async fn group(&self, owner: i64, name: &str) -> i64 {
+        let reply = self.post("/api/groups", json!({ "group_name": name, "created_by": owner })).await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+        reply.json()["id"].as_i64().unwrap()
+    }
+
+    async fn join(&self, group: i64, user: i64) {
+        let reply = self.post(&format!("/api/groups/{group}/users"), json!({ "user_id": user })).await;
+        assert_eq!(reply.status, StatusCode::NO_CONTENT, "{}", reply.text());
+    }
+
+    async fn note(&self, group: i64, body: Value) -> i64 {
+        let reply = self.post(&format!("/api/groups/{group}/notes"), body).await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+        reply.json()["id"].as_i64().unwrap()
+    }
+
+    async fn note_ids(&self, group: i64) -> Vec<i64> {
+        let reply = self.get(&format!("/api/groups/{group}/notes")).await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+        ids(&reply.json()["notes"])
+    }
+}
+
+fn ids(items: &Value) -> Vec<i64> {
+    items.as_array().unwrap().iter().map(|i| i["id"].as_i64().unwrap()).collect()
+}
+
+// -------------------------------------------------------------------
+// Accounts
+
+#[tokio::test]
+async fn create_account_returns_summary_without_password() {
+    let app = TestApp::new().await;
+    let reply = app
+        .post(
+            "/api/accounts",
+            json!({ "name": "  Alice ", "email": " alice@example.com ", "password": "secret123" }),
+        )
+        .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let body = reply.json();
+    assert_eq!(body["name"], "Alice");
+    assert_eq!(body["email"], "alice@example.com");
+    assert!(body.get("password_hash").is_none());
+    assert!(body["created_at"].as_str().is_some());
+}
+
+#[tokio::test]
+async fn create_account_validation_errors() {
+    let app = TestApp::new().await;
+    let cases = [
+        (json!({ "name": " ", "email": "a@example.com", "password": "secret123" }), StatusCode::BAD_REQUEST, "name_empty"),
+        (json!({ "name": "a", "email": "", "password": "secret123" }), StatusCode::BAD_REQUEST, "email_empty"),
+        (json!({ "name": "a", "email": "nope", "password": "secret123" }), StatusCode::UNPROCESSABLE_ENTITY, "email_invalid"),
+        (json!({ "name": "a", "email": "a@example.com", "password": "12345" }), StatusCode::UNPROCESSABLE_ENTITY, "password_short"),
+    ];
+    for (body, status, code) in cases {
+        let reply = app.post("/api/accounts", body).await;
+        reply.assert_error(status, code);
+        assert_eq!(reply.json()["fields"][0]["code"], code);
+    }
+
+    // Every invalid field is reported at once.
+    let reply = app.post("/api/accounts", json!({ "name": "", "email": "nope", "password": "1" })).await;
+    reply.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let fields: Vec<_> = reply.json()["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["field"].as_str().unwrap().to_string(), f["code"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(
+        fields,
+        [("name", "name_empty"), ("email", "email_invalid"), ("password", "password_short")]
+            .map(|(f, c)| (f.to_string(), c.to_string()))
+    );
+    assert!(app.directory("/api/accounts").await.json()["accounts"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn list_accounts_paginates_and_sorts() {
+    let app = TestApp::new().await;
+    for name in ["carol", "alice", "bob"] {
+        app.account(name).await;
+    }
+
+    let first = app.directory("/api/accounts?sort=name&limit=2").await.json();
+    let names: Vec<_> = first["accounts"].as_array().unwrap().iter().map(|a| a["name"].clone()).collect();
+    assert_eq!(names, ["alice", "bob"]);
+    let cursor = first["next_cursor"].as_str().unwrap();
+
+    let rest = app.directory(&format!("/api/accounts?sort=name&limit=2&cursor={cursor}")).await.json();
+    assert_eq!(rest["accounts"][0]["name"], "carol");
+    assert!(rest["next_cursor"].is_null());
+
+    let desc = app.directory("/api/accounts?sort=-name").await.json();
+    assert_eq!(desc["accounts"][0]["name"], "carol");
+
+    app.directory("/api/accounts?sort=email").await.assert_error(StatusCode::BAD_REQUEST, "invalid_sort");
+    app.directory("/api/accounts?limit=0").await.assert_error(StatusCode::BAD_REQUEST, "invalid_limit");
+    app.directory("/api/accounts?limit=501").await.assert_error(StatusCode::BAD_REQUEST, "invalid_limit");
+    app.directory("/api/accounts?cursor=zz").await.assert_error(StatusCode::BAD_REQUEST, "invalid_cursor");
+    let reply = app.directory("/api/accounts?sort=email&limit=0").await;
+    reply.assert_error(StatusCode::BAD_REQUEST, "validation_failed");
+    assert_eq!(reply.json()["fields"].as_array().unwrap().len(), 2);
+    // A cursor issued for one sort order is rejected for another.
+    app.directory(&format!("/api/accounts?sort=created_at&cursor={cursor}"))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_cursor");
+}
+
+#[tokio::test]
+async fn account_directory_is_admin_only() {
+    let app = TestApp::new().await;
+    app.account("alice").await;
+    app.get("/api/accounts").await.assert_error(StatusCode::UNAUTHORIZED, "admin_required");
+    let session = app.login("alice").await;
+    app.session(Method::GET, "/api/accounts", &session)
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "admin_token_invalid");
+    assert_eq!(app.directory("/api/accounts").await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_logout_and_me() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+
+    let reply = app.post("/api/auth/login", json!({ "email": " alice@example.com ", "password": "secret123" })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    let body = reply.json();
+    assert_eq!(body["account"]["id"], alice);
+    assert!(body["account"].get("password_hash").is_none());
+    let token = body["token"].as_str().unwrap();
+    assert_eq!(token.len(), 64);
+
+    for body in [
+        json!({ "email": "alice@example.com", "password": "wrong-pass" }),
+        json!({ "email": "nobody@example.com", "password": "secret123" }),
+    ] {
+        app.post("/api/auth/login", body).await.assert_error(StatusCode::UNAUTHORIZED, "login_failed");
+    }
+    let reply = app.post("/api/auth/login", json!({ "email": "", "password": "" })).await;
+    reply.assert_error(StatusCode::BAD_REQUEST, "validation_failed");
+    assert_eq!(reply.json()["fields"].as_array().unwrap().len(), 2);
+
+    let me = app.session(Method::GET, "/api/accounts/me", token).await;
+    assert_eq!(me.status, StatusCode::OK, "{}", me.text());
+    assert_eq!(me.json()["email"], "alice@example.com");
+    app.get("/api/accounts/me").await.assert_error(StatusCode::UNAUTHORIZED, "login_required");
+    app.session(Method::GET, "/api/accounts/me", "not-a-session")
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+
+    let reply = app.session(Method::POST, "/api/auth/logout", token).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    app.session(Method::GET, "/api/accounts/me", token)
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+    app.session(Method::POST, "/api/auth/logout", token)
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+}
+
+#[tokio::test]
+async fn password_changes_end_sessions() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let session = app.login("alice").await;
+    let reply = app
+        .call_as(
+            Method::PUT,
+            &format!("/api/accounts/{alice}/password"),
+            &session,
+            json!({ "current_password": "secret123", "new_password": "n3w-pass" }),
+        )
+        .await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT, "{}", reply.text());
+    app.session(Method::GET, "/api/accounts/me", &session)
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+
+    // Changing the address leaves them alone.
+    let session = app.login_with("alice", "n3w-pass").await;
+    let reply = app
+        .call_as(
+            Method::PATCH,
+            &format!("/api/accounts/{alice}"),
+            &session,
+            json!({ "email": "alice@example.org", "current_password": "n3w-pass" }),
+        )
+        .await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    let me = app.session(Method::GET, "/api/accounts/me", &session).await;
+    assert_eq!(me.json()["email"], "alice@example.org");
+}
+
+async fn lookup(app: &TestApp, session: &str, query: &str) -> Reply {
+    app.session(Method::GET, &format!("/api/accounts/lookup?{query}"), session).await
+}
+
+#[tokio::test]
+async fn lookup_finds_only_exact_addresses_and_group_peers() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let alan = app.account("alan").await;
+    let albert = app.account("albert").await;
+    let bob = app.account("bob").await;
+    let group = app.group(alice, "board").await;
+    app.join(group, alan).await;
+    app.join(group, bob).await;
+    // Albert shares a group with Bob, not with Alice.
+    let other = app.group(bob, "other").await;
+    app.join(other, albert).await;
+    let session = app.login("alice").await;
+
+    let reply = lookup(&app, &session, "email=albert@example.com").await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!(reply.json(), json!({ "users": [{ "id": albert, "name": "albert" }] }));
+    let reply = lookup(&app, &session, "email=albert").await;
+    reply.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "email_invalid");
+    assert_eq!(lookup(&app, &session, "email=carol@example.com").await.json()["users"], json!([]));
+
+    assert_eq!(ids(&lookup(&app, &session, "q=al").await.json()["users"]), [alan]);
+    assert_eq!(ids(&lookup(&app, &session, "q=B").await.json()["users"]), [bob]);
+    // `%` and `_` match themselves.
+    assert_eq!(lookup(&app, &session, "q=%25").await.json()["users"], json!([]));
+    assert_eq!(lookup(&app, &session, "q=_").await.json()["users"], json!([]));
+
+    for query in ["", "q=%20", "email=bob@example.com&q=bob"] {
+        let reply = lookup(&app, &session, query).await;
+        reply.assert_error(StatusCode::BAD_REQUEST, "lookup_query_invalid");
+        assert_eq!(reply.json()["fields"][0]["field"], "q");
+    }
+    app.get("/api/accounts/lookup?q=al").await.assert_error(StatusCode::UNAUTHORIZED, "login_required");
+}
+
+#[tokio::test]
+async fn list_groups_for_user_reports_roles() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let bob = app.account("bob").await;
+    let owned = app.group(alice, "alice's").await;
+    let joined = app.group(bob, "bob's").await;
+    app.join(joined, alice).await;
+
+    let groups = app.get(&format!("/api/accounts/{alice}/groups")).await.json();
+    let mut roles: Vec<_> = groups["groups"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|g| (g["id"].as_i64().unwrap(), g["role"].as_str().unwrap().to_string()))
+        .collect();
+    roles.sort();
+    assert_eq!(roles, [(owned, "owner".to_string()), (joined, "member".to_string())]);
+
+    app.get("/api/accounts/0/groups").await.assert_error(StatusCode::BAD_REQUEST, "invalid_user_id");
+    app.get("/api/accounts/999/groups").await.assert_error(StatusCode::NOT_FOUND, "account_not_found");
+}
+
+#[tokio::test]
+async fn update_account_profile() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    app.account("bob").await;
+    app.db.mark_email_verified(alice).await.unwrap();
+    let uri = format!("/api/accounts/{alice}");
+    let session = app.login("alice").await;
+
+    let reply = app.call_as(Method::PATCH, &uri, &session, json!({ "name": " Alice Liddell " })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!((reply.json()["name"].as_str(), reply.json()["email_verified"].as_bool()), (Some("Alice Liddell"), Some(true)));
+    // Repeating the current address needs no password and changes nothing.
+    let reply = app.call_as(Method::PATCH, &uri, &session, json!({ "email": "alice@example.com" })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!(reply.json()["email_verified"], true);
+
+    app.call_as(Method::PATCH, &uri, &session, json!({ "name": "", "email": "nope" }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    app.call_as(Method::PATCH, &uri, &session, json!({ "email": "alice@example.org" }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "password_required");
+    let reply = app.call_as(Method::PATCH, &uri, &session, json!({ "email": "alice@example.org", "current_password": "wrong-pass" })).await;
+    reply.assert_error(StatusCode::FORBIDDEN, "password_incorrect");
+    assert_eq!(reply.json()["fields"][0]["field"], "current_password");
+    let reply = app.call_as(Method::PATCH, &uri, &session, json!({ "email": "bob@example.com", "current_password": "secret123" })).await;
+    reply.assert_error(StatusCode::CONFLICT, "email_taken");
+    assert_eq!(reply.json()["fields"][0]["field"], "email");
+
+    let reply = app.call_as(Method::PATCH, &uri, &session, json!({ "email": "alice@example.org", "current_password": "secret123" })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!((reply.json()["email"].as_str(), reply.json()["email_verified"].as_bool()), (Some("alice@example.org"), Some(false)));
+
+    app.call_as(Method::PATCH, "/api/accounts/0", &session, json!({ "name": "x" }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_user_id");
+    app.call_as(Method::PATCH, "/api/accounts/999", ADMIN_TOKEN, json!({ "name": "x" }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "account_not_found");
+    // Sign-up checks the address too.
+    app.post("/api/accounts", json!({ "name": "eve", "email": "bob@example.com", "password": "secret123" }))
+        .await
+        .assert_error(StatusCode::CONFLICT, "email_taken");
+}
+
+#[tokio::test]
+async fn address_taken_during_a_change_is_a_conflict() {
+    let app = TestApp::new().await;
+    // Only Postgres lets a change check the address while another
+    // transaction holds it uncommitted.
+    if !app.db.is_postgres() {
+        return;
+    }
+    let alice = app.account("alice").await;
+    let bob = app.account("bob").await;
+    let mut other = sqlx::PgConnection::connect(&app.db.url).await.unwrap();
+    sqlx::query("BEGIN").execute(&mut other).await.unwrap();
+    sqlx::query("UPDATE accounts SET email = 'shared@example.com' WHERE id = $1")
+        .bind(bob)
+        .execute(&mut other)
+        .await
+        .unwrap();
+
+    let uri = format!("/api/accounts/{alice}");
+    let body = json!({ "email": "shared@example.com", "current_password": "secret123" });
+    let (reply, ()) = tokio::join!(app.call_as(Method::PATCH, &uri, ADMIN_TOKEN, body), async {
+        // Long enough for the change to pass its check and wait on bob's row.
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        sqlx::query("COMMIT").execute(&mut other).await.unwrap();
+    });
+    reply.assert_error(StatusCode::CONFLICT, "email_taken");
+}
+
+#[tokio::test]
+async fn accounts_change_only_themselves() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    app.account("bob").await;
+    let bobs_session = app.login("bob").await;
+    let original = app.db.get_account(alice).await.unwrap().unwrap();
+    let uri = format!("/api/accounts/{alice}");
+    let rename = json!({ "name": "mallory" });
+
+    app.patch(&uri, rename.clone()).await.assert_error(StatusCode::UNAUTHORIZED, "login_required");
+    app.call_as(Method::PATCH, &uri, "not-a-session", rename.clone())
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+    app.call_as(Method::PATCH, &uri, &bobs_session, rename.clone())
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "account_forbidden");
+    // Knowing the password is not enough without the session.
+    let change = json!({ "current_password": "secret123", "new_password": "n3w-pass" });
+    app.call_as(Method::PUT, &format!("{uri}/password"), &bobs_session, change.clone())
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "account_forbidden");
+    app.call(Method::PUT, &format!("{uri}/password"), Some(change))
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "login_required");
+    app.call_as(Method::DELETE, &uri, &bobs_session, json!({ "current_password": "secret123" }))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "account_forbidden");
+    let account = app.db.get_account(alice).await.unwrap().unwrap();
+    assert_eq!((account.name, account.password_hash), (original.name, original.password_hash));
+
+    // The admin may change anyone.
+    let reply = app.call_as(Method::PATCH, &uri, ADMIN_TOKEN, rename).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!(reply.json()["name"], "mallory");
+}
+
+#[tokio::test]
+async fn change_password_needs_the_current_one() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let uri = format!("/api/accounts/{alice}/password");
+    let original = app.db.get_account(alice).await.unwrap().unwrap().password_hash;
+    let session = app.login("alice").await;
+
+    app.call_as(Method::PUT, &uri, &session, json!({ "current_password": "wrong-pass", "new_password": "n3w-pass" }))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "password_incorrect");
+    let reply = app.call_as(Method::PUT, &uri, &session, json!({ "current_password": "secret123", "new_password": "short" })).await;
+    reply.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "password_short");
+    assert_eq!(reply.json()["fields"][0]["field"], "new_password");
+    assert_eq!(app.db.get_account(alice).await.unwrap().unwrap().password_hash, original);
+
+    let reply = app.call_as(Method::PUT, &uri, &session, json!({ "current_password": "secret123", "new_password": "n3w-pass" })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT, "{}", reply.text());
+    let session = app.login_with("alice", "n3w-pass").await;
+    app.call_as(Method::PUT, &uri, &session, json!({ "current_password": "secret123", "new_password": "again123" }))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "password_incorrect");
+}
+
+#[tokio::test]
+async fn delete_account_hands_over_its_groups() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let bob = app.account("bob").await;
+    let carol = app.account("carol").await;
+    let shared = app.group(alice, "shared").await;
+    app.join(shared, bob).await;
+    let co_owned = app.group(alice, "co-owned").await;
+    app.join(co_owned, bob).await;
+    let reply = app.post(&format!("/api/groups/{co_owned}/users"), json!({ "user_id": carol, "role": "owner" })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    let solo = app.group(alice, "solo").await;
+    app.note(solo, json!({ "x": 0, "y": 0 })).await;
+    let kept = app.note(shared, json!({ "x": 0, "y": 0, "created_by": alice })).await;
+    let bobs = app.group(bob, "bob's").await;
+    app.join(bobs, alice).await;
+    let uri = format!("/api/accounts/{alice}");
+    let session = app.login("alice").await;
+
+    app.call_as(Method::DELETE, &uri, &session, json!({ "current_password": "" }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "password_required");
+    app.call_as(Method::DELETE, &uri, &session, json!({ "current_password": "wrong-pass" }))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "password_incorrect");
+
+    let reply = app.call_as(Method::DELETE, &uri, &session, json!({ "current_password": "secret123" })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!(reply.json(), json!({ "transferred_groups": [shared, co_owned], "deleted_groups": [solo] }));
+
+    // An existing owner takes precedence over a longer-standing member.
+    assert_eq!(app.get(&format!("/api/groups/{shared}")).await.json()["created_by"], bob);
+    assert_eq!(app.get(&format!("/api/groups/{co_owned}")).await.json()["created_by"], carol);
+    let roles = app.get(&format!("/api/accounts/{bob}/groups")).await.json();
+    let shared_role = roles["groups"].as_array().unwrap().iter().find(|g| g["id"] == shared).unwrap()["role"].clone();
+    assert_eq!(shared_role, "owner");
+    app.get(&format!("/api/groups/{solo}")).await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+    assert_eq!(app.db.count_notes().await.unwrap(), 1);
+    let notes = app.get(&format!("/api/groups/{shared}/notes")).await.json();
+    assert_eq!((notes["notes"][0]["id"].as_i64(), notes["notes"][0]["created_by"].is_null()), (Some(kept), true));
+    let members = app.get(&format!("/api/groups/{bobs}/users")).await.json();
+    assert_eq!(members["members"].as_array().unwrap().len(), 1);
+
+    // Its sessions went with it.
+    app.call_as(Method::DELETE, &uri, &session, json!({ "current_password": "secret123" }))
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "session_invalid");
+    app.call_as(Method::DELETE, &uri, ADMIN_TOKEN, json!({ "current_password": "secret123" }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "account_not_found");
+    app.get(&format!("/api/accounts/{alice}/groups")).await.assert_error(StatusCode::NOT_FOUND, "account_not_found");
+}
+
+// -------------------------------------------------------------------
+// Groups and members
+
+#[tokio::test]
+async fn create_and_get_group() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+
+    let created = app.post("/api/groups", json!({ "group_name": " planning ", "created_by": alice })).await;
+    assert_eq!(created.status, StatusCode::OK);
+    let created = created.json();
+    assert_eq!(created["group_name"], "planning");
+    assert_eq!(created["created_by"], alice);
+
+    let fetched = app.get(&format!("/api/groups/{}", created["id"])).await;
+    assert_eq!(fetched.json(), created);
+
+    app.post("/api/groups", json!({ "group_name": "", "created_by": alice }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "group_name_empty");
+    app.post("/api/groups", json!({ "group_name": "x", "created_by": 0 }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "created_by_invalid");
+    app.post("/api/groups", json!({ "group_name": "x", "created_by": 999 }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "account_not_found");
+    app.get("/api/groups/0").await.assert_error(StatusCode::BAD_REQUEST, "invalid_id");
+    app.get("/api/groups/999").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn group_members() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let bob = app.account("bob").await;
+    let carol = app.account("carol").await;
+    let group = app.group(alice, "team").await;
+    let uri = format!("/api/groups/{group}/users");
+
+    app.join(group, bob).await;
+    // Joining twice keeps the original membership.
+    app.post(&uri, json!({ "user_id": bob, "role": "owner" })).await;
+    let reply = app.post(&uri, json!({ "user_id": carol, "role": "owner" })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+
+    let members = app.get(&format!("{uri}?sort=user_id")).await.json();
+    let roles: Vec<_> = members["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["user_id"].as_i64().unwrap(), m["role"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(
+        roles,
+        [(alice, "owner".to_string()), (bob, "member".to_string()), (carol, "owner".to_string())]
+    );
+
+    let page = app.get(&format!("{uri}?limit=2")).await.json();
+    assert_eq!(page["members"].as_array().unwrap().len(), 2);
+    assert!(page["next_cursor"].is_string());
+
+    app.post(&uri, json!({ "user_id": bob, "role": "admin" }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "invalid_role");
+    app.post(&uri, json!({ "user_id": 0 })).await.assert_error(StatusCode::BAD_REQUEST, "invalid_user_id");
+    app.post(&uri, json!({ "user_id": 999 })).await.assert_error(StatusCode::NOT_FOUND, "account_not_found");
+    app.post("/api/groups/999/users", json!({ "user_id": bob }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "group_not_found");
+    app.get("/api/groups/999/users").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+    app.get(&format!("{uri}?sort=name")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_sort");
+}
+
+#[tokio::test]
+async fn group_routes_reject_non_positive_ids() {
+    let app = TestApp::new().await;
+    let gets = [
+        "/api/groups/0/users",
+        "/api/groups/0/notes",
+        "/api/groups/0/notes/changes",
+        "/api/groups/0/export",
+        "/api/groups/-1/snapshot.svg",
+        "/api/groups/-1/snapshot.png",
+    ];
+    for uri in gets {
+        app.get(uri).await.assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+    }
+    app.post("/api/groups/0/users", json!({ "user_id": 1 }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+    app.post("/api/groups/0/notes", json!({ "x": 0, "y": 0 }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+    app.post("/api/groups/0/notes/batch", json!({ "ops": [] }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+    app.delete("/api/groups/0/notes").await.assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+    app.post("/api/groups/0/import", json!([]))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_group_id");
+
+    app.patch("/api/notes/0", json!({})).await.assert_error(StatusCode::BAD_REQUEST, "invalid_note_id");
+    app.patch("/api/notes/0/position", json!({ "x": 0, "y": 0 }))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_note_id");
+    app.delete("/api/notes/0").await.assert_error(StatusCode::BAD_REQUEST, "invalid_note_id");
+}
+
+// -------------------------------------------------------------------
+// Notes
+
+#[tokio::test]
+async fn create_note_applies_defaults() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+
+    let plain = app.note(group, json!({ "x": 10, "y": 20 })).await;
+    let styled = app
+        .note(
+            group,
+            json!({
+                "title": "t", "content": "c", "color": "blue", "x": 1, "y": 2,
+                "width": 300, "height": 100, "z_index": 3, "created_by": alice, "can_edit": true
+            }),
+        )
+        .await;
+
+    let notes = app.get(&format!("/api/groups/{group}/notes")).await.json();
+    let notes = notes["notes"].as_array().unwrap();
+    assert_eq!(ids(&json!(notes)), [plain, styled]);
+    assert_eq!(notes[0]["color"], "#FFFF88");
+    assert_eq!(notes[0]["width"], 200.0);
+    assert_eq!(notes[0]["height"], 150.0);
+    assert_eq!(notes[0]["can_edit"], false);
+    assert!(notes[0]["created_by"].is_null());
+    assert_eq!(notes[1]["color"], "#BFDBFE");
+    assert_eq!(notes[1]["z_index"], 3);
+    assert_eq!(notes[1]["created_by"], alice);
+    assert_eq!(notes[1]["can_edit"], true);
+}
+
+#[tokio::test]
+async fn create_note_requires_member_author() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let outsider = app.account("outsider").await;
+    let group = app.group(alice, "board").await;
+    let uri = format!("/api/groups/{group}/notes");
+
+    app.post(&uri, json!({ "x": 0, "y": 0, "created_by": outsider }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "not_member");
+    app.post(&uri, json!({ "x": 0, "y": 0, "created_by": 999 }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "account_not_found");
+    app.post("/api/groups/999/notes", json!({ "x": 0, "y": 0 }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "group_not_found");
+    assert!(app.note_ids(group).await.is_empty());
+}
+
+#[tokio::test]
+async fn update_and_delete_note() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    let note = app.note(group, json!({ "title": "old", "x": 0, "y": 0 })).await;
+
+    let reply = app
+        .patch(&format!("/api/notes/{note}/position"), json!({ "x": 5, "y": 6, "width": 70, "z_index": 9 }))
+        .await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    let reply = app
+        .patch(&format!("/api/notes/{note}"), json!({ "title": "new", "content": "body", "color": "#00ff00" }))
+        .await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+
+    let stored = &app.get(&format!("/api/groups/{group}/notes")).await.json()["notes"][0];
+    assert_eq!((stored["x"].as_f64(), stored["y"].as_f64()), (Some(5.0), Some(6.0)));
+    assert_eq!(stored["width"], 70.0);
+    assert_eq!(stored["height"], 150.0);
+    assert_eq!(stored["z_index"], 9);
+    assert_eq!(stored["title"], "new");
+    assert_eq!(stored["content"], "body");
+    assert_eq!(stored["color"], "#00FF00");
+
+    assert_eq!(app.delete(&format!("/api/notes/{note}")).await.status, StatusCode::NO_CONTENT);
+    app.delete(&format!("/api/notes/{note}")).await.assert_error(StatusCode::NOT_FOUND, "note_not_found");
+    app.patch(&format!("/api/notes/{note}"), json!({}))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "note_not_found");
+    app.patch(&format!("/api/notes/{note}/position"), json!({ "x": 0, "y": 0 }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "note_not_found");
+}
+
+#[tokio::test]
+async fn deleting_a_note_removes_its_shares() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    let keep = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let gone = app.note(group, json!({ "x": 1, "y": 1 })).await;
+    let cursor = app.get(&format!("/api/groups/{group}/notes/changes")).await.json()["cursor"].clone();
+
+    app.delete(&format!("/api/notes/{gone}")).await;
+
+    assert_eq!(app.note_ids(group).await, [keep]);
+    assert_eq!(app.db.count_notes().await.unwrap(), 1);
+    // A tombstone is only reported once no share row is left for the note.
+    let changes = app
+        .get(&format!("/api/groups/{group}/notes/changes?since={}", cursor.as_str().unwrap()))
+        .await
+        .json();
+    assert_eq!(changes["deleted"], json!([gone]));
+    // The cascaded share no longer counts towards clearing the group.
+    let cleared = app.delete(&format!("/api/groups/{group}/notes")).await.json();
+    assert_eq!(cleared["removed"], 1);
+}
+
+#[tokio::test]
+async fn clear_notes_counts_only_the_group() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let board = app.group(alice, "board").await;
+    let other = app.group(alice, "other").await;
+    for i in 0..3 {
+        app.note(board, json!({ "x": i, "y": i })).await;
+    }
+    let survivor = app.note(other, json!({ "x": 0, "y": 0 })).await;
+
+    let cleared = app.delete(&format!("/api/groups/{board}/notes")).await;
+    assert_eq!(cleared.status, StatusCode::OK);
+    assert_eq!(cleared.json()["removed"], 3);
+    assert!(app.note_ids(board).await.is_empty());
+    assert_eq!(app.note_ids(other).await, [survivor]);
+    assert_eq!(app.db.count_notes().await.unwrap(), 1);
+
+    let again = app.delete(&format!("/api/groups/{board}/notes")).await.json();
+    assert_eq!(again["removed"], 0);
+    app.delete("/api/groups/999/notes").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn list_notes_filters_and_pages() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let bob = app.account("bob").await;
+    let group = app.group(alice, "board").await;
+    app.join(group, bob).await;
+    let a = app.note(group, json!({ "title": "Budget 100%", "x": 0, "y": 0, "created_by": alice, "color": "pink" })).await;
+    let b = app.note(group, json!({ "title": "budget review", "x": 0, "y": 0, "created_by": bob })).await;
+    let c = app.note(group, json!({ "content": "misc", "x": 0, "y": 0, "created_by": bob, "z_index": -1 })).await;
+    let uri = format!("/api/groups/{group}/notes");
+
+    assert_eq!(app.note_ids(group).await, [c, a, b]);
+    let by_author = app.get(&format!("{uri}?author={bob}")).await.json();
+    assert_eq!(ids(&by_author["notes"]), [c, b]);
+    let by_color = app.get(&format!("{uri}?color=%23ffc0cb")).await.json();
+    assert_eq!(ids(&by_color["notes"]), Vec::<i64>::new());
+    let by_palette = app.get(&format!("{uri}?color=pink")).await.json();
+    assert_eq!(ids(&by_palette["notes"]), [a]);
+    let by_text = app.get(&format!("{uri}?q=BUDGET")).await.json();
+    assert_eq!(ids(&by_text["notes"]), [a, b]);
+    let literal_percent = app.get(&format!("{uri}?q=100%25")).await.json();
+    assert_eq!(ids(&literal_percent["notes"]), [a]);
+    let recent = app.get(&format!("{uri}?updated_since=2000-01-01")).await.json();
+    assert_eq!(recent["notes"].as_array().unwrap().len(), 3);
+    let future = app.get(&format!("{uri}?updated_since=2999-01-01T00:00:00Z")).await.json();
+    assert!(future["notes"].as_array().unwrap().is_empty());
+
+    let first = app.get(&format!("{uri}?sort=-created_at&limit=2")).await.json();
+    assert_eq!(ids(&first["notes"]), [c, b]);
+    let next = app
+        .get(&format!("{uri}?sort=-created_at&limit=2&cursor={}", first["next_cursor"].as_str().unwrap()))
+        .await
+        .json();
+    assert_eq!(ids(&next["notes"]), [a]);
+
+    app.get(&format!("{uri}?color=mauve")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_color");
+    app.get(&format!("{uri}?updated_since=yesterday"))
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, "invalid_updated_since");
+    app.get(&format!("{uri}?sort=author")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_sort");
+    app.get("/api/groups/999/notes").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn note_changes_since_cursor() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    let other = app.group(alice, "other").await;
+    let moved = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let untouched = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let uri = format!("/api/groups/{group}/notes/changes");
+
+    let full = app.get(&uri).await.json();
+    assert_eq!(ids(&full["notes"]), [moved, untouched]);
+    assert_eq!(full["deleted"], json!([]));
+    let cursor = full["cursor"].as_str().unwrap().to_string();
+
+    app.patch(&format!("/api/notes/{moved}/position"), json!({ "x": 9, "y": 9 })).await;
+    let created = app.note(group, json!({ "x": 1, "y": 1 })).await;
+    app.note(other, json!({ "x": 1, "y": 1 })).await;
+    app.delete(&format!("/api/notes/{untouched}")).await;
+
+    let delta = app.get(&format!("{uri}?since={cursor}")).await.json();
+    assert_eq!(ids(&delta["notes"]), [moved, created]);
+    assert_eq!(delta["deleted"], json!([untouched]));
+    let next = delta["cursor"].as_str().unwrap();
+    assert!(next.parse::<i64>().unwrap() > cursor.parse::<i64>().unwrap());
+
+    let quiet = app.get(&format!("{uri}?since={next}")).await.json();
+    assert!(quiet["notes"].as_array().unwrap().is_empty());
+    assert_eq!(quiet["deleted"], json!([]));
+
+    app.get(&format!("{uri}?since=abc")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_cursor");
+    app.get(&format!("{uri}?since=-1")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_cursor");
+    app.get(&format!("{uri}?since=99999")).await.assert_error(StatusCode::GONE, "resync_required");
+    app.get("/api/groups/999/notes/changes").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn batch_applies_all_or_nothing() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let outsider = app.account("outsider").await;
+    let group = app.group(alice, "board").await;
+    let other = app.group(alice, "other").await;
+    let a = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let b = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let foreign = app.note(other, json!({ "x": 0, "y": 0 })).await;
+    let uri = format!("/api/groups/{group}/notes/batch");
+
+    let ok = app
+        .post(
+            &uri,
+            json!({ "ops": [
+                { "op": "create", "x": 1, "y": 1, "created_by": alice },
+                { "op": "update_position", "note_id": a, "x": 40, "y": 50 },
+                { "op": "update_content", "note_id": a, "title": "moved" },
+                { "op": "delete", "note_id": b },
+            ]}),
+        )
+        .await;
+    assert_eq!(ok.status, StatusCode::OK);
+    let report = ok.json();
+    assert_eq!(report["applied"], true);
+    let created = report["results"][0]["note_id"].as_i64().unwrap();
+    assert_eq!(report["results"][3]["note_id"], b);
+    assert_eq!(app.note_ids(group).await, [a, created]);
+
+    // A note outside the group fails the batch and rolls back the create.
+    let rejected = app
+        .post(
+            &uri,
+            json!({ "ops": [
+                { "op": "create", "x": 1, "y": 1 },
+                { "op": "delete", "note_id": foreign },
+            ]}),
+        )
+        .await;
+    assert_eq!(rejected.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let report = rejected.json();
+    assert_eq!(report["applied"], false);
+    assert_eq!(report["results"][0]["status"], "skipped");
+    assert_eq!(report["results"][1]["error"]["code"], "note_not_found");
+    assert_eq!(app.note_ids(group).await, [a, created]);
+    assert_eq!(app.note_ids(other).await, [foreign]);
+
+    let invalid = app
+        .post(
+            &uri,
+            json!({ "ops": [
+                { "op": "delete", "note_id": 0 },
+                { "op": "create", "x": 0, "y": 0, "created_by": outsider },
+                { "op": "update_position", "note_id": a, "x": 0, "y": 0 },
+            ]}),
+        )
+        .await
+        .json();
+    let statuses: Vec<_> = invalid["results"].as_array().unwrap().iter().map(|r| r["status"].clone()).collect();
+    assert_eq!(statuses, ["error", "error", "skipped"]);
+    assert_eq!(invalid["results"][0]["error"]["code"], "invalid_note_id");
+    assert_eq!(invalid["results"][1]["error"]["code"], "not_member");
+
+    app.post(&uri, json!({ "ops": [] })).await.assert_error(StatusCode::BAD_REQUEST, "batch_empty");
+    let too_many: Vec<_> = (0..501).map(|_| json!({ "op": "delete", "note_id": a })).collect();
+    app.post(&uri, json!({ "ops": too_many })).await.assert_error(StatusCode::BAD_REQUEST, "batch_too_large");
+    app.post("/api/groups/999/notes/batch", json!({ "ops": [{ "op": "delete", "note_id": a }] }))
+        .await
+        .assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+// -------------------------------------------------------------------
+// Export, import and snapshots
+
+#[tokio::test]
+async fn export_formats() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    app.note(group, json!({ "title": "hello, \"world\"", "x": 0, "y": 0, "created_by": alice })).await;
+    app.note(group, json!({ "title": " ", "x": 0, "y": 10 })).await;
+    let uri = format!("/api/groups/{group}/export");
+
+    let exported = app.get(&uri).await;
+    assert_eq!(exported.content_type, "application/json");
+    let body = exported.json();
+    assert_eq!(body["group_name"], "board");
+    assert_eq!(body["notes"][0]["author_name"], "alice");
+
+    let csv = app.get(&format!("{uri}?format=csv")).await;
+    assert!(csv.content_type.starts_with("text/csv"));
+    assert!(csv.text().contains("\"hello, \"\"world\"\"\""));
+
+    let md = app.get(&format!("{uri}?format=md")).await;
+    assert!(md.content_type.starts_with("text/markdown"));
+    assert!(md.text().starts_with("# board"));
+    assert!(md.text().contains("- **(無題)**\n"));
+    let empty = app.group(alice, "empty").await;
+    let request = Request::get(format!("/api/groups/{empty}/export?format=md"))
+        .header(header::ACCEPT_LANGUAGE, "en")
+        .body(Body::empty());
+    assert_eq!(app.send(request.unwrap()).await.text(), "# empty\n\n_No notes_\n");
+
+    app.get(&format!("{uri}?format=xml")).await.assert_error(StatusCode::BAD_REQUEST, "invalid_format");
+    app.get("/api/groups/999/export").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn import_round_trip_and_row_errors() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let source = app.group(alice, "source").await;
+    let target = app.group(alice, "target").await;
+    app.note(source, json!({ "title": "a", "x": 1, "y": 2, "created_by": alice })).await;
+    app.note(source, json!({ "title": "b", "x": 3, "y": 4, "color": "green" })).await;
+    let exported = app.get(&format!("/api/groups/{source}/export")).await.json();
+
+    let imported = app.post(&format!("/api/groups/{target}/import"), exported).await;
+    assert_eq!(imported.status, StatusCode::OK);
+    let report = imported.json();
+    assert_eq!((report["accepted"].as_u64(), report["rejected"].as_u64()), (Some(2), Some(0)));
+    let notes = app.get(&format!("/api/groups/{target}/notes")).await.json();
+    let titles: Vec<_> = notes["notes"].as_array().unwrap().iter().map(|n| n["title"].clone()).collect();
+    assert_eq!(titles, ["a", "b"]);
+    assert_eq!(notes["notes"][0]["created_by"], alice);
+
+    // Rows are numbered by the line they start on, blank lines included.
+    let csv = "title,x,y,color\nok,1,2,\n\nbad color,1,2,mauve\n\"no\ny\",1,,\n";
+    let request = Request::post(format!("/api/groups/{target}/import"))
+        .header(header::CONTENT_TYPE, "text/csv")
+        .body(Body::from(csv))
+        .unwrap();
+    let report = app.send(request).await.json();
+    assert_eq!((report["accepted"].as_u64(), report["rejected"].as_u64()), (Some(1), Some(2)));
+    assert_eq!(report["rows"][1]["errors"][0]["code"], "invalid_color");
+    assert_eq!(report["rows"][2]["errors"][0]["code"], "geometry_missing");
+    let lines: Vec<_> = report["rows"].as_array().unwrap().iter().map(|r| r["row"].clone()).collect();
+    assert_eq!(lines, [2, 4, 5]);
+
+    let request = Request::post(format!("/api/groups/{target}/import"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{not json"))
+        .unwrap();
+    app.send(request).await.assert_error(StatusCode::BAD_REQUEST, "import_malformed");
+    app.post("/api/groups/999/import", json!([])).await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+#[tokio::test]
+async fn snapshots_render() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board <1>").await;
+    app.note(group, json!({ "title": "note", "x": 0, "y": 0 })).await;
+
+    let svg = app.get(&format!("/api/groups/{group}/snapshot.svg")).await;
+    assert_eq!(svg.status, StatusCode::OK);
+    assert_eq!(svg.content_type, "image/svg+xml");
+    assert!(svg.text().contains("<title>board &lt;1&gt;</title>"));
+
+    let png = app.get(&format!("/api/groups/{group}/snapshot.png")).await;
+    assert_eq!(png.status, StatusCode::OK);
+    assert_eq!(png.content_type, "image/png");
+    assert!(png.body.starts_with(b"\x89PNG"));
+
+    app.get("/api/groups/999/snapshot.svg").await.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+}
+
+// -------------------------------------------------------------------
+// Admin and misc
+
+#[tokio::test]
+async fn admin_backups() {
+    let app = TestApp::new().await;
+    app.admin(Method::GET, "/api/admin/backups", None)
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, "admin_required");
+    app.admin(Method::POST, "/api/admin/backups", Some("wrong"))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "admin_token_invalid");
+
+    // File-backed backups are covered by tests/client.rs.
+    app.admin(Method::POST, "/api/admin/backups", Some(ADMIN_TOKEN))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "backup_unavailable");
+    let listed = app.admin(Method::GET, "/api/admin/backups", Some(ADMIN_TOKEN)).await;
+    assert_eq!(listed.status, StatusCode::OK);
+    assert_eq!(listed.json()["backups"], json!([]));
+    assert_eq!(std::fs::read_dir(app.backups.path()).unwrap().count(), 0);
+
+    let disabled = TestApp::with_admin_token(None).await;
+    disabled
+        .admin(Method::GET, "/api/admin/backups", Some(ADMIN_TOKEN))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, "admin_disabled");
+}
+
+#[tokio::test]
+async fn debug_reports_the_database() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    app.note(group, json!({ "x": 0, "y": 0 })).await;
+
+    app.get("/api/debug").await.assert_error(StatusCode::UNAUTHORIZED, "admin_required");
+    let debug = app.admin(Method::GET, "/api/debug", Some(ADMIN_TOKEN)).await.json();
+    assert_eq!(debug["database_url"], app.db.url);
+    assert!(debug["db_file_path"].is_null());
+    assert_eq!(debug["file_exists"], false);
+    assert_eq!(debug["total_notes"], 1);
+}
+
+#[tokio::test]
+async fn health_and_readiness_probes() {
+    let app = TestApp::new().await;
+    let health = app.get("/healthz").await;
+    assert_eq!(health.status, StatusCode::OK);
+    assert_eq!(health.json()["status"], "ok");
+
+    let ready = app.get("/readyz").await;
+    assert_eq!(ready.status, StatusCode::OK);
+    let ready = ready.json();
+    assert_eq!(ready["status"], "ready");
+    let checks: Vec<_> = ready["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["name"].as_str().unwrap(), c["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(checks, [("database", "ok"), ("migrations", "ok"), ("llm", "skipped")]);
+
+    // Once the pool is gone the server reports itself unready.
+    app.db.close().await.unwrap();
+    let ready = app.get("/readyz").await;
+    assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready.json()["status"], "not_ready");
+    assert_eq!(app.get("/healthz").await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn metrics_in_prometheus_format() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    app.note(group, json!({ "x": 0, "y": 0 })).await;
+    app.directory("/api/accounts").await;
+    app.get(&format!("/api/groups/{group}/notes")).await;
+    app.get("/api/groups/999").await;
+
+    let metrics = app.get("/metrics").await;
+    assert_eq!(metrics.status, StatusCode::OK);
+    assert!(metrics.content_type.starts_with("text/plain; version=0.0.4"));
+    let text = metrics.text();
+    for line in [
+        r#"http_requests_total{method="GET",route="/api/accounts",status="200"} 1"#,
+        r#"http_requests_total{method="GET",route="/api/groups/:id/notes",status="200"} 1"#,
+        r#"http_requests_total{method="GET",route="/api/groups/:id",status="404"} 1"#,
+        r#"http_request_duration_seconds_count{method="GET",route="/api/accounts"} 1"#,
+        r#"http_request_duration_seconds_bucket{method="GET",route="/api/accounts",le="+Inf"} 1"#,
+        "accounts_total 1",
+        "groups_total 1",
+        "notes_total 1",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing {line:?} in\n{text}");
+    }
+    assert!(text.contains("# TYPE http_request_duration_seconds histogram"));
+    // The pool size depends on the backend under test.
+    let max_connections = text
+        .lines()
+        .find_map(|l| l.strip_prefix("db_pool_max_connections "))
+        .unwrap_or_else(|| panic!("missing db_pool_max_connections in\n{text}"));
+    assert!(max_connections.parse::<u32>().unwrap() > 0, "{max_connections}");
+}
+
+#[tokio::test]
+async fn openapi_document_and_docs_page() {
+    let app = TestApp::new().await;
+    let spec = app.get("/api/openapi.json").await;
+    assert_eq!(spec.status, StatusCode::OK);
+    assert!(spec.json()["openapi"].as_str().unwrap().starts_with("3."));
+    let docs = app.get("/api/docs").await;
+    assert_eq!(docs.status, StatusCode::OK);
+    assert!(docs.content_type.starts_with("text/html"));
+}
+
+#[tokio::test]
+async fn malformed_json_is_rejected() {
+    let app = TestApp::new().await;
+    let request = Request::post("/api/accounts")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"name\":"))
+        .unwrap();
+    assert_eq!(app.send(request).await.status, StatusCode::BAD_REQUEST);
+    let missing_field = app.post("/api/accounts", json!({ "name": "a" })).await;
+    assert_eq!(missing_field.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn input_lengths_and_geometry_are_limited() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    let note = app.note(group, json!({ "x": 0, "y": 0 })).await;
+    let uri = format!("/api/groups/{group}/notes");
+
+    let long_name = "n".repeat(101);
+    app.post("/api/accounts", json!({ "name": long_name, "email": "n@example.com", "password": "secret123" }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "too_long");
+    app.post("/api/groups", json!({ "group_name": long_name, "created_by": alice }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "too_long");
+
+    let reply = app.post(&uri, json!({ "title": "t".repeat(201), "x": 0, "y": 0 })).await;
+    reply.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "too_long");
+    assert!(reply.json()["message"].as_str().unwrap().starts_with("title"));
+    assert_eq!(reply.json()["fields"][0]["field"], "title");
+    let reply = app.post(&uri, json!({ "title": "t".repeat(201), "x": 1e9, "y": 0, "width": 0 })).await;
+    reply.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let fields: Vec<_> = reply.json()["fields"].as_array().unwrap().iter().map(|f| f["field"].clone()).collect();
+    assert_eq!(fields, ["title", "x", "width"]);
+    app.patch(&format!("/api/notes/{note}"), json!({ "content": "c".repeat(10_001) }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "too_long");
+    // Lengths count characters, not bytes.
+    app.note(group, json!({ "title": "あ".repeat(200), "x": 0, "y": 0 })).await;
+
+    for geometry in [
+        json!({ "x": 1e9, "y": 0 }),
+        json!({ "x": 0, "y": -100_001 }),
+        json!({ "x": 0, "y": 0, "width": -1 }),
+        json!({ "x": 0, "y": 0, "height": 0 }),
+    ] {
+        app.post(&uri, geometry.clone())
+            .await
+            .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "geometry_out_of_range");
+        app.patch(&format!("/api/notes/{note}/position"), geometry)
+            .await
+            .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "geometry_out_of_range");
+    }
+
+    let batch = app
+        .post(
+            &format!("{uri}/batch"),
+            json!({ "ops": [
+                { "op": "create", "x": 0, "y": 0, "width": 20_000 },
+                { "op": "update_content", "note_id": note, "title": "t".repeat(201) },
+            ]}),
+        )
+        .await;
+    assert_eq!(batch.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let report = batch.json();
+    assert_eq!(report["results"][0]["error"]["code"], "geometry_out_of_range");
+    assert_eq!(report["results"][1]["error"]["code"], "too_long");
+
+    let csv = format!("title,x,y,width\n{},1,2,\nok,1,2,-5\n", "t".repeat(201));
+    let request = Request::post(format!("{}/import", uri.trim_end_matches("/notes")))
+        .header(header::CONTENT_TYPE, "text/csv")
+        .body(Body::from(csv))
+        .unwrap();
+    let report = app.send(request).await.json();
+    assert_eq!(report["rejected"], 2);
+    assert_eq!(report["rows"][0]["errors"][0]["code"], "too_long");
+    assert_eq!(report["rows"][0]["errors"][0]["field"], "title");
+    assert_eq!(report["rows"][1]["errors"][0]["code"], "geometry_out_of_range");
+    assert_eq!(report["rows"][1]["errors"][0]["field"], "width");
+    assert_eq!(app.note_ids(group).await.len(), 2);
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected() {
+    let app = TestApp::new().await;
+    let alice = app.account("alice").await;
+    let group = app.group(alice, "board").await;
+    let body = json!({ "title": "t", "content": "c".repeat(3 * 1024 * 1024), "x": 0, "y": 0 });
+    let reply = app.post(&format!("/api/groups/{group}/notes"), body).await;
+    assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(app.note_ids(group).await.is_empty());
+}
+
+#[tokio::test]
+async fn messages_follow_accept_language() {
+    let app = TestApp::new().await;
+    let localized = |language: Option<&'static str>| {
+        let mut builder = Request::post("/api/accounts").header(header::CONTENT_TYPE, "application/json");
+        if let Some(language) = language {
+            builder = builder.header(header::ACCEPT_LANGUAGE, language);
+        }
+        builder.body(Body::from(json!({ "name": "", "email": "nope", "password": "secret123" }).to_string())).unwrap()
+    };
+
+    let english = app.send(localized(Some("en-US,en;q=0.9,ja;q=0.8"))).await;
+    english.assert_error(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed");
+    let body = english.json();
+    assert_eq!(body["message"], "The request has 2 invalid fields");
+    assert_eq!(body["fields"][0]["message"], "Enter a name");
+    assert_eq!(body["fields"][1]["message"], "The email address is not valid");
+
+    // Codes do not change; Japanese is the default.
+    for language in [None, Some("ja"), Some("fr, *")] {
+        let reply = app.send(localized(language)).await;
+        let body = reply.json();
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["fields"][0]["message"], "名前を入力してください");
+    }
+
+    let request = Request::get("/api/accounts?limit=0")
+        .header(header::ACCEPT_LANGUAGE, "en")
+        .header(header::AUTHORIZATION, format!("Bearer {ADMIN_TOKEN}"))
+        .body(Body::empty());
+    let reply = app.send(request.unwrap()).await;
+    assert_eq!(reply.json()["message"], "limit must be between 1 and 500");
+}
+
+#[tokio::test]
+async fn accounts_store_a_locale_preference() {
+    let app = TestApp::new().await;
+    let reply = app
+        .post(
+            "/api/accounts",
+            json!({ "name": "emma", "email": "emma@example.com", "password": "secret123", "locale": "en-GB" }),
+        )
+        .await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    assert_eq!(reply.json()["locale"], "en");
+    app.account("alice").await;
+    let accounts = app.directory("/api/accounts").await.json();
+    assert_eq!(accounts["accounts"][0]["locale"], "en");
+    assert!(accounts["accounts"][1]["locale"].is_null());
+
+    app.post(
+        "/api/accounts",
+        json!({ "name": "x", "email": "x@example.com", "password": "secret123", "locale": "fr" }),
+    )
+    .await
+    .assert_error(StatusCode::UNPROCESSABLE_ENTITY, "locale_invalid");
+
+    // Without `Accept-Language` the signed-in account's preference applies.
+    let emma = app.login("emma").await;
+    let reply = app.session(Method::GET, "/api/groups/999", &emma).await;
+    reply.assert_error(StatusCode::NOT_FOUND, "group_not_found");
+    assert_eq!(reply.json()["message"], "The group was not found");
+    let request = Request::get("/api/groups/999")
+        .header(header::ACCEPT_LANGUAGE, "ja")
+        .header(header::AUTHORIZATION, format!("Bearer {emma}"))
+        .body(Body::empty());
+    assert_eq!(app.send(request.unwrap()).await.json()["message"], "グループが見つかりません");
+    let alice = app.login("alice").await;
+    let reply = app.session(Method::GET, "/api/groups/999", &alice).await;
+    assert_eq!(reply.json()["message"], "グループが見つかりません");
+}
@@ -0,0 +1,271 @@
+//! Address verification and password reset, with mails written to a
+//! temporary directory by the file mailer and read back for their tokens.
+
+mod common;
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::config::MailConfig;
+use app::db::TokenPurpose;
+use app::mail::{AccountMailer, FileMailer};
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use common::TestDb;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+struct TestApp {
+    router: Router,
+    db: TestDb,
+    dir: tempfile::TempDir,
+}
+
+struct Reply {
+    status: StatusCode,
+    body: Value,
+}
+
+impl TestApp {
+    async fn new() -> Self {
+        let db = TestDb::new().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = MailConfig { link_base_url: "https://board.example.com/".to_string(), ..Default::default() };
+        let mailer = FileMailer::new(dir.path().join("mail"), config.from.clone());
+        let router = api::routes(AppState {
+            db: db.clone(),
+            database_url: db.url.clone(),
+            admin_token: None,
+            backups: BackupConfig { dir: dir.path().join("backups"), interval: None, retention: 2 },
+            metrics: Default::default(),
+            rate_limiter: Default::default(),
+            limits: Default::default(),
+            mail: AccountMailer::new(Arc::new(mailer), &config),
+            llm_endpoint: None,
+        });
+        Self { router, db, dir }
+    }
+
+    async fn post(&self, uri: &str, body: Value) -> Reply {
+        self.post_with_language(uri, body, None).await
+    }
+
+    async fn post_with_language(&self, uri: &str, body: Value, language: Option<&str>) -> Reply {
+        let mut builder = Request::builder().method("POST").uri(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(language) = language {
+            builder = builder.header(header::ACCEPT_LANGUAGE, language);
+        }
+        let response = self.router.clone().oneshot(builder.body(Body::from(body.to_string())).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap() };
+        Reply { status, body }
+    }
+
+    async fn account(&self, name: &str, locale: Option<&str>) -> i64 {
+        let reply = self
+            .post(
+                "/api/accounts",
+                json!({ "name": name, "email": format!("{name}@example.com"), "password": "secret123", "locale": locale }),
+            )
+            .await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.body);
+        assert_eq!(reply.body["email_verified"], false);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    fn mail_files(&self) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(self.dir.path().join("mail")) else {
+            return Vec::new();
+        };
+        let mut files: Vec<_> = entries.map(|e| e.unwrap().path()).collect();
+        files.sort();
+        files
+    }
+
+    /// Mails are sent in the background; waits for the `n`th one.
+    async fn mail(&self, n: usize) -> String {
+        for _ in 0..200 {
+            if let Some(path) = self.mail_files().get(n - 1) {
+                return std::fs::read_to_string(path).unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("mail {n} was not sent");
+    }
+
+    async fn password_hash(&self, id: i64) -> String {
+        self.db.get_account(id).await.unwrap().unwrap().password_hash
+    }
+
+    async fn is_verified(&self, id: i64) -> bool {
+        self.db.get_account(id).await.unwrap().unwrap().email_verified_at.is_some()
+    }
+}
+
+/// The token in the mail's link.
+fn token(mail: &str) -> String {
+    let start = mail.find("?token=").expect("mail has a link") + "?token=".len();
+    mail[start..].chars().take_while(char::is_ascii_hexdigit).collect()
+}
+
+fn digest(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[tokio::test]
+async fn sign_up_mails_a_verification_link() {
+    let app = TestApp::new().await;
+    let id = app.account("alice", Some("en")).await;
+
+    let mail = app.mail(1).await;
+    assert!(mail.contains("To: alice@example.com"), "{mail}");
+    assert!(mail.contains("Subject: Confirm your email address"), "{mail}");
+    assert!(mail.contains("Hello alice,"), "{mail}");
+    assert!(mail.contains("https://board.example.com/verify-email?token="), "{mail}");
+    assert!(mail.contains("expires in 24 hours"), "{mail}");
+    let token = token(&mail);
+    assert_eq!(token.len(), 64);
+
+    let reply = app.post("/api/auth/verify-email", json!({ "token": token })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT, "{}", reply.body);
+    assert!(app.is_verified(id).await);
+
+    let reply = app.post("/api/auth/verify-email", json!({ "token": token })).await;
+    assert_eq!((reply.status, reply.body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("token_invalid")));
+    assert_eq!(reply.body["fields"][0]["field"], "token");
+
+    // Verified addresses get no more mails.
+    let reply = app.post("/api/auth/verify-email/resend", json!({ "email": "alice@example.com" })).await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(app.mail_files().len(), 1);
+}
+
+#[tokio::test]
+async fn mails_follow_the_account_locale_then_the_request() {
+    let app = TestApp::new().await;
+    app.account("taro", None).await;
+    let mail = app.mail(1).await;
+    assert!(mail.contains("taro さん"), "{mail}");
+    assert!(mail.contains("24 時間"), "{mail}");
+
+    let reply = app
+        .post_with_language("/api/auth/verify-email/resend", json!({ "email": "taro@example.com" }), Some("en"))
+        .await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    assert!(app.mail(2).await.contains("Hello taro,"));
+
+    app.account("hanako", Some("ja")).await;
+    app.mail(3).await;
+    let reply = app
+        .post_with_language("/api/auth/password-reset", json!({ "email": "hanako@example.com" }), Some("en"))
+        .await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    let mail = app.mail(4).await;
+    assert!(mail.contains("https://board.example.com/reset-password?token="), "{mail}");
+    assert!(mail.contains("有効期限は 1 時間です"), "{mail}");
+}
+
+#[tokio::test]
+async fn password_reset_tokens_are_single_use() {
+    let app = TestApp::new().await;
+    let id = app.account("alice", Some("en")).await;
+    app.mail(1).await;
+    let original = app.password_hash(id).await;
+
+    // Unknown addresses look the same to the client and get no mail.
+    let reply = app.post("/api/auth/password-reset", json!({ "email": "nobody@example.com" })).await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    let reply = app.post("/api/auth/password-reset", json!({ "email": "not an address" })).await;
+    assert_eq!((reply.status, reply.body["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("email_invalid")));
+
+    for _ in 0..2 {
+        let reply = app.post("/api/auth/password-reset", json!({ "email": "alice@example.com" })).await;
+        assert_eq!(reply.status, StatusCode::ACCEPTED);
+    }
+    let first = token(&app.mail(2).await);
+    let second = token(&app.mail(3).await);
+    assert_ne!(first, second);
+
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": "", "password": "1" })).await;
+    assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(reply.body["code"], "validation_failed");
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": second, "password": "short" })).await;
+    assert_eq!(reply.body["code"], "password_short");
+    assert_eq!(app.password_hash(id).await, original);
+
+    // A verification token is no reset token.
+    let verify = token(&app.mail(1).await);
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": verify, "password": "n3w-pass" })).await;
+    assert_eq!(reply.body["code"], "token_invalid");
+
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": second, "password": "n3w-pass" })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT, "{}", reply.body);
+    assert_eq!(app.password_hash(id).await, digest("n3w-pass"));
+    assert!(app.is_verified(id).await);
+
+    // Using one token spends the other open one as well.
+    for token in [&second, &first] {
+        let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": token, "password": "again123" })).await;
+        assert_eq!((reply.status, reply.body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("token_invalid")));
+    }
+    assert_eq!(app.password_hash(id).await, digest("n3w-pass"));
+}
+
+#[tokio::test]
+async fn expired_tokens_are_rejected() {
+    let app = TestApp::new().await;
+    let id = app.account("alice", None).await;
+    app.db.create_account_token(id, TokenPurpose::ResetPassword, &digest("stale"), Duration::ZERO).await.unwrap();
+
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": "stale", "password": "n3w-pass" })).await;
+    assert_eq!((reply.status, reply.body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("token_invalid")));
+    assert_eq!(app.db.consume_account_token(TokenPurpose::ResetPassword, &digest("stale")).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn account_changes_spend_open_links() {
+    let app = TestApp::new().await;
+    let id = app.account("alice", Some("en")).await;
+    let verify = token(&app.mail(1).await);
+    let reply = app.post("/api/auth/password-reset", json!({ "email": "alice@example.com" })).await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    let reset = token(&app.mail(2).await);
+
+    // A new address gets its own link; those sent to the old one stop working.
+    let login = app.post("/api/auth/login", json!({ "email": "alice@example.com", "password": "secret123" })).await;
+    let session = login.body["token"].as_str().unwrap();
+    let request = Request::builder()
+        .method("PATCH")
+        .uri(format!("/api/accounts/{id}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {session}"))
+        .body(Body::from(json!({ "email": "alice@example.org", "current_password": "secret123" }).to_string()))
+        .unwrap();
+    assert_eq!(app.router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    let mail = app.mail(3).await;
+    assert!(mail.contains("To: alice@example.org"), "{mail}");
+    for (uri, body) in [
+        ("/api/auth/verify-email", json!({ "token": verify })),
+        ("/api/auth/password-reset/confirm", json!({ "token": reset, "password": "n3w-pass" })),
+    ] {
+        assert_eq!(app.post(uri, body).await.body["code"], "token_invalid");
+    }
+    let reply = app.post("/api/auth/verify-email", json!({ "token": token(&mail) })).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    assert!(app.is_verified(id).await);
+
+    // So does changing the password.
+    let reply = app.post("/api/auth/password-reset", json!({ "email": "alice@example.org" })).await;
+    assert_eq!(reply.status, StatusCode::ACCEPTED);
+    let reset = token(&app.mail(4).await);
+    app.db.set_password_hash(id, &digest("changed1")).await.unwrap();
+    let reply = app.post("/api/auth/password-reset/confirm", json!({ "token": reset, "password": "n3w-pass" })).await;
+    assert_eq!(reply.body["code"], "token_invalid");
+    assert_eq!(app.password_hash(id).await, digest("changed1"));
+}
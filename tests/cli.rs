@@ -0,0 +1,145 @@
+//! Runs the operator commands against a temporary SQLite file and checks their
+//! output and exit codes.
+
+use app::cli::{self, AdminCommand, CliError, Command};
+use app::config::Config;
+
+struct Instance {
+    config: Config,
+    dir: tempfile::TempDir,
+}
+
+fn instance() -> Instance {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        database_url: format!("sqlite://{}", dir.path().join("app.db").display()),
+        backup: app::config::BackupSettings { dir: dir.path().join("backups"), ..Default::default() },
+        ..Default::default()
+    };
+    Instance { config, dir }
+}
+
+fn parse(args: &[&str]) -> Result<AdminCommand, CliError> {
+    match cli::parse_args(args.iter().map(|a| a.to_string()))?.command {
+        Command::Admin(command) => Ok(command),
+        _ => panic!("{args:?} is not an admin command"),
+    }
+}
+
+impl Instance {
+    async fn run(&self, args: &[&str], input: &str) -> Result<String, CliError> {
+        let mut out = Vec::new();
+        cli::run(parse(args)?, &self.config, &mut input.as_bytes(), &mut out).await?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let code = |args: &[&str]| cli::parse_args(args.iter().map(|a| a.to_string())).err().map(|e| e.code());
+    assert!(matches!(cli::parse_args(Vec::new()).unwrap().command, Command::Serve));
+    assert_eq!(code(&["frobnicate"]), Some(CliError::USAGE));
+    assert_eq!(code(&["create-account", "only-name"]), Some(CliError::USAGE));
+    assert_eq!(code(&["export-group", "0"]), Some(CliError::USAGE));
+    assert_eq!(code(&["export-group", "1", "--format", "xml"]), Some(CliError::USAGE));
+    assert_eq!(code(&["list-groups", "--format", "csv"]), Some(CliError::USAGE));
+    assert_eq!(code(&["--config"]), Some(CliError::USAGE));
+    assert_eq!(code(&["--verbose"]), Some(CliError::USAGE));
+}
+
+#[tokio::test]
+async fn accounts_and_passwords() {
+    let app = instance();
+    assert_eq!(app.run(&["migrate"], "").await.unwrap(), "schema version 4\n");
+
+    let id = app.run(&["create-account", "Dave", "dave@example.com"], "secret1\n").await.unwrap();
+    assert_eq!(id.trim(), "1");
+    let dup = app.run(&["create-account", "Dave", "dave@example.com"], "secret1\n").await.unwrap_err();
+    assert_eq!(dup.code(), CliError::INVALID);
+    let short = app.run(&["create-account", "Eve", "eve@example.com"], "123\n").await.unwrap_err();
+    assert_eq!(short.code(), CliError::INVALID);
+    let no_password = app.run(&["create-account", "Eve", "eve@example.com"], "").await.unwrap_err();
+    assert_eq!(no_password.code(), CliError::INVALID);
+
+    let db = app::db::Db::init(&app.config.database_url).await.unwrap();
+    let before = db.get_account(1).await.unwrap().unwrap().password_hash;
+    assert_eq!(app.run(&["reset-password", "dave@example.com"], "another1\n").await.unwrap(), "1\n");
+    assert_eq!(app.run(&["reset-password", "1"], "another2\n").await.unwrap(), "1\n");
+    let after = db.get_account(1).await.unwrap().unwrap().password_hash;
+    assert_ne!(before, after);
+    let missing = app.run(&["reset-password", "nobody@example.com"], "another1\n").await.unwrap_err();
+    assert_eq!(missing.code(), CliError::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn seed_list_and_export() {
+    let app = instance();
+    let seeded = app.run(&["seed-demo"], "").await.unwrap();
+    assert!(seeded.starts_with("group 1\n"), "{seeded}");
+    assert_eq!(app.run(&["seed-demo"], "").await.unwrap_err().code(), CliError::INVALID);
+
+    let listed = app.run(&["list-groups"], "").await.unwrap();
+    let rows: Vec<Vec<&str>> = listed.lines().map(|l| l.split('\t').collect()).collect();
+    assert_eq!(rows[0], ["id", "name", "owner", "members", "notes", "created_at"]);
+    assert_eq!(rows[1][..5], ["1", "デモボード", "1", "3", "4"]);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&app.run(&["export-group", "1"], "").await.unwrap()).unwrap();
+    assert_eq!(json["notes"].as_array().unwrap().len(), 4);
+    let csv = app.run(&["export-group", "1", "--format", "csv"], "").await.unwrap();
+    assert_eq!(csv.lines().count(), 5);
+    let missing = app.run(&["export-group", "2"], "").await.unwrap_err();
+    assert_eq!(missing.code(), CliError::NOT_FOUND);
+
+    let path = app.run(&["backup"], "").await.unwrap();
+    assert!(std::path::Path::new(path.trim()).is_file());
+}
+
+#[tokio::test]
+async fn restore_keeps_the_whole_replaced_database() {
+    use sqlx::Connection;
+
+    let app = instance();
+    app.run(&["seed-demo"], "").await.unwrap();
+    let backup = app.run(&["backup"], "").await.unwrap();
+    let backup = std::path::Path::new(backup.trim());
+
+    // A WAL database left behind with its last commit not yet checkpointed,
+    // as after a crash: copied while the connection that wrote it is open.
+    let live = app.dir.path().join("app.db");
+    let crashed = app.dir.path().join("crashed.db");
+    let mut conn = open(&live).await;
+    sqlx::query("PRAGMA journal_mode = WAL").execute(&mut conn).await.unwrap();
+    sqlx::query("INSERT INTO accounts (name, email, password_hash) VALUES ('Late', 'late@example.com', 'x')")
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    std::fs::copy(&live, &crashed).unwrap();
+    std::fs::copy(live.with_extension("db-wal"), crashed.with_extension("db-wal")).unwrap();
+    conn.close().await.unwrap();
+    let url = format!("sqlite://{}", crashed.display());
+
+    let mut writer = open(&crashed).await;
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut writer).await.unwrap();
+    let refused = app::backup::restore(&url, backup).await.unwrap_err();
+    assert!(format!("{refused:#}").contains("is in use"), "{refused:#}");
+    sqlx::query("ROLLBACK").execute(&mut writer).await.unwrap();
+    writer.close().await.unwrap();
+
+    let previous = app::backup::restore(&url, backup).await.unwrap().unwrap();
+    let late = |path: std::path::PathBuf| async move {
+        let mut conn = open(&path).await;
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM accounts WHERE name = 'Late'")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        count
+    };
+    assert_eq!(late(previous).await, 1);
+    assert_eq!(late(crashed).await, 0);
+}
+
+async fn open(path: &std::path::Path) -> sqlx::SqliteConnection {
+    use sqlx::ConnectOptions;
+    sqlx::sqlite::SqliteConnectOptions::new().filename(path).connect().await.unwrap()
+}
@@ -0,0 +1,276 @@
+//! Drives a real server over HTTP through `api-client`, so the client and the
+//! routes are checked against each other.
+
+mod common;
+
+use api_client::types::{
+    BatchOpRequest, BatchRequest, ChangePasswordRequest, CreateAccountRequest, CreateGroupRequest,
+    CreateNoteRequest, JoinGroupRequest, NoteListParams, PageParams, UpdateAccountRequest,
+    UpdateNoteContentRequest, UpdateNotePositionRequest, UserLookupParams, UserProfile,
+};
+use api_client::{Client, Error, StatusCode};
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::telemetry;
+use common::TestDb;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+struct TestServer {
+    client: Client,
+    // Keep the database and backup dir alive for the test.
+    _db: TestDb,
+    _dir: tempfile::TempDir,
+}
+
+async fn spawn_server() -> TestServer {
+    serve(TestDb::new().await, tempfile::tempdir().unwrap()).await
+}
+
+/// On a SQLite file, which unlike the other databases can be backed up.
+async fn spawn_file_server() -> TestServer {
+    let dir = tempfile::tempdir().unwrap();
+    let db = TestDb::sqlite(&format!("sqlite://{}", dir.path().join("app.db").display())).await;
+    serve(db, dir).await
+}
+
+async fn serve(db: TestDb, dir: tempfile::TempDir) -> TestServer {
+    let app = api::routes(AppState {
+        db: db.clone(),
+        database_url: db.url.clone(),
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        backups: BackupConfig { dir: dir.path().join("backups"), interval: None, retention: 2 },
+        metrics: Default::default(),
+        rate_limiter: Default::default(),
+        limits: Default::default(),
+        mail: Default::default(),
+        llm_endpoint: None,
+    });
+    let app = telemetry::apply(app);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    TestServer { client: Client::new(format!("http://{addr}")), _db: db, _dir: dir }
+}
+
+fn api_error(result: Result<impl std::fmt::Debug, Error>) -> api_client::ApiError {
+    match result {
+        Err(Error::Api(e)) => e,
+        other => panic!("expected an API error, got {other:?}"),
+    }
+}
+
+async fn create_account(client: &Client, name: &str) -> i64 {
+    client
+        .create_account(&CreateAccountRequest {
+            name: name.to_string(),
+            email: format!("{name}@example.com"),
+            password: "secret123".to_string(),
+            locale: None,
+        })
+        .await
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn accounts_and_groups() {
+    let server = spawn_server().await;
+    let client = &server.client;
+
+    let alice = create_account(client, "alice").await;
+    let bob = create_account(client, "bob").await;
+    let err = api_error(client.list_accounts(&PageParams::default()).await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "admin_required"));
+    let admin = client.clone().with_admin_token(ADMIN_TOKEN);
+    let accounts = admin.list_accounts(&PageParams::default()).await.unwrap();
+    assert_eq!(accounts.accounts.len(), 2);
+
+    let err = api_error(
+        client
+            .create_account(&CreateAccountRequest {
+                name: "carol".to_string(),
+                email: "carol".to_string(),
+                password: "secret123".to_string(),
+                locale: Some("en".to_string()),
+            })
+            .await,
+    );
+    assert_eq!(err.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(err.code, "email_invalid");
+    assert_eq!(err.fields.len(), 1);
+    assert_eq!(err.fields[0].field, "email");
+
+    let group = client
+        .create_group(&CreateGroupRequest { group_name: "planning".to_string(), created_by: alice })
+        .await
+        .unwrap();
+    assert_eq!(client.get_group(group.id).await.unwrap(), group);
+    client
+        .add_user_to_group(group.id, &JoinGroupRequest { user_id: bob, role: None })
+        .await
+        .unwrap();
+
+    let members = client.list_group_members(group.id, &PageParams::default()).await.unwrap();
+    let roles: Vec<_> = members.members.iter().map(|m| (m.user_id, m.role.as_str())).collect();
+    assert_eq!(roles, [(alice, "owner"), (bob, "member")]);
+    let groups = client.list_groups_for_user(bob).await.unwrap();
+    assert_eq!(groups.groups.len(), 1);
+    assert_eq!(groups.groups[0].role, "member");
+
+    let err = api_error(client.get_group(group.id + 100).await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::NOT_FOUND, "group_not_found"));
+    assert!(err.request_id.is_some_and(|id| !id.is_empty()));
+    let english = client.clone().with_language("en");
+    let err = api_error(english.get_group(group.id + 100).await);
+    assert_eq!((err.code.as_str(), err.message.as_str()), ("group_not_found", "The group was not found"));
+
+    client.request_password_reset("nobody@example.com").await.unwrap();
+    client.resend_verification("bob@example.com").await.unwrap();
+    let err = api_error(client.verify_email("not-a-token").await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::BAD_REQUEST, "token_invalid"));
+
+    let login = client.login("bob@example.com", "secret123").await.unwrap();
+    assert_eq!(login.account.id, bob);
+    let bob_session = client.clone().with_session_token(&login.token);
+    assert_eq!(bob_session.current_account().await.unwrap().name, "bob");
+    let found = bob_session
+        .lookup_users(&UserLookupParams { q: Some("ali".to_string()), ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(found.users, [UserProfile { id: alice, name: "alice".to_string() }]);
+    let err = api_error(client.current_account().await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "login_required"));
+    bob_session.logout().await.unwrap();
+    let err = api_error(bob_session.current_account().await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "session_invalid"));
+
+    let err = api_error(
+        client.update_account(bob, &UpdateAccountRequest { name: Some("mallory".to_string()), ..Default::default() }).await,
+    );
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "login_required"));
+    let bob_session = client.clone().with_session_token(client.login("bob@example.com", "secret123").await.unwrap().token);
+    let renamed = bob_session
+        .update_account(bob, &UpdateAccountRequest { name: Some("robert".to_string()), ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(renamed.name, "robert");
+    let change = ChangePasswordRequest { current_password: "secret123".to_string(), new_password: "n3w-pass".to_string() };
+    bob_session.change_password(bob, &change).await.unwrap();
+    // The change ended the session.
+    let err = api_error(bob_session.change_password(bob, &change).await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "session_invalid"));
+    let bob_session = client.clone().with_session_token(client.login("bob@example.com", "n3w-pass").await.unwrap().token);
+    let err = api_error(bob_session.change_password(bob, &change).await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::FORBIDDEN, "password_incorrect"));
+    let alice_session = client.clone().with_session_token(client.login("alice@example.com", "secret123").await.unwrap().token);
+    let removal = alice_session.delete_account(alice, "secret123").await.unwrap();
+    assert_eq!((removal.transferred_groups, removal.deleted_groups), (vec![group.id], vec![]));
+    assert_eq!(client.get_group(group.id).await.unwrap().created_by, bob);
+}
+
+#[tokio::test]
+async fn notes_sync_and_batch() {
+    let server = spawn_server().await;
+    let client = &server.client;
+    let alice = create_account(client, "alice").await;
+    let group = client
+        .create_group(&CreateGroupRequest { group_name: "board".to_string(), created_by: alice })
+        .await
+        .unwrap();
+
+    let initial = client.note_changes(group.id, None).await.unwrap();
+    assert!(initial.notes.is_empty());
+
+    let first = client
+        .create_note(
+            group.id,
+            &CreateNoteRequest { title: Some("first".to_string()), x: 10.0, y: 20.0, ..Default::default() },
+        )
+        .await
+        .unwrap()
+        .id;
+    let second = client
+        .create_note(
+            group.id,
+            &CreateNoteRequest { color: Some("pink".to_string()), x: 30.0, y: 40.0, ..Default::default() },
+        )
+        .await
+        .unwrap()
+        .id;
+
+    let pink = client
+        .list_notes(group.id, &NoteListParams { color: Some("pink".to_string()), ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(pink.notes.iter().map(|n| n.id).collect::<Vec<_>>(), [second]);
+
+    client
+        .update_note_position(first, &UpdateNotePositionRequest { x: 50.0, y: 60.0, ..Default::default() })
+        .await
+        .unwrap();
+    client
+        .update_note_content(
+            second,
+            &UpdateNoteContentRequest { title: Some("renamed".to_string()), ..Default::default() },
+        )
+        .await
+        .unwrap();
+    let since = client.note_changes(group.id, None).await.unwrap().cursor;
+    client.delete_note(second).await.unwrap();
+    let changes = client.note_changes(group.id, Some(&since)).await.unwrap();
+    assert!(changes.notes.is_empty());
+    assert_eq!(changes.deleted, [second]);
+    assert_eq!(api_error(client.delete_note(second).await).code, "note_not_found");
+
+    let rejected = client
+        .batch_notes(
+            group.id,
+            &BatchRequest {
+                ops: vec![
+                    BatchOpRequest::Delete { note_id: first },
+                    BatchOpRequest::Delete { note_id: second },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+    assert!(!rejected.applied);
+    assert_eq!(rejected.results[1].error.as_ref().unwrap().code, "note_not_found");
+
+    let applied = client
+        .batch_notes(
+            group.id,
+            &BatchRequest {
+                ops: vec![
+                    BatchOpRequest::Create(CreateNoteRequest { x: 1.0, y: 2.0, ..Default::default() }),
+                    BatchOpRequest::UpdatePosition {
+                        note_id: first,
+                        x: 0.0,
+                        y: 0.0,
+                        width: None,
+                        height: None,
+                        z_index: Some(5),
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+    assert!(applied.applied);
+
+    assert_eq!(client.clear_notes(group.id).await.unwrap().removed, 2);
+}
+
+#[tokio::test]
+async fn admin_backups_need_token() {
+    let server = spawn_file_server().await;
+
+    let err = api_error(server.client.list_backups().await);
+    assert_eq!((err.status, err.code.as_str()), (StatusCode::UNAUTHORIZED, "admin_required"));
+
+    let admin = server.client.clone().with_admin_token(ADMIN_TOKEN);
+    let backup = admin.create_backup().await.unwrap();
+    let listed = admin.list_backups().await.unwrap();
+    assert_eq!(listed.backups, [backup]);
+}
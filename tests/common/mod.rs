@@ -0,0 +1,96 @@
+//! The database each integration test runs on. By default a fresh
+//! `sqlite::memory:` one; with `TEST_DATABASE_URL` pointing at a Postgres
+//! server (for example `postgres://postgres@localhost:5432/postgres`), a
+//! database of its own created through that connection and dropped again
+//! when the test ends.
+
+// Each test binary compiles this module and uses only part of it.
+#![allow(dead_code)]
+
+use app::db::Db;
+use sqlx::{Connection, PgConnection};
+
+pub struct TestDb {
+    db: Db,
+    /// What the app under test is configured with (`AppState::database_url`).
+    pub url: String,
+    /// The server connection and database name to drop, on Postgres.
+    postgres: Option<(String, String)>,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        match std::env::var("TEST_DATABASE_URL") {
+            Ok(server) if !server.is_empty() => Self::postgres(server).await,
+            _ => Self::sqlite("sqlite::memory:").await,
+        }
+    }
+
+    /// SQLite whatever `TEST_DATABASE_URL` says, for the features only it
+    /// has (file backups and restore).
+    pub async fn sqlite(url: &str) -> Self {
+        let db = Db::init(url).await.unwrap();
+        Self { db, url: url.to_string(), postgres: None }
+    }
+
+    async fn postgres(server: String) -> Self {
+        let name = format!("app_test_{}", uuid::Uuid::new_v4().simple());
+        let mut conn = PgConnection::connect(&server)
+            .await
+            .unwrap_or_else(|e| panic!("TEST_DATABASE_URL: {e}"));
+        sqlx::query(&format!("CREATE DATABASE \"{name}\"")).execute(&mut conn).await.unwrap();
+        conn.close().await.unwrap();
+        let url = with_database(&server, &name);
+        let db = Db::init(&url).await.unwrap();
+        Self { db, url, postgres: Some((server, name)) }
+    }
+
+    pub fn is_postgres(&self) -> bool {
+        self.postgres.is_some()
+    }
+}
+
+impl std::ops::Deref for TestDb {
+    type Target = Db;
+
+    fn deref(&self) -> &Db {
+        &self.db
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let Some((server, name)) = self.postgres.take() else {
+            return;
+        };
+        // The test's own runtime is still running this drop and cannot block
+        // on another future, so the cleanup gets a thread and runtime of its
+        // own. FORCE closes the connections the app's pool still holds.
+        let dropped = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(async {
+                let mut conn = PgConnection::connect(&server).await?;
+                sqlx::query(&format!("DROP DATABASE IF EXISTS \"{name}\" WITH (FORCE)")).execute(&mut conn).await?;
+                anyhow::Ok(())
+            })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("failed to drop a test database: {dropped:?}");
+        }
+    }
+}
+
+/// `url` with its database name replaced, keeping any query string.
+fn with_database(url: &str, name: &str) -> String {
+    let (base, query) = url.split_once('?').map_or((url, None), |(base, query)| (base, Some(query)));
+    let host_start = base.find("://").map_or(0, |i| i + 3);
+    let base = match base[host_start..].find('/') {
+        Some(slash) => &base[..host_start + slash],
+        None => base,
+    };
+    match query {
+        Some(query) => format!("{base}/{name}?{query}"),
+        None => format!("{base}/{name}"),
+    }
+}
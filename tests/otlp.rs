@@ -0,0 +1,79 @@
+//! Span export over OTLP/HTTP, against a stand-in collector that just keeps
+//! the posted payloads. Run with `cargo test --features otlp`.
+#![cfg(feature = "otlp")]
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::db::Db;
+use app::telemetry;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+type Received = Arc<Mutex<Vec<Bytes>>>;
+
+/// Accepts `POST /v1/traces` on a free port and returns its URL.
+async fn collector() -> (String, Received) {
+    let received = Received::default();
+    let app = Router::new()
+        .route(
+            "/v1/traces",
+            post(|State(received): State<Received>, body: Bytes| async move {
+                received.lock().unwrap().push(body);
+                StatusCode::OK
+            }),
+        )
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{addr}/v1/traces"), received)
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle.as_bytes())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn request_and_query_spans_reach_the_collector() {
+    let (endpoint, received) = collector().await;
+    let provider = telemetry::otlp_provider(&endpoint, "otlp-test").unwrap();
+    let subscriber = tracing_subscriber::registry().with(telemetry::otlp_layer(&provider));
+
+    {
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let db = Db::init("sqlite::memory:").await.unwrap();
+        let router = telemetry::apply(api::routes(AppState {
+            db,
+            database_url: "sqlite::memory:".to_string(),
+            admin_token: None,
+            backups: BackupConfig { dir: "backups".into(), interval: None, retention: 1 },
+            metrics: Default::default(),
+            rate_limiter: Default::default(),
+            limits: Default::default(),
+            mail: Default::default(),
+            llm_endpoint: None,
+        }));
+        let request = Request::post("/api/groups")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"group_name":"board","created_by":7}"#))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    // Shutting down flushes the batch; it blocks, so keep it off the runtime.
+    tokio::task::spawn_blocking(move || provider.shutdown()).await.unwrap().unwrap();
+
+    let received = received.lock().unwrap();
+    let payload: Vec<u8> = received.iter().flat_map(|b| b.iter().copied()).collect();
+    assert!(!payload.is_empty(), "nothing exported");
+    for expected in ["otlp-test", "request", "/api/groups", "account_id", "get_account", "db.system", "sqlite"] {
+        assert!(contains(&payload, expected), "{expected:?} not exported");
+    }
+}
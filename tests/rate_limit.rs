@@ -0,0 +1,195 @@
+//! Rate limiting as wired into `api::routes`.
+
+mod common;
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::config::{RateLimit, RateLimitConfig};
+use app::rate_limit::RateLimiter;
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::Router;
+use common::TestDb;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// The router and the database it runs on, which lives as long as the test
+/// holds it.
+async fn app(config: RateLimitConfig) -> (Router, TestDb) {
+    let db = TestDb::new().await;
+    let router = api::routes(AppState {
+        db: db.clone(),
+        database_url: db.url.clone(),
+        admin_token: None,
+        backups: BackupConfig { dir: "backups".into(), interval: None, retention: 1 },
+        metrics: Default::default(),
+        rate_limiter: Arc::new(RateLimiter::new(&config)),
+        limits: Default::default(),
+        mail: Default::default(),
+        llm_endpoint: None,
+    });
+    (router, db)
+}
+
+fn tight() -> RateLimitConfig {
+    RateLimitConfig {
+        default: RateLimit { per_minute: 60, burst: 3 },
+        auth: RateLimit { per_minute: 1, burst: 2 },
+        ..RateLimitConfig::default()
+    }
+}
+
+/// Sends a request as if it came from `ip`.
+async fn send(router: &Router, method: Method, uri: &str, ip: [u8; 4], body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+    send_as(router, method, uri, ip, None, body).await
+}
+
+/// [`send`] with an optional session token.
+async fn send_as(
+    router: &Router,
+    method: Method,
+    uri: &str,
+    ip: [u8; 4],
+    session: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, HeaderMap, Value) {
+    let mut builder = Request::builder().method(method).uri(uri).header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = session {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let mut request = builder.body(body.map_or(Body::empty(), |b| Body::from(b.to_string()))).unwrap();
+    request.extensions_mut().insert(ConnectInfo(SocketAddr::from((ip, 40000))));
+    let response = router.clone().oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn signup(n: usize) -> Option<Value> {
+    Some(json!({ "name": format!("user{n}"), "email": format!("user{n}@example.com"), "password": "secret123" }))
+}
+
+#[tokio::test]
+async fn auth_bucket_is_stricter_and_per_client() {
+    let (router, _db) = app(tight()).await;
+    let alice = [192, 0, 2, 1];
+    for n in 0..2 {
+        let (status, _, _) = send(&router, Method::POST, "/api/accounts", alice, signup(n)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, headers, body) = send(&router, Method::POST, "/api/accounts", alice, signup(2)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+    let retry_after: u64 = headers[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after), "{retry_after}");
+
+    // Reads use the default bucket, and other clients their own.
+    let (status, _, _) = send(&router, Method::GET, "/api/accounts/1/groups", alice, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = send(&router, Method::POST, "/api/accounts", [192, 0, 2, 2], signup(3)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn default_bucket_spares_probes() {
+    let (router, _db) = app(tight()).await;
+    let ip = [198, 51, 100, 7];
+    for _ in 0..3 {
+        assert_eq!(send(&router, Method::GET, "/api/groups/1", ip, None).await.0, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(send(&router, Method::GET, "/api/groups/1", ip, None).await.0, StatusCode::TOO_MANY_REQUESTS);
+    for _ in 0..10 {
+        assert_eq!(send(&router, Method::GET, "/healthz", ip, None).await.0, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn signed_in_clients_are_keyed_by_account() {
+    let (router, _db) = app(tight()).await;
+    let mut sessions = Vec::new();
+    for n in 0..2u8 {
+        let ip = [192, 0, 2, 10 + n];
+        assert_eq!(send(&router, Method::POST, "/api/accounts", ip, signup(n.into())).await.0, StatusCode::OK);
+        let login = json!({ "email": format!("user{n}@example.com"), "password": "secret123" });
+        let (status, _, body) = send(&router, Method::POST, "/api/auth/login", ip, Some(login)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        sessions.push(body["token"].as_str().unwrap().to_string());
+    }
+    let office = [198, 51, 100, 1];
+    let get = |ip: [u8; 4], session: Option<&str>| {
+        let router = router.clone();
+        let session = session.map(str::to_string);
+        async move { send_as(&router, Method::GET, "/api/groups/1", ip, session.as_deref(), None).await.0 }
+    };
+
+    for _ in 0..3 {
+        assert_eq!(get(office, Some(&sessions[0])).await, StatusCode::NOT_FOUND);
+    }
+    // The account's bucket follows it to another address...
+    assert_eq!(get([203, 0, 113, 9], Some(&sessions[0])).await, StatusCode::TOO_MANY_REQUESTS);
+    // ...and others behind the same address keep their own.
+    assert_eq!(get(office, Some(&sessions[1])).await, StatusCode::NOT_FOUND);
+    assert_eq!(get(office, None).await, StatusCode::NOT_FOUND);
+    // An invalid token counts against the address.
+    assert_eq!(get(office, Some("not-a-session")).await, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn rejected_requests_skip_the_session_lookup() {
+    let (router, db) = app(tight()).await;
+    let ip = [192, 0, 2, 20];
+    assert_eq!(send(&router, Method::POST, "/api/accounts", ip, signup(0)).await.0, StatusCode::OK);
+    let login = json!({ "email": "user0@example.com", "password": "secret123" });
+    let (_, _, body) = send(&router, Method::POST, "/api/auth/login", ip, Some(login)).await;
+    let session = body["token"].as_str().unwrap();
+    for _ in 0..3 {
+        let (status, _, _) = send_as(&router, Method::GET, "/api/groups/1", ip, Some(session), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // With the database gone the account's bucket still applies, from any
+    // address, and the rejection needs no query.
+    db.close().await.unwrap();
+    let (status, headers, body) = send_as(&router, Method::GET, "/api/groups/1", [203, 0, 113, 5], Some(session), None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "{body}");
+    assert_eq!(headers[header::CONTENT_LANGUAGE], "ja");
+}
+
+#[tokio::test]
+async fn forwarded_for_only_when_trusted() {
+    let forwarded = |router: Router, from: String| async move {
+        let mut request = Request::get("/api/groups/1").header("x-forwarded-for", from).body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 80))));
+        router.oneshot(request).await.unwrap().status()
+    };
+
+    // Behind an untrusted proxy every client shares the proxy's bucket.
+    let (router, _db) = app(tight()).await;
+    for (i, from) in ["203.0.113.1", "203.0.113.2", "203.0.113.3"].into_iter().enumerate() {
+        assert_eq!(forwarded(router.clone(), from.into()).await, StatusCode::NOT_FOUND, "request {i}");
+    }
+    assert_eq!(forwarded(router, "203.0.113.4".into()).await, StatusCode::TOO_MANY_REQUESTS);
+
+    // The proxy appends the address it saw; whatever the client put before
+    // it does not get a fresh bucket.
+    let (router, _db) = app(RateLimitConfig { trust_forwarded_for: true, ..tight() }).await;
+    for spoofed in ["198.51.100.1", "198.51.100.2", "198.51.100.3"] {
+        let from = format!("{spoofed}, 203.0.113.1");
+        assert_eq!(forwarded(router.clone(), from).await, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(forwarded(router.clone(), "198.51.100.4, 203.0.113.1".into()).await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(forwarded(router.clone(), "203.0.113.1".into()).await, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(forwarded(router, "203.0.113.1, 203.0.113.2".into()).await, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn openapi_documents_429() {
+    let (router, _db) = app(RateLimitConfig::default()).await;
+    let (_, _, spec) = send(&router, Method::GET, "/api/openapi.json", [127, 0, 0, 1], None).await;
+    assert!(spec["paths"]["/api/accounts"]["post"]["responses"]["429"].is_object());
+    assert!(spec["paths"]["/api/groups/{id}"]["get"]["responses"]["429"].is_object());
+    assert!(spec["paths"]["/healthz"]["get"]["responses"]["429"].is_null());
+}
@@ -0,0 +1,123 @@
+//! CORS and security headers as wired by `security::apply`.
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::config::Config;
+use app::db::Db;
+use app::security;
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
+async fn app(config: &Config) -> Router {
+    let db = Db::init("sqlite::memory:").await.unwrap();
+    let router = api::routes(AppState {
+        db,
+        database_url: "sqlite::memory:".to_string(),
+        admin_token: None,
+        backups: BackupConfig { dir: "backups".into(), interval: None, retention: 1 },
+        metrics: Default::default(),
+        rate_limiter: Default::default(),
+        limits: Default::default(),
+        mail: Default::default(),
+        llm_endpoint: None,
+    });
+    security::apply(router, config)
+}
+
+async fn send(router: &Router, request: Request<Body>) -> (StatusCode, HeaderMap) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    (response.status(), response.headers().clone())
+}
+
+fn get(uri: &str, origin: Option<&str>) -> Request<Body> {
+    let mut builder = Request::get(uri);
+    if let Some(origin) = origin {
+        builder = builder.header(header::ORIGIN, origin);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+fn allowlist() -> Config {
+    let mut config = Config::default();
+    config.cors.allowed_origins = vec!["https://board.example.com".to_string()];
+    config.cors.allow_credentials = true;
+    config.validate().unwrap();
+    config
+}
+
+#[tokio::test]
+async fn default_headers_on_api_responses() {
+    let router = app(&Config::default()).await;
+    let (status, headers) = send(&router, get("/api/openapi.json", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::REFERRER_POLICY], "strict-origin-when-cross-origin");
+    assert_eq!(headers[header::CONTENT_SECURITY_POLICY], security::DEFAULT_CSP);
+    assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=31536000; includeSubDomains");
+
+    // Errors and unknown routes get them too.
+    let (status, headers) = send(&router, get("/api/groups/999", None)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    let (_, headers) = send(&router, get("/nope", None)).await;
+    assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+}
+
+#[tokio::test]
+async fn docs_page_allows_redoc_cdn() {
+    let router = app(&Config::default()).await;
+    let (status, headers) = send(&router, get("/api/docs", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let csp = headers[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+    assert!(csp.contains("https://cdn.redoc.ly"), "{csp}");
+}
+
+#[tokio::test]
+async fn headers_can_be_disabled() {
+    let mut config = Config::default();
+    config.security.content_security_policy = String::new();
+    config.security.hsts_max_age_secs = 0;
+    let router = app(&config).await;
+    let (_, headers) = send(&router, get("/api/accounts", None)).await;
+    assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+}
+
+#[tokio::test]
+async fn any_origin_by_default() {
+    let router = app(&Config::default()).await;
+    let (_, headers) = send(&router, get("/api/accounts", Some("http://localhost:3000"))).await;
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+}
+
+#[tokio::test]
+async fn allowlisted_origin_with_credentials() {
+    let router = app(&allowlist()).await;
+
+    let (_, headers) = send(&router, get("/api/accounts", Some("https://board.example.com"))).await;
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://board.example.com");
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+    for (uri, method) in [("/api/notes/1", "PATCH"), ("/api/accounts/1/password", "PUT")] {
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri(uri)
+            .header(header::ORIGIN, "https://board.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers) = send(&router, preflight).await;
+        assert_eq!(status, StatusCode::OK, "{method} {uri}");
+        let methods = headers[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        assert!(methods.split(',').any(|m| m.trim() == method), "{method} {uri}: {methods}");
+        assert!(headers[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap().contains("content-type"));
+    }
+
+    let (_, headers) = send(&router, get("/api/accounts", Some("https://evil.example.com"))).await;
+    assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
@@ -0,0 +1,88 @@
+//! Pieces of the shutdown sequence in `main.rs`: stopping the backup
+//! scheduler, draining the mails still being sent and closing the database.
+
+use app::backup::{self, BackupConfig};
+use app::config::MailConfig;
+use app::db::Db;
+use app::mail::{AccountMailer, Mail, Mailer};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Takes `delay` to deliver each mail.
+struct SlowMailer {
+    delay: Duration,
+    sent: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl Mailer for SlowMailer {
+    async fn send(&self, mail: &Mail) -> anyhow::Result<()> {
+        tokio::time::sleep(self.delay).await;
+        self.sent.lock().unwrap().push(mail.subject.clone());
+        Ok(())
+    }
+}
+
+fn slow_mailer(delay: Duration) -> (Arc<SlowMailer>, AccountMailer) {
+    let slow = Arc::new(SlowMailer { delay, sent: Mutex::default() });
+    let mailer = AccountMailer::new(slow.clone(), &MailConfig::default());
+    (slow, mailer)
+}
+
+fn mail(subject: &str) -> Mail {
+    Mail { to: "alice@example.com".into(), subject: subject.into(), body: String::new() }
+}
+
+#[tokio::test]
+async fn scheduler_stops_when_told() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Db::init(&format!("sqlite://{}", dir.path().join("app.db").display())).await.unwrap();
+    let config = BackupConfig {
+        dir: dir.path().join("backups"),
+        interval: Some(Duration::from_secs(3600)),
+        retention: 1,
+    };
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let handle = backup::spawn_scheduler(db.clone(), config, stop_rx).unwrap();
+
+    stop_tx.send(true).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+    assert!(!dir.path().join("backups").exists());
+
+    db.close().await.unwrap();
+    assert!(db.count_notes().await.is_err());
+}
+
+#[tokio::test]
+async fn scheduler_is_off_without_interval() {
+    let db = Db::init("sqlite::memory:").await.unwrap();
+    let config = BackupConfig { dir: "unused".into(), interval: None, retention: 1 };
+    let (_stop_tx, stop_rx) = watch::channel(false);
+    assert!(backup::spawn_scheduler(db, config, stop_rx).is_none());
+}
+
+#[tokio::test]
+async fn mails_being_sent_survive_shutdown() {
+    let (slow, mailer) = slow_mailer(Duration::from_millis(300));
+    mailer.send_later(mail("verify"));
+    mailer.send_later(mail("reset"));
+    assert!(slow.sent.lock().unwrap().is_empty());
+
+    assert!(mailer.drain(Duration::from_secs(5)).await);
+    let mut sent = slow.sent.lock().unwrap().clone();
+    sent.sort();
+    assert_eq!(sent, ["reset", "verify"]);
+}
+
+#[tokio::test]
+async fn mail_drain_gives_up_at_the_timeout() {
+    let (slow, mailer) = slow_mailer(Duration::from_secs(60));
+    mailer.send_later(mail("verify"));
+
+    let started = std::time::Instant::now();
+    assert!(!mailer.drain(Duration::from_millis(100)).await);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(slow.sent.lock().unwrap().is_empty());
+}
@@ -0,0 +1,73 @@
+//! Request IDs as wired by `telemetry::apply`.
+
+use app::api::{self, AppState};
+use app::backup::BackupConfig;
+use app::db::Db;
+use app::telemetry::{self, REQUEST_ID_HEADER};
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn app() -> Router {
+    let db = Db::init("sqlite::memory:").await.unwrap();
+    let router = api::routes(AppState {
+        db,
+        database_url: "sqlite::memory:".to_string(),
+        admin_token: None,
+        backups: BackupConfig { dir: "backups".into(), interval: None, retention: 1 },
+        metrics: Default::default(),
+        rate_limiter: Default::default(),
+        limits: Default::default(),
+        mail: Default::default(),
+        llm_endpoint: None,
+    });
+    telemetry::apply(router)
+}
+
+/// Status, `X-Request-Id` response header and JSON body (`Null` if none).
+async fn get(router: &Router, uri: &str, request_id: Option<&str>) -> (StatusCode, String, Value) {
+    let mut builder = Request::get(uri);
+    if let Some(id) = request_id {
+        builder = builder.header(REQUEST_ID_HEADER, id);
+    }
+    let response = router.clone().oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, id, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn generated_id_is_echoed_in_header_and_error_body() {
+    let router = app().await;
+    let (status, id, body) = get(&router, "/api/groups/999", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(id.len(), 36, "{id}");
+    assert_eq!(body["code"], "group_not_found");
+    assert_eq!(body["request_id"], id.as_str());
+
+    let (_, other, _) = get(&router, "/api/groups/999", None).await;
+    assert_ne!(id, other);
+
+    // Successful responses carry the header but no body field.
+    let (status, ok_id, body) = get(&router, "/api/openapi.json", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!ok_id.is_empty());
+    assert!(body.get("request_id").is_none());
+}
+
+#[tokio::test]
+async fn incoming_id_is_propagated_when_well_formed() {
+    let router = app().await;
+    let (_, id, body) = get(&router, "/api/groups/999", Some("edge-7f3a.42_b")).await;
+    assert_eq!(id, "edge-7f3a.42_b");
+    assert_eq!(body["request_id"], "edge-7f3a.42_b");
+
+    for bad in ["has space", "quote\"d", &"x".repeat(65)] {
+        let (_, id, _) = get(&router, "/api/groups/999", Some(bad)).await;
+        assert_ne!(id, bad);
+        assert_eq!(id.len(), 36);
+    }
+}